@@ -0,0 +1,189 @@
+//! Unsigned 256-bit arithmetic that needs more than the wrapping operators
+//! `U256` provides.
+
+use primitive_types::{U256, U512};
+
+/// `(a + b) % n` computed without overflow, or 0 when `n` is 0 (ADDMOD).
+pub fn addmod(a: U256, b: U256, n: U256) -> U256 {
+    if n.is_zero() {
+        return U256::zero();
+    }
+    reduce(U512::from(a) + U512::from(b), n)
+}
+
+/// `(a * b) % n` computed without overflow, or 0 when `n` is 0 (MULMOD).
+pub fn mulmod(a: U256, b: U256, n: U256) -> U256 {
+    if n.is_zero() {
+        return U256::zero();
+    }
+    reduce(a.full_mul(b), n)
+}
+
+/// `base ^ exponent` modulo 2^256 (EXP). Anything to the power 0 is 1.
+pub fn exp(base: U256, exponent: U256) -> U256 {
+    base.overflowing_pow(exponent).0
+}
+
+/// `value << shift` (SHL). Shifts of 256 or more give 0.
+pub fn shl(shift: U256, value: U256) -> U256 {
+    if shift >= U256::from(256) {
+        return U256::zero();
+    }
+    value << shift.as_usize()
+}
+
+/// `value >> shift` (SHR). Shifts of 256 or more give 0.
+pub fn shr(shift: U256, value: U256) -> U256 {
+    if shift >= U256::from(256) {
+        return U256::zero();
+    }
+    value >> shift.as_usize()
+}
+
+/// Byte `index` of `value`, counting from the most significant byte (BYTE).
+/// Indices of 32 or more give 0.
+pub fn byte(index: U256, value: U256) -> U256 {
+    if index >= U256::from(32) {
+        return U256::zero();
+    }
+    U256::from(value.byte(31 - index.as_usize()))
+}
+
+/// Reduces a 512-bit value modulo a non-zero `n`.
+fn reduce(value: U512, n: U256) -> U256 {
+    let remainder = value % U512::from(n);
+    U256::try_from(remainder).expect("remainder is below the modulus")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use num_bigint::BigUint;
+    use proptest::prelude::*;
+
+    fn to_big(value: U256) -> BigUint {
+        let mut bytes = [0u8; 32];
+        value.to_big_endian(&mut bytes);
+        BigUint::from_bytes_be(&bytes)
+    }
+
+    fn from_big(value: BigUint) -> U256 {
+        U256::from_big_endian(&value.to_bytes_be())
+    }
+
+    fn u256() -> impl Strategy<Value = U256> {
+        prop_oneof![
+            any::<[u8; 32]>().prop_map(|bytes| U256::from_big_endian(&bytes)),
+            any::<u64>().prop_map(U256::from),
+            (0u64..4).prop_map(|delta| U256::MAX - delta),
+        ]
+    }
+
+    #[test]
+    fn zero_modulus_gives_zero() {
+        assert_eq!(addmod(U256::from(5), U256::from(7), U256::zero()), U256::zero());
+        assert_eq!(mulmod(U256::from(5), U256::from(7), U256::zero()), U256::zero());
+        assert_eq!(addmod(U256::MAX, U256::MAX, U256::zero()), U256::zero());
+        assert_eq!(mulmod(U256::MAX, U256::MAX, U256::zero()), U256::zero());
+    }
+
+    #[test]
+    fn overflowing_intermediates() {
+        // (2^256 - 1) * 2 = 2^257 - 2, and 2^256 = 1 (mod 2^256 - 1).
+        assert_eq!(addmod(U256::MAX, U256::MAX, U256::MAX), U256::zero());
+        assert_eq!(addmod(U256::MAX, U256::from(2), U256::from(2)), U256::one());
+        assert_eq!(mulmod(U256::MAX, U256::MAX, U256::from(12)), U256::from(9));
+        assert_eq!(mulmod(U256::MAX, U256::MAX, U256::MAX), U256::zero());
+        assert_eq!(mulmod(U256::MAX, U256::from(2), U256::MAX - 1), U256::from(2));
+    }
+
+    #[test]
+    fn exp_cases() {
+        let two = U256::from(2);
+        let cases = [
+            (U256::zero(), U256::zero(), U256::one()),
+            (U256::MAX, U256::zero(), U256::one()),
+            (U256::zero(), U256::one(), U256::zero()),
+            (U256::zero(), U256::MAX, U256::zero()),
+            (U256::one(), U256::MAX, U256::one()),
+            (U256::from(10), U256::from(2), U256::from(100)),
+            (two, U256::from(255), U256::one() << 255),
+            (two, U256::from(256), U256::zero()),
+            (two, U256::MAX, U256::zero()),
+            (U256::MAX, U256::from(2), U256::one()),
+            (U256::MAX, U256::from(3), U256::MAX),
+            // 3^200 wraps around 2^256.
+            (
+                U256::from(3),
+                U256::from(200),
+                U256::from_dec_str("87795648507191311727083257018345013676806519597779187230292693766092659142817")
+                    .unwrap(),
+            ),
+        ];
+        for (base, exponent, expected) in cases {
+            assert_eq!(exp(base, exponent), expected, "{} ** {}", base, exponent);
+        }
+    }
+
+    #[test]
+    fn shift_cases() {
+        let value = U256::from(0xff) << 248 | U256::one();
+        // Shifts whose low 32 bits look small but whose full value is huge.
+        let wide = (U256::one() << 32) + 1;
+        let cases = [
+            (U256::zero(), value, value, value),
+            (U256::one(), U256::one(), U256::from(2), U256::zero()),
+            (
+                U256::from(4),
+                value,
+                U256::from(0xf) << 252 | U256::from(16),
+                U256::from(0xff) << 244,
+            ),
+            (U256::from(255), U256::one(), U256::one() << 255, U256::zero()),
+            (U256::from(255), value, U256::one() << 255, U256::one()),
+            (U256::from(256), value, U256::zero(), U256::zero()),
+            (U256::from(257), U256::MAX, U256::zero(), U256::zero()),
+            (wide, U256::MAX, U256::zero(), U256::zero()),
+            (U256::one() << 128, U256::MAX, U256::zero(), U256::zero()),
+            (U256::MAX, U256::MAX, U256::zero(), U256::zero()),
+        ];
+        for (shift, value, left, right) in cases {
+            assert_eq!(shl(shift, value), left, "{:x} << {:x}", value, shift);
+            assert_eq!(shr(shift, value), right, "{:x} >> {:x}", value, shift);
+        }
+    }
+
+    #[test]
+    fn byte_cases() {
+        let value = U256::from_big_endian(&(1..=32).collect::<Vec<u8>>());
+        let cases = [
+            (U256::zero(), value, U256::from(1)),
+            (U256::from(15), value, U256::from(16)),
+            (U256::from(31), value, U256::from(32)),
+            (U256::from(32), value, U256::zero()),
+            (U256::from(31), U256::MAX, U256::from(0xff)),
+            (U256::zero(), U256::MAX, U256::from(0xff)),
+            (U256::one() << 64, value, U256::zero()),
+            (U256::MAX, U256::MAX, U256::zero()),
+        ];
+        for (index, value, expected) in cases {
+            assert_eq!(byte(index, value), expected, "byte {:x} of {:x}", index, value);
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn addmod_matches_bigint(a in u256(), b in u256(), n in u256()) {
+            prop_assume!(!n.is_zero());
+            let expected = (to_big(a) + to_big(b)) % to_big(n);
+            prop_assert_eq!(addmod(a, b, n), from_big(expected));
+        }
+
+        #[test]
+        fn mulmod_matches_bigint(a in u256(), b in u256(), n in u256()) {
+            prop_assume!(!n.is_zero());
+            let expected = (to_big(a) * to_big(b)) % to_big(n);
+            prop_assert_eq!(mulmod(a, b, n), from_big(expected));
+        }
+    }
+}
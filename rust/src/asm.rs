@@ -0,0 +1,333 @@
+//! Turns the mnemonic form used by the `asm` field of `evm.json` into
+//! bytecode.
+//!
+//! Besides one instruction per line, as in `PUSH1 0x42`, the source may use:
+//!
+//! - `PUSH value` without a size, which picks the smallest PUSH that fits.
+//!   Zero becomes `PUSH1 0x00`, as PUSH0 only exists from Shanghai onward.
+//! - Hex (`0x2a`) and decimal (`42`) literals.
+//! - `name:` labels, which emit a JUMPDEST. `PUSH name` pushes its offset.
+//! - `#define NAME value` constants, usable wherever a literal is.
+//! - Comments starting with `;` or `//`.
+//!
+//! Mnemonics are case-insensitive, and `KECCAK256` and `PREVRANDAO` are
+//! accepted for `SHA3` and `DIFFICULTY`.
+
+use crate::opcode;
+use primitive_types::U256;
+use std::collections::HashMap;
+use std::fmt;
+
+/// Why source could not be assembled, and the line it happened on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    /// 1-based line number.
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for AsmError {}
+
+const PUSH1: u8 = 0x60;
+const JUMPDEST: u8 = 0x5b;
+
+enum Operand {
+    Value(U256),
+    Label(String),
+}
+
+enum Item {
+    Op(u8),
+    /// A PUSH with its data size, or `None` to pick one.
+    Push {
+        size: Option<usize>,
+        operand: Operand,
+        line: usize,
+    },
+    Label(String),
+}
+
+/// Assembles `source` into bytecode.
+pub fn assemble(source: &str) -> Result<Vec<u8>, AsmError> {
+    let mnemonics = mnemonics();
+    let mut constants: HashMap<String, U256> = HashMap::new();
+    let mut labels: HashMap<String, usize> = HashMap::new();
+    let mut items = Vec::new();
+
+    for (index, text) in source.lines().enumerate() {
+        let line = index + 1;
+        let err = |message: String| AsmError { line, message };
+        let text = strip_comment(text).trim();
+
+        if let Some(rest) = text.strip_prefix("#define") {
+            let mut tokens = rest.split_whitespace();
+            let (Some(name), Some(value), None) = (tokens.next(), tokens.next(), tokens.next()) else {
+                return Err(err("expected `#define NAME value`".to_string()));
+            };
+            check_name(name).map_err(err)?;
+            let value = resolve(value, &constants).map_err(err)?;
+            if constants.insert(name.to_string(), value).is_some() {
+                return Err(err(format!("constant `{}` is defined twice", name)));
+            }
+            continue;
+        }
+
+        let mut tokens = text.split_whitespace();
+        while let Some(token) = tokens.next() {
+            if let Some(name) = token.strip_suffix(':') {
+                check_name(name).map_err(err)?;
+                if labels.insert(name.to_string(), line).is_some() {
+                    return Err(err(format!("label `{}` is defined twice", name)));
+                }
+                items.push(Item::Label(name.to_string()));
+                continue;
+            }
+
+            let upper = token.to_ascii_uppercase();
+            let size = match upper.as_str() {
+                "PUSH" => None,
+                _ => match mnemonics.get(upper.as_str()) {
+                    Some(&opcode) => match opcode::info(opcode).map(|info| info.immediate) {
+                        Some(0) | None => {
+                            items.push(Item::Op(opcode));
+                            continue;
+                        }
+                        size => size,
+                    },
+                    None => return Err(err(format!("unknown instruction `{}`", token))),
+                },
+            };
+            let Some(operand) = tokens.next() else {
+                return Err(err(format!("`{}` needs a value", token)));
+            };
+            let operand = match resolve(operand, &constants) {
+                Ok(value) => {
+                    if let Some(size) = size {
+                        if byte_len(value) > size {
+                            return Err(err(format!("{} does not fit in {}", operand, upper)));
+                        }
+                    }
+                    Operand::Value(value)
+                }
+                Err(_) if is_name(operand) => Operand::Label(operand.to_string()),
+                Err(message) => return Err(err(message)),
+            };
+            items.push(Item::Push { size, operand, line });
+        }
+    }
+
+    for item in &items {
+        if let Item::Push {
+            operand: Operand::Label(name),
+            line,
+            ..
+        } = item
+        {
+            if !labels.contains_key(name) {
+                return Err(AsmError {
+                    line: *line,
+                    message: format!("unknown label or constant `{}`", name),
+                });
+            }
+        }
+    }
+
+    // Label pushes start at one byte and grow until every offset fits.
+    // Sizes only ever grow, so this settles.
+    let mut sizes: Vec<usize> = items
+        .iter()
+        .map(|item| match item {
+            Item::Push { size: Some(size), .. } => *size,
+            Item::Push {
+                operand: Operand::Value(value),
+                ..
+            } => byte_len(*value).max(1),
+            _ => 1,
+        })
+        .collect();
+    let offsets = loop {
+        let offsets = label_offsets(&items, &sizes);
+        let mut grew = false;
+        for (item, size) in items.iter().zip(&mut sizes) {
+            if let Item::Push {
+                size: None,
+                operand: Operand::Label(name),
+                ..
+            } = item
+            {
+                let needed = byte_len(U256::from(offsets[name])).max(1);
+                if needed > *size {
+                    *size = needed;
+                    grew = true;
+                }
+            }
+        }
+        if !grew {
+            break offsets;
+        }
+    };
+
+    let mut code = Vec::new();
+    for (item, &size) in items.iter().zip(&sizes) {
+        match item {
+            Item::Op(opcode) => code.push(*opcode),
+            Item::Label(_) => code.push(JUMPDEST),
+            Item::Push { operand, line, .. } => {
+                let value = match operand {
+                    Operand::Value(value) => *value,
+                    Operand::Label(name) => U256::from(offsets[name]),
+                };
+                if byte_len(value) > size {
+                    return Err(AsmError {
+                        line: *line,
+                        message: format!("offset {:#x} does not fit in PUSH{}", value, size),
+                    });
+                }
+                let mut word = [0u8; 32];
+                value.to_big_endian(&mut word);
+                code.push(PUSH1 + size as u8 - 1);
+                code.extend_from_slice(&word[32 - size..]);
+            }
+        }
+    }
+    Ok(code)
+}
+
+/// Opcodes by upper-case mnemonic, with the newer names of renamed ones.
+fn mnemonics() -> HashMap<&'static str, u8> {
+    let mut mnemonics: HashMap<&'static str, u8> = (0..=255u8)
+        .filter_map(|opcode| opcode::info(opcode).map(|info| (info.name, opcode)))
+        .collect();
+    mnemonics.insert("KECCAK256", 0x20);
+    mnemonics.insert("PREVRANDAO", 0x44);
+    mnemonics
+}
+
+fn label_offsets(items: &[Item], sizes: &[usize]) -> HashMap<String, usize> {
+    let mut offsets = HashMap::new();
+    let mut pc = 0;
+    for (item, size) in items.iter().zip(sizes) {
+        match item {
+            Item::Op(_) => pc += 1,
+            Item::Label(name) => {
+                offsets.insert(name.clone(), pc);
+                pc += 1;
+            }
+            Item::Push { .. } => pc += 1 + size,
+        }
+    }
+    offsets
+}
+
+fn strip_comment(line: &str) -> &str {
+    let end = [line.find(';'), line.find("//")].into_iter().flatten().min();
+    &line[..end.unwrap_or(line.len())]
+}
+
+/// Reads a literal, or the value of a constant defined earlier.
+fn resolve(token: &str, constants: &HashMap<String, U256>) -> Result<U256, String> {
+    if let Some(value) = constants.get(token) {
+        return Ok(*value);
+    }
+    let invalid = || format!("invalid value `{}`", token);
+    if let Some(digits) = token.strip_prefix("0x").or_else(|| token.strip_prefix("0X")) {
+        if digits.is_empty() || digits.len() > 64 {
+            return Err(invalid());
+        }
+        return U256::from_str_radix(digits, 16).map_err(|_| invalid());
+    }
+    if token.starts_with(|c: char| c.is_ascii_digit()) {
+        return U256::from_dec_str(token).map_err(|_| invalid());
+    }
+    Err(invalid())
+}
+
+fn is_name(token: &str) -> bool {
+    token.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && token.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn check_name(name: &str) -> Result<(), String> {
+    match is_name(name) {
+        true => Ok(()),
+        false => Err(format!("invalid name `{}`", name)),
+    }
+}
+
+/// Number of bytes needed to hold `value`.
+fn byte_len(value: U256) -> usize {
+    value.bits().div_ceil(8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_instructions() {
+        let code = assemble("PUSH1 0x01\npush2 0x0203\nADD\nSHA3\nKECCAK256").unwrap();
+        assert_eq!(code, [0x60, 0x01, 0x61, 0x02, 0x03, 0x01, 0x20, 0x20]);
+    }
+
+    #[test]
+    fn literals_and_sizing() {
+        assert_eq!(assemble("PUSH 0").unwrap(), [0x60, 0x00]);
+        assert_eq!(assemble("PUSH 255").unwrap(), [0x60, 0xff]);
+        assert_eq!(assemble("PUSH 256").unwrap(), [0x61, 0x01, 0x00]);
+        assert_eq!(assemble("PUSH2 0x1").unwrap(), [0x61, 0x00, 0x01]);
+        let max = format!("PUSH 0x{}", "ff".repeat(32));
+        assert_eq!(assemble(&max).unwrap(), [&[0x7f][..], &[0xff; 32]].concat());
+    }
+
+    #[test]
+    fn labels_and_constants() {
+        let source = "
+            #define COUNT 3   ; loop this many times
+            PUSH COUNT
+            loop:
+            PUSH1 1
+            SWAP1
+            SUB
+            DUP1
+            PUSH loop         // jumps back while non-zero
+            JUMPI
+        ";
+        let code = assemble(source).unwrap();
+        assert_eq!(code, [0x60, 0x03, 0x5b, 0x60, 0x01, 0x90, 0x03, 0x80, 0x60, 0x02, 0x57]);
+    }
+
+    #[test]
+    fn forward_labels_grow_past_one_byte() {
+        let source = format!("PUSH end\nJUMP\n{}\nend:\nSTOP", "JUMPDEST\n".repeat(300));
+        let code = assemble(&source).unwrap();
+        // PUSH2 0x0130, JUMP, 300 JUMPDESTs, then the label at 304.
+        assert_eq!(code[..4], [0x61, 0x01, 0x30, 0x56]);
+        assert_eq!(code[0x130], JUMPDEST);
+        assert_eq!(code.len(), 0x132);
+    }
+
+    #[test]
+    fn errors_carry_line_numbers() {
+        let error = |source: &str| assemble(source).unwrap_err();
+        assert_eq!(error("ADD\nFOO").line, 2);
+        assert_eq!(error("PUSH1 0x100").to_string(), "line 1: 0x100 does not fit in PUSH1");
+        assert_eq!(error("STOP\n\nPUSH1").to_string(), "line 3: `PUSH1` needs a value");
+        assert_eq!(error("PUSH nowhere").message, "unknown label or constant `nowhere`");
+        assert_eq!(error("a:\na:").line, 2);
+        assert_eq!(error("#define X").line, 1);
+        assert_eq!(error("PUSH 0xzz").message, "invalid value `0xzz`");
+    }
+
+    #[test]
+    fn round_trips_the_disassembler() {
+        let code = hex::decode("6001600201600052602060006000f05a3a5f").unwrap();
+        let asm = crate::render_asm(&crate::disassemble(&code));
+        assert_eq!(assemble(&asm).unwrap(), code);
+    }
+}
@@ -0,0 +1,701 @@
+//! An interactive step debugger for EVM bytecode.
+//!
+//! Loads code, with optional calldata and the `tx`, `block` and `state` of
+//! an `evm.json` test, and runs it under commands read from stdin: stepping,
+//! stepping over calls, breakpoints on a pc or an opcode, watched storage
+//! slots, and views of the stack, memory, storage, return data and call
+//! stack. `cargo run --bin debug -- <code>` starts it, and `help` lists the
+//! commands.
+
+use clap::Parser;
+use evm::{
+    disassemble, execute, opcode_info, Address, Block, Bytes, CallContext, CallInputs, CallOutcome, CreateInputs,
+    CreateOutcome, Env, FrameState, Inspector, Instruction, SpecId, State, StateHost, StepControl, Txn,
+};
+use primitive_types::U256;
+use serde::de::DeserializeOwned;
+use std::collections::BTreeMap;
+use std::io::{self, BufRead, Write};
+use std::process::ExitCode;
+use std::str::FromStr;
+
+#[derive(Parser)]
+struct Args {
+    /// The code, in hex or as a file holding hex.
+    code: String,
+    /// Calldata, in hex. Replaces the `data` of `--tx`.
+    #[arg(long, value_name = "HEX")]
+    calldata: Option<Bytes>,
+    /// The transaction, as the `tx` of an evm.json test.
+    #[arg(long, value_name = "FILE")]
+    tx: Option<String>,
+    /// The block, as the `block` of an evm.json test.
+    #[arg(long, value_name = "FILE")]
+    block: Option<String>,
+    /// The prestate, as the `state` of an evm.json test.
+    #[arg(long, value_name = "FILE")]
+    state: Option<String>,
+    #[arg(long, value_name = "FORK", default_value_t = SpecId::default())]
+    fork: SpecId,
+}
+
+const HELP: &str = "\
+s, step [N]           run N instructions (default 1)
+n, next               run to the next instruction of this frame, stepping over calls
+c, continue           run to the next breakpoint or watched change
+b, break PC|OPCODE    stop before the instruction at PC, or before every OPCODE
+w, watch SLOT [ADDR]  stop after SLOT of ADDR (default: any account) changes
+d, delete N           remove breakpoint or watch N
+i, info               list breakpoints and watches
+l, list               disassemble around the current instruction
+st, stack             show the stack, top first
+m, memory [OFF [LEN]] hex dump of memory
+sto, storage [ADDR]   storage of ADDR (default: the current account)
+rd, returndata        return data of the last call
+bt, callstack         the call stack, innermost first
+q, quit               stop debugging";
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    match run(args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            ExitCode::from(2)
+        }
+    }
+}
+
+fn run(args: Args) -> Result<(), String> {
+    let code = read_code(&args.code)?;
+    let mut tx: Option<Txn> = args.tx.as_deref().map(read_json).transpose()?;
+    if let Some(calldata) = args.calldata {
+        tx.get_or_insert_with(Txn::default).data = calldata;
+    }
+    let block: Option<Block> = args.block.as_deref().map(read_json).transpose()?;
+    let mut state: State = args.state.as_deref().map(read_json).transpose()?.unwrap_or_default();
+
+    let (default_tx, default_block) = (Txn::default(), Block::default());
+    let tx = tx.as_ref().unwrap_or(&default_tx);
+    let env = Env::from_fixture(tx, block.as_ref().unwrap_or(&default_block), args.fork);
+    let context = CallContext {
+        code: &code,
+        input: &tx.data,
+        address: tx.to.unwrap_or_default(),
+        caller: tx.from,
+        value: tx.value,
+        gas: env.tx.gas_limit,
+        is_static: false,
+        depth: 0,
+    };
+
+    println!("Loaded {} bytes of code. Type `help` for commands.", code.len());
+    let mut debugger = Debugger::new(state.clone(), io::stdin().lock(), io::stdout());
+    let mut host = StateHost::new(&mut state, &env).with_inspector(&mut debugger);
+    let result = execute(&context, &mut host);
+    host.finish();
+
+    let exit = match (result.success, result.halt) {
+        (true, _) => "success".to_string(),
+        (false, Some(halt)) => halt.to_string(),
+        (false, None) => "revert".to_string(),
+    };
+    println!("Finished: {}", exit);
+    println!("Gas used: {}", context.gas - result.gas_left);
+    println!("Output:   {:?}", Bytes::from(result.output));
+    print_stack(&mut io::stdout(), &result.stack).map_err(|err| err.to_string())
+}
+
+/// Where to stop next, besides breakpoints.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Mode {
+    /// After this many more instructions.
+    Step(usize),
+    /// At the next instruction at or above this depth.
+    Next(usize),
+    Continue,
+}
+
+#[derive(Debug, PartialEq)]
+enum Breakpoint {
+    Pc(usize),
+    Opcode(u8),
+    /// A storage slot, of one account or of any.
+    Watch(Option<Address>, U256),
+}
+
+/// A command typed at the prompt.
+#[derive(Debug, PartialEq)]
+enum Command {
+    Step(usize),
+    Next,
+    Continue,
+    /// Sets a breakpoint or a watch.
+    Break(Breakpoint),
+    Delete(usize),
+    Info,
+    List,
+    Stack,
+    Memory {
+        offset: usize,
+        len: Option<usize>,
+    },
+    Storage(Option<Address>),
+    ReturnData,
+    CallStack,
+    Help,
+    Quit,
+}
+
+impl FromStr for Command {
+    type Err = String;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let (&command, rest) = words.split_first().ok_or("empty command")?;
+        let command = match command {
+            "s" | "step" => Command::Step(match rest.first() {
+                Some(count) => count.parse().map_err(|_| format!("invalid count {:?}", count))?,
+                None => 1,
+            }),
+            "n" | "next" => Command::Next,
+            "c" | "continue" => Command::Continue,
+            "b" | "break" => {
+                let target = rest.first().ok_or("usage: break PC|OPCODE")?;
+                Command::Break(match parse_number(target) {
+                    Some(pc) => Breakpoint::Pc(pc.low_u64() as usize),
+                    None => Breakpoint::Opcode(parse_opcode(target)?),
+                })
+            }
+            "w" | "watch" => {
+                let slot = rest
+                    .first()
+                    .and_then(|slot| parse_number(slot))
+                    .ok_or("usage: watch SLOT [ADDR]")?;
+                let address = rest.get(1).map(|address| evm::parse_address(address)).transpose()?;
+                Command::Break(Breakpoint::Watch(address, slot))
+            }
+            "d" | "delete" => Command::Delete(
+                rest.first()
+                    .and_then(|index| index.parse().ok())
+                    .ok_or("usage: delete N")?,
+            ),
+            "i" | "info" => Command::Info,
+            "l" | "list" => Command::List,
+            "st" | "stack" => Command::Stack,
+            "m" | "memory" => Command::Memory {
+                offset: rest.first().map(|offset| parse_usize(offset)).transpose()?.unwrap_or(0),
+                len: rest.get(1).map(|len| parse_usize(len)).transpose()?,
+            },
+            "sto" | "storage" => Command::Storage(rest.first().map(|address| evm::parse_address(address)).transpose()?),
+            "rd" | "returndata" => Command::ReturnData,
+            "bt" | "callstack" => Command::CallStack,
+            "h" | "help" => Command::Help,
+            "q" | "quit" => Command::Quit,
+            _ => return Err(format!("unknown command {:?}, try `help`", command)),
+        };
+        Ok(command)
+    }
+}
+
+/// A frame of the call stack.
+struct Frame {
+    /// CALL, CREATE2 and so on, or TX for the outermost frame.
+    kind: &'static str,
+    address: Address,
+    /// The frame's code, disassembled at its first instruction.
+    instructions: Vec<Instruction>,
+    pc: usize,
+}
+
+/// Reads commands from `input` and prints to `output`.
+struct Debugger<R, W> {
+    mode: Mode,
+    breakpoints: Vec<Breakpoint>,
+    /// The state before execution, which storage views start from.
+    base: State,
+    /// Storage writes of each open frame. A frame's writes move to its
+    /// caller when it succeeds and are dropped when it fails.
+    journal: Vec<Vec<(Address, U256, U256)>>,
+    frames: Vec<Frame>,
+    /// A watched slot that changed, reported at the next stop.
+    changed: Option<String>,
+    input: R,
+    output: W,
+    /// Ends the session, at `quit` or at the end of the input.
+    quit: fn() -> !,
+}
+
+impl<R: BufRead, W: Write> Debugger<R, W> {
+    fn new(base: State, input: R, output: W) -> Self {
+        Debugger {
+            mode: Mode::Step(1),
+            breakpoints: Vec::new(),
+            base,
+            journal: vec![Vec::new()],
+            frames: vec![Frame {
+                kind: "TX",
+                address: Address::zero(),
+                instructions: Vec::new(),
+                pc: 0,
+            }],
+            changed: None,
+            input,
+            output,
+            quit: || std::process::exit(0),
+        }
+    }
+
+    /// Whether execution stops before the instruction in `frame`.
+    fn should_stop(&mut self, frame: &FrameState) -> bool {
+        let at_breakpoint = self.breakpoints.iter().any(|breakpoint| match *breakpoint {
+            Breakpoint::Pc(pc) => pc == frame.pc,
+            Breakpoint::Opcode(opcode) => opcode == frame.opcode,
+            Breakpoint::Watch(..) => false,
+        });
+        let by_mode = match self.mode {
+            Mode::Step(ref mut count) => {
+                *count = count.saturating_sub(1);
+                *count == 0
+            }
+            Mode::Next(depth) => frame.depth <= depth,
+            Mode::Continue => false,
+        };
+        at_breakpoint || by_mode || self.changed.is_some()
+    }
+
+    /// Reads and runs commands until one resumes execution.
+    fn prompt(&mut self, frame: &FrameState) -> io::Result<()> {
+        if let Some(change) = self.changed.take() {
+            writeln!(self.output, "{}", change)?;
+        }
+        writeln!(self.output, "{}", self.location(frame))?;
+        loop {
+            write!(self.output, "(debug) ")?;
+            self.output.flush()?;
+            let mut line = String::new();
+            if self.input.read_line(&mut line)? == 0 {
+                (self.quit)();
+            }
+            if line.trim().is_empty() {
+                continue;
+            }
+            match line.parse() {
+                Ok(command) => {
+                    if let Some(mode) = self.command(command, frame)? {
+                        self.mode = mode;
+                        return Ok(());
+                    }
+                }
+                Err(err) => writeln!(self.output, "{}", err)?,
+            }
+        }
+    }
+
+    /// Runs one command. Returns how to resume, or `None` to keep prompting.
+    fn command(&mut self, command: Command, frame: &FrameState) -> io::Result<Option<Mode>> {
+        match command {
+            Command::Step(count) => return Ok(Some(Mode::Step(count))),
+            Command::Next => return Ok(Some(Mode::Next(frame.depth))),
+            Command::Continue => return Ok(Some(Mode::Continue)),
+            Command::Break(breakpoint) => {
+                let kind = match breakpoint {
+                    Breakpoint::Watch(..) => "Watch",
+                    _ => "Breakpoint",
+                };
+                self.breakpoints.push(breakpoint);
+                writeln!(self.output, "{} {} set.", kind, self.breakpoints.len() - 1)?;
+            }
+            Command::Delete(index) if index >= self.breakpoints.len() => {
+                writeln!(self.output, "no breakpoint {}", index)?;
+            }
+            Command::Delete(index) => drop(self.breakpoints.remove(index)),
+            Command::Info => {
+                for (index, breakpoint) in self.breakpoints.iter().enumerate() {
+                    match breakpoint {
+                        Breakpoint::Pc(pc) => writeln!(self.output, "{}: pc {}", index, pc)?,
+                        Breakpoint::Opcode(opcode) => {
+                            writeln!(self.output, "{}: opcode {}", index, opcode_name(*opcode))?
+                        }
+                        Breakpoint::Watch(Some(address), slot) => {
+                            writeln!(self.output, "{}: slot {:#x} of {:?}", index, slot, address)?
+                        }
+                        Breakpoint::Watch(None, slot) => writeln!(self.output, "{}: slot {:#x}", index, slot)?,
+                    }
+                }
+            }
+            Command::List => {
+                let instructions = &self.frames[frame.depth].instructions;
+                let current = instructions.partition_point(|instruction| instruction.pc < frame.pc);
+                for instruction in &instructions[current.saturating_sub(5)..(current + 6).min(instructions.len())] {
+                    let marker = if instruction.pc == frame.pc { "=>" } else { "  " };
+                    writeln!(self.output, "{} {:>5}  {}", marker, instruction.pc, instruction)?;
+                }
+            }
+            Command::Stack => print_stack(&mut self.output, frame.stack)?,
+            Command::Memory { offset, len } => {
+                let end = len.map_or(frame.memory.len(), |len| offset.saturating_add(len));
+                let end = end.min(frame.memory.len());
+                if offset >= end {
+                    writeln!(self.output, "(empty, memory is {} bytes)", frame.memory.len())?;
+                }
+                hex_dump(&mut self.output, &frame.memory[offset.min(end)..end], offset)?;
+            }
+            Command::Storage(address) => {
+                let storage = self.storage(&address.unwrap_or(frame.address));
+                if storage.is_empty() {
+                    writeln!(self.output, "(empty)")?;
+                }
+                for (slot, value) in storage {
+                    writeln!(self.output, "{:#x}: {:#x}", slot, value)?;
+                }
+            }
+            Command::ReturnData => {
+                if frame.return_data.is_empty() {
+                    writeln!(self.output, "(empty)")?;
+                }
+                hex_dump(&mut self.output, frame.return_data, 0)?;
+            }
+            Command::CallStack => {
+                for (depth, call) in self.frames.iter().enumerate().rev() {
+                    writeln!(
+                        self.output,
+                        "#{} {:<12} {:?} pc {}",
+                        depth, call.kind, call.address, call.pc
+                    )?;
+                }
+            }
+            Command::Help => writeln!(self.output, "{}", HELP)?,
+            Command::Quit => (self.quit)(),
+        }
+        Ok(None)
+    }
+
+    /// The current instruction, as shown at every stop.
+    fn location(&self, frame: &FrameState) -> String {
+        let instructions = &self.frames[frame.depth].instructions;
+        let instruction = instructions
+            .binary_search_by_key(&frame.pc, |instruction| instruction.pc)
+            .map(|index| instructions[index].to_string())
+            .unwrap_or_else(|_| opcode_name(frame.opcode));
+        format!(
+            "[depth {}] pc {:>5}  {:<24} gas {}",
+            frame.depth, frame.pc, instruction, frame.gas_left
+        )
+    }
+
+    /// The storage of `address` as execution has left it so far.
+    fn storage(&self, address: &Address) -> BTreeMap<U256, U256> {
+        let mut storage: BTreeMap<U256, U256> = match self.base.account(address) {
+            Some(account) => account.storage.iter().map(|(slot, value)| (*slot, *value)).collect(),
+            None => BTreeMap::new(),
+        };
+        for (_, slot, value) in self.journal.iter().flatten().filter(|(written, ..)| written == address) {
+            storage.insert(*slot, *value);
+        }
+        storage.retain(|_, value| !value.is_zero());
+        storage
+    }
+
+    /// Records the write of an SSTORE about to run, and notes a change to a
+    /// watched slot.
+    fn record_sstore(&mut self, frame: &FrameState) {
+        let [.., value, slot] = frame.stack else {
+            return;
+        };
+        let old = self.storage(&frame.address).get(slot).copied().unwrap_or_default();
+        let watched = self.breakpoints.iter().any(|breakpoint| match breakpoint {
+            Breakpoint::Watch(address, watched) => watched == slot && address.is_none_or(|a| a == frame.address),
+            _ => false,
+        });
+        if watched && old != *value {
+            self.changed = Some(format!(
+                "Slot {:#x} of {:?} changed from {:#x} to {:#x} at pc {}.",
+                slot, frame.address, old, value, frame.pc
+            ));
+        }
+        self.journal
+            .last_mut()
+            .expect("a frame is open")
+            .push((frame.address, *slot, *value));
+    }
+
+    fn enter(&mut self, kind: &'static str, address: Address) {
+        self.journal.push(Vec::new());
+        self.frames.push(Frame {
+            kind,
+            address,
+            instructions: Vec::new(),
+            pc: 0,
+        });
+    }
+
+    fn exit(&mut self, success: bool) {
+        self.frames.pop();
+        let writes = self.journal.pop().unwrap_or_default();
+        if success {
+            self.journal
+                .last_mut()
+                .expect("the outermost frame stays open")
+                .extend(writes);
+        }
+    }
+}
+
+impl<R: BufRead, W: Write> Inspector for Debugger<R, W> {
+    fn step(&mut self, frame: &FrameState) -> StepControl {
+        if let Some(current) = self.frames.get_mut(frame.depth) {
+            if current.instructions.is_empty() {
+                current.instructions = disassemble(frame.code);
+            }
+            current.pc = frame.pc;
+            current.address = frame.address;
+        }
+        if self.should_stop(frame) && self.prompt(frame).is_err() {
+            (self.quit)();
+        }
+        if frame.opcode == 0x55 {
+            self.record_sstore(frame);
+        }
+        StepControl::Continue
+    }
+
+    fn call(&mut self, inputs: &mut CallInputs) -> Option<CallOutcome> {
+        let kind = match inputs.scheme {
+            evm::CallScheme::Call => "CALL",
+            evm::CallScheme::CallCode => "CALLCODE",
+            evm::CallScheme::DelegateCall => "DELEGATECALL",
+            evm::CallScheme::StaticCall => "STATICCALL",
+        };
+        self.enter(kind, inputs.address);
+        None
+    }
+
+    fn call_end(&mut self, _inputs: &CallInputs, outcome: CallOutcome) -> CallOutcome {
+        self.exit(outcome.success);
+        outcome
+    }
+
+    fn create(&mut self, inputs: &mut CreateInputs) -> Option<CreateOutcome> {
+        let kind = match inputs.scheme {
+            evm::CreateScheme::Create => "CREATE",
+            evm::CreateScheme::Create2 { .. } => "CREATE2",
+        };
+        self.enter(kind, Address::zero());
+        None
+    }
+
+    fn create_end(&mut self, _inputs: &CreateInputs, outcome: CreateOutcome) -> CreateOutcome {
+        self.exit(outcome.result.success);
+        outcome
+    }
+}
+
+fn print_stack(output: &mut impl Write, stack: &[U256]) -> io::Result<()> {
+    if stack.is_empty() {
+        writeln!(output, "(empty stack)")?;
+    }
+    for (index, value) in stack.iter().rev().enumerate() {
+        writeln!(output, "{:>4}: {:#x}", index, value)?;
+    }
+    Ok(())
+}
+
+/// Prints 16 bytes per line, with the offset of the first and the printable
+/// ones as ASCII.
+fn hex_dump(output: &mut impl Write, bytes: &[u8], start: usize) -> io::Result<()> {
+    for (line, chunk) in bytes.chunks(16).enumerate() {
+        let hex: Vec<String> = chunk.iter().map(|byte| format!("{:02x}", byte)).collect();
+        let ascii: String = chunk
+            .iter()
+            .map(|&byte| if byte.is_ascii_graphic() { byte as char } else { '.' })
+            .collect();
+        writeln!(output, "{:#06x}: {:<47}  {}", start + line * 16, hex.join(" "), ascii)?;
+    }
+    Ok(())
+}
+
+fn opcode_name(opcode: u8) -> String {
+    match opcode_info(opcode) {
+        Some(info) => info.name.to_string(),
+        None => format!("UNKNOWN {:#04x}", opcode),
+    }
+}
+
+/// A decimal or 0x-prefixed hex number.
+fn parse_number(text: &str) -> Option<U256> {
+    match text.strip_prefix("0x") {
+        Some(hex) => U256::from_str_radix(hex, 16).ok(),
+        None => U256::from_dec_str(text).ok(),
+    }
+}
+
+fn parse_usize(text: &str) -> Result<usize, String> {
+    parse_number(text)
+        .filter(|number| number.bits() <= 64)
+        .map(|number| number.low_u64() as usize)
+        .ok_or_else(|| format!("invalid number {:?}", text))
+}
+
+fn parse_opcode(name: &str) -> Result<u8, String> {
+    (0..=u8::MAX)
+        .find(|&opcode| opcode_info(opcode).is_some_and(|info| info.name.eq_ignore_ascii_case(name)))
+        .ok_or_else(|| format!("unknown opcode {:?}", name))
+}
+
+/// Reads code given as hex, or as the name of a file holding hex.
+fn read_code(code: &str) -> Result<Vec<u8>, String> {
+    if let Ok(bytes) = code.parse::<Bytes>() {
+        return Ok(bytes.0);
+    }
+    let text = std::fs::read_to_string(code).map_err(|err| format!("code is neither hex nor a file: {}", err))?;
+    let hex: String = text.split_whitespace().collect();
+    Ok(hex
+        .parse::<Bytes>()
+        .map_err(|err| format!("{} does not hold hex code: {}", code, err))?
+        .0)
+}
+
+fn read_json<T: DeserializeOwned>(path: &str) -> Result<T, String> {
+    let text = std::fs::read_to_string(path).map_err(|err| format!("cannot read {}: {}", path, err))?;
+    serde_json::from_str(&text).map_err(|err| format!("cannot parse {}: {}", path, err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use evm::Account;
+
+    /// Runs `code` as the transaction's under the commands in `script`, and
+    /// returns all the debugger printed.
+    fn session(code: &str, mut state: State, script: &str) -> String {
+        let code = read_code(code).unwrap();
+        let env = Env::from_fixture(&Txn::default(), &Block::default(), SpecId::default());
+        let context = CallContext {
+            code: &code,
+            input: &[],
+            address: Address::zero(),
+            caller: Address::zero(),
+            value: U256::zero(),
+            gas: env.tx.gas_limit,
+            is_static: false,
+            depth: 0,
+        };
+        let mut output = Vec::new();
+        {
+            let mut debugger = Debugger::new(state.clone(), script.as_bytes(), &mut output);
+            debugger.quit = || panic!("the script ended before execution did");
+            let mut host = StateHost::new(&mut state, &env).with_inspector(&mut debugger);
+            execute(&context, &mut host);
+            host.finish();
+        }
+        String::from_utf8(output).unwrap()
+    }
+
+    /// Calls 0xbb with no value or data, then stops.
+    const CALL_BB: &str = "0x6000600060006000600060bb5af100";
+
+    /// Stores 1 in slot 0.
+    fn callee() -> State {
+        let mut state = State::new();
+        let code = read_code("0x600160005500").unwrap();
+        state.insert(
+            Address::from_low_u64_be(0xbb),
+            Account {
+                code,
+                ..Account::default()
+            },
+        );
+        state
+    }
+
+    #[test]
+    fn parses_commands() {
+        assert_eq!("step".parse(), Ok(Command::Step(1)));
+        assert_eq!(" s  3 ".parse(), Ok(Command::Step(3)));
+        assert_eq!("b 0x10".parse(), Ok(Command::Break(Breakpoint::Pc(16))));
+        assert_eq!("break sstore".parse(), Ok(Command::Break(Breakpoint::Opcode(0x55))));
+        assert_eq!(
+            "w 2 bb".parse(),
+            Ok(Command::Break(Breakpoint::Watch(
+                Some(Address::from_low_u64_be(0xbb)),
+                U256::from(2)
+            )))
+        );
+        assert_eq!(
+            "m 0x20 8".parse(),
+            Ok(Command::Memory {
+                offset: 32,
+                len: Some(8)
+            })
+        );
+        assert_eq!("sto".parse(), Ok(Command::Storage(None)));
+        assert_eq!("bt".parse(), Ok(Command::CallStack));
+
+        assert_eq!(Command::from_str("s x"), Err("invalid count \"x\"".to_string()));
+        assert_eq!(Command::from_str("b"), Err("usage: break PC|OPCODE".to_string()));
+        assert_eq!(
+            Command::from_str("b JUMPY"),
+            Err("unknown opcode \"JUMPY\"".to_string())
+        );
+        assert_eq!(Command::from_str("d one"), Err("usage: delete N".to_string()));
+        assert_eq!(
+            Command::from_str("frob"),
+            Err("unknown command \"frob\", try `help`".to_string())
+        );
+    }
+
+    #[test]
+    fn steps_and_stops_at_breakpoints() {
+        // PUSH1 1, PUSH1 2, ADD, PUSH1 3, MUL, STOP.
+        let output = session(
+            "0x600160020160030200",
+            State::new(),
+            "s 2\nst\nb MUL\nbogus\nc\nst\nc\n",
+        );
+        assert!(output.contains("[depth 0] pc     0  PUSH1 0x01"), "{}", output);
+        assert!(output.contains("[depth 0] pc     4  ADD"), "{}", output);
+        assert!(output.contains("   0: 0x2\n   1: 0x1\n"), "{}", output);
+        assert!(output.contains("Breakpoint 0 set."), "{}", output);
+        assert!(output.contains("unknown command \"bogus\""), "{}", output);
+        assert!(output.contains("[depth 0] pc     7  MUL"), "{}", output);
+        assert!(output.contains("   0: 0x3\n   1: 0x3\n"), "{}", output);
+        assert!(!output.contains("STOP"), "{}", output);
+    }
+
+    #[test]
+    fn steps_over_calls_and_watches_storage() {
+        let output = session(CALL_BB, callee(), "b CALL\nc\nn\nsto bb\nc\n");
+        assert!(output.contains("[depth 0] pc    13  CALL"), "{}", output);
+        assert!(output.contains("[depth 0] pc    14  STOP"), "{}", output);
+        assert!(!output.contains("[depth 1]"), "{}", output);
+        assert!(output.contains("0x0: 0x1\n"), "{}", output);
+
+        let output = session(CALL_BB, callee(), "w 0 bb\nc\nc\n");
+        let changed = format!(
+            "Slot 0x0 of {:?} changed from 0x0 to 0x1 at pc 4.",
+            Address::from_low_u64_be(0xbb)
+        );
+        assert!(
+            output.contains(&format!("{}\n[depth 1] pc     5  STOP", changed)),
+            "{}",
+            output
+        );
+    }
+
+    #[test]
+    fn lists_the_code_a_frame_runs() {
+        // Creates a contract whose code stores 1 in slot 0, then calls it.
+        let code = "0x6e656001600055006000526006601af3600052600f60116000f060006000600060006000855af100";
+        let output = session(code, State::new(), "b CALL\nc\ns\nl\nbt\nc\n");
+        assert!(output.contains("[depth 0] pc    38  CALL"), "{}", output);
+        assert!(output.contains("[depth 1] pc     0  PUSH1 0x01"), "{}", output);
+        assert!(
+            output.contains("=>     0  PUSH1 0x01\n       2  PUSH1 0x00\n       4  SSTORE\n"),
+            "{}",
+            output
+        );
+        assert!(output.contains("#1 CALL "), "{}", output);
+        assert!(output.contains("#0 TX "), "{}", output);
+    }
+}
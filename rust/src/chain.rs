@@ -0,0 +1,567 @@
+//! Blocks beyond the single transaction: headers and the consensus rules
+//! that tie them to their parent, withdrawals, receipts and log blooms.
+
+use crate::{keccak256, Address, BlockEnv, Bytes, CfgEnv, Evm, Log, SpecId, State, Transaction, TxEnv, TxKind, TxType};
+use primitive_types::{H160, H256, U256};
+use rlp::{Decodable, DecoderError, Encodable, Rlp, RlpStream};
+use std::collections::BTreeMap;
+
+/// Keccak-256 of the RLP of an empty list: the ommers hash of a block
+/// without ommers.
+pub const EMPTY_OMMERS_HASH: H256 = H256([
+    0x1d, 0xcc, 0x4d, 0xe8, 0xde, 0xc7, 0x5d, 0x7a, 0xab, 0x85, 0xb5, 0x67, 0xb6, 0xcc, 0xd4, 0x1a, 0xd3, 0x12, 0x45,
+    0x1b, 0x94, 0x8a, 0x74, 0x13, 0xf0, 0xa1, 0x42, 0xfd, 0x40, 0xd4, 0x93, 0x47,
+]);
+
+/// Base fee of the first London block (EIP-1559).
+pub const INITIAL_BASE_FEE: u64 = 1_000_000_000;
+/// Blob gas a block aims for (EIP-4844).
+pub const TARGET_BLOB_GAS_PER_BLOCK: u64 = 393_216;
+pub const MAX_BLOB_GAS_PER_BLOCK: u64 = 786_432;
+
+/// The sender of system calls.
+pub const SYSTEM_ADDRESS: Address = H160([
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+    0xfe,
+]);
+/// The contract that keeps recent beacon block roots (EIP-4788).
+pub const BEACON_ROOTS_ADDRESS: Address = H160([
+    0x00, 0x0f, 0x3d, 0xf6, 0xd7, 0x32, 0x80, 0x7e, 0xf1, 0x31, 0x9f, 0xb7, 0xb8, 0xbb, 0x85, 0x22, 0xd0, 0xbe, 0xac,
+    0x02,
+]);
+const SYSTEM_CALL_GAS: u64 = 30_000_000;
+
+const MIN_GAS_LIMIT: u64 = 5000;
+const MIN_DIFFICULTY: u64 = 131_072;
+const MAX_EXTRA_DATA: usize = 32;
+
+/// A 2048-bit bloom filter over log addresses and topics.
+pub type Bloom = [u8; 256];
+
+/// A block header. Fields added by later forks are `None` before them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Header {
+    pub parent_hash: H256,
+    pub ommers_hash: H256,
+    pub coinbase: Address,
+    pub state_root: H256,
+    pub transactions_root: H256,
+    pub receipts_root: H256,
+    pub logs_bloom: Bloom,
+    pub difficulty: U256,
+    pub number: u64,
+    pub gas_limit: u64,
+    pub gas_used: u64,
+    pub timestamp: u64,
+    pub extra_data: Bytes,
+    /// The PoW mix hash, or the beacon chain randomness from Paris on.
+    pub mix_hash: H256,
+    pub nonce: [u8; 8],
+    pub base_fee: Option<U256>,
+    pub withdrawals_root: Option<H256>,
+    pub blob_gas_used: Option<u64>,
+    pub excess_blob_gas: Option<u64>,
+    pub parent_beacon_block_root: Option<H256>,
+}
+
+impl Header {
+    pub fn hash(&self) -> H256 {
+        H256(keccak256(&rlp::encode(self)))
+    }
+
+    /// Checks the header against its parent under the rules of `spec`.
+    /// Proof of work seals are not verified.
+    pub fn validate(&self, parent: &Header, spec: SpecId) -> Result<(), String> {
+        if self.parent_hash != parent.hash() {
+            return Err(format!("unknown parent {:?}", self.parent_hash));
+        }
+        if self.number != parent.number + 1 {
+            return Err(format!("invalid number {}, parent is {}", self.number, parent.number));
+        }
+        if self.timestamp <= parent.timestamp {
+            return Err(format!(
+                "timestamp {} not after parent's {}",
+                self.timestamp, parent.timestamp
+            ));
+        }
+        if self.extra_data.len() > MAX_EXTRA_DATA {
+            return Err(format!("extra data too long: {} bytes", self.extra_data.len()));
+        }
+        if self.gas_used > self.gas_limit {
+            return Err(format!("gas used {} above gas limit {}", self.gas_used, self.gas_limit));
+        }
+
+        // The first London block doubles the limit, keeping the target.
+        let parent_gas_limit = match (spec.is_enabled(SpecId::London), parent.base_fee) {
+            (true, None) => parent.gas_limit * 2,
+            _ => parent.gas_limit,
+        };
+        if self.gas_limit.abs_diff(parent_gas_limit) >= parent_gas_limit / 1024 || self.gas_limit < MIN_GAS_LIMIT {
+            return Err(format!(
+                "invalid gas limit {}, parent has {}",
+                self.gas_limit, parent.gas_limit
+            ));
+        }
+
+        let expected_base_fee = spec.is_enabled(SpecId::London).then(|| calc_base_fee(parent));
+        if self.base_fee != expected_base_fee {
+            return Err(format!(
+                "invalid base fee {:?}, expected {:?}",
+                self.base_fee, expected_base_fee
+            ));
+        }
+
+        if spec.is_enabled(SpecId::Paris) {
+            if !self.difficulty.is_zero() || self.nonce != [0; 8] || self.ommers_hash != EMPTY_OMMERS_HASH {
+                return Err("proof of work fields set after the merge".to_string());
+            }
+        } else {
+            let expected = calc_difficulty(parent, self.timestamp, spec);
+            if self.difficulty != expected {
+                return Err(format!("invalid difficulty {}, expected {}", self.difficulty, expected));
+            }
+        }
+
+        if self.withdrawals_root.is_some() != spec.is_enabled(SpecId::Shanghai) {
+            return Err("withdrawals root presence does not match the fork".to_string());
+        }
+        let is_cancun = spec.is_enabled(SpecId::Cancun);
+        let blob_fields = [
+            self.blob_gas_used.is_some(),
+            self.excess_blob_gas.is_some(),
+            self.parent_beacon_block_root.is_some(),
+        ];
+        if blob_fields.iter().any(|present| *present != is_cancun) {
+            return Err("blob gas fields presence does not match the fork".to_string());
+        }
+        if is_cancun {
+            let expected = calc_excess_blob_gas(parent);
+            if self.excess_blob_gas != Some(expected) {
+                return Err(format!(
+                    "invalid excess blob gas {:?}, expected {}",
+                    self.excess_blob_gas, expected
+                ));
+            }
+            if self.blob_gas_used.unwrap_or_default() > MAX_BLOB_GAS_PER_BLOCK {
+                return Err(format!("blob gas used {:?} above the maximum", self.blob_gas_used));
+            }
+        }
+        Ok(())
+    }
+
+    /// The environment transactions of this block run in. BLOCKHASH sees
+    /// `block_hashes`.
+    pub fn block_env(&self, spec: SpecId, block_hashes: BTreeMap<u64, H256>) -> BlockEnv {
+        BlockEnv {
+            number: self.number,
+            coinbase: self.coinbase,
+            timestamp: self.timestamp,
+            gas_limit: self.gas_limit,
+            basefee: self.base_fee.unwrap_or_default(),
+            difficulty: self.difficulty,
+            prevrandao: spec.is_enabled(SpecId::Paris).then_some(self.mix_hash),
+            excess_blob_gas: self.excess_blob_gas,
+            block_hashes,
+        }
+    }
+}
+
+impl Encodable for Header {
+    fn rlp_append(&self, stream: &mut RlpStream) {
+        let optional = [
+            self.base_fee.is_some(),
+            self.withdrawals_root.is_some(),
+            self.blob_gas_used.is_some(),
+            self.excess_blob_gas.is_some(),
+            self.parent_beacon_block_root.is_some(),
+        ];
+        stream.begin_list(15 + optional.iter().filter(|present| **present).count());
+        stream.append(&self.parent_hash);
+        stream.append(&self.ommers_hash);
+        stream.append(&self.coinbase);
+        stream.append(&self.state_root);
+        stream.append(&self.transactions_root);
+        stream.append(&self.receipts_root);
+        stream.append(&self.logs_bloom.as_slice());
+        stream.append(&self.difficulty);
+        stream.append(&self.number);
+        stream.append(&self.gas_limit);
+        stream.append(&self.gas_used);
+        stream.append(&self.timestamp);
+        stream.append(&self.extra_data.0);
+        stream.append(&self.mix_hash);
+        stream.append(&self.nonce.as_slice());
+        if let Some(ref base_fee) = self.base_fee {
+            stream.append(base_fee);
+        }
+        if let Some(ref root) = self.withdrawals_root {
+            stream.append(root);
+        }
+        if let Some(ref gas) = self.blob_gas_used {
+            stream.append(gas);
+        }
+        if let Some(ref gas) = self.excess_blob_gas {
+            stream.append(gas);
+        }
+        if let Some(ref root) = self.parent_beacon_block_root {
+            stream.append(root);
+        }
+    }
+}
+
+impl Decodable for Header {
+    fn decode(rlp: &Rlp) -> Result<Self, DecoderError> {
+        let count = rlp.item_count()?;
+        if !(15..=20).contains(&count) {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+        let optional = |index: usize| (index < count).then_some(index);
+        let bloom: Vec<u8> = rlp.val_at(6)?;
+        let nonce: Vec<u8> = rlp.val_at(14)?;
+        Ok(Header {
+            parent_hash: rlp.val_at(0)?,
+            ommers_hash: rlp.val_at(1)?,
+            coinbase: rlp.val_at(2)?,
+            state_root: rlp.val_at(3)?,
+            transactions_root: rlp.val_at(4)?,
+            receipts_root: rlp.val_at(5)?,
+            logs_bloom: bloom
+                .try_into()
+                .map_err(|_| DecoderError::Custom("logs bloom is not 256 bytes"))?,
+            difficulty: rlp.val_at(7)?,
+            number: rlp.val_at(8)?,
+            gas_limit: rlp.val_at(9)?,
+            gas_used: rlp.val_at(10)?,
+            timestamp: rlp.val_at(11)?,
+            extra_data: Bytes(rlp.val_at(12)?),
+            mix_hash: rlp.val_at(13)?,
+            nonce: nonce
+                .try_into()
+                .map_err(|_| DecoderError::Custom("nonce is not 8 bytes"))?,
+            base_fee: optional(15).map(|index| rlp.val_at(index)).transpose()?,
+            withdrawals_root: optional(16).map(|index| rlp.val_at(index)).transpose()?,
+            blob_gas_used: optional(17).map(|index| rlp.val_at(index)).transpose()?,
+            excess_blob_gas: optional(18).map(|index| rlp.val_at(index)).transpose()?,
+            parent_beacon_block_root: optional(19).map(|index| rlp.val_at(index)).transpose()?,
+        })
+    }
+}
+
+/// A validator withdrawal (EIP-4895).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Withdrawal {
+    pub index: u64,
+    pub validator_index: u64,
+    pub address: Address,
+    /// In gwei.
+    pub amount: u64,
+}
+
+impl Withdrawal {
+    /// The amount in wei.
+    pub fn amount_wei(&self) -> U256 {
+        U256::from(self.amount) * U256::exp10(9)
+    }
+}
+
+impl Encodable for Withdrawal {
+    fn rlp_append(&self, stream: &mut RlpStream) {
+        stream.begin_list(4);
+        stream.append(&self.index);
+        stream.append(&self.validator_index);
+        stream.append(&self.address);
+        stream.append(&self.amount);
+    }
+}
+
+impl Decodable for Withdrawal {
+    fn decode(rlp: &Rlp) -> Result<Self, DecoderError> {
+        if rlp.item_count()? != 4 {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+        Ok(Withdrawal {
+            index: rlp.val_at(0)?,
+            validator_index: rlp.val_at(1)?,
+            address: rlp.val_at(2)?,
+            amount: rlp.val_at(3)?,
+        })
+    }
+}
+
+/// A block as it is sent over the wire.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SealedBlock {
+    pub header: Header,
+    pub transactions: Vec<Transaction>,
+    pub ommers: Vec<Header>,
+    /// `None` before Shanghai.
+    pub withdrawals: Option<Vec<Withdrawal>>,
+}
+
+impl SealedBlock {
+    pub fn decode(bytes: &[u8]) -> Result<Self, String> {
+        let rlp = Rlp::new(bytes);
+        let invalid = |err: DecoderError| format!("invalid block: {}", err);
+        let info = rlp.payload_info().map_err(invalid)?;
+        if info.header_len + info.value_len != bytes.len() {
+            return Err("trailing bytes after block".to_string());
+        }
+        let count = rlp.item_count().map_err(invalid)?;
+        if !(3..=4).contains(&count) {
+            return Err(invalid(DecoderError::RlpIncorrectListLen));
+        }
+        let transactions = rlp
+            .at(1)
+            .map_err(invalid)?
+            .iter()
+            .map(|tx| Transaction::decode_rlp(&tx))
+            .collect::<Result<_, _>>()?;
+        Ok(SealedBlock {
+            header: rlp.val_at(0).map_err(invalid)?,
+            transactions,
+            ommers: rlp.list_at(2).map_err(invalid)?,
+            withdrawals: (count == 4).then(|| rlp.list_at(3)).transpose().map_err(invalid)?,
+        })
+    }
+}
+
+/// The outcome of a transaction as a block commits to it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Receipt {
+    pub tx_type: TxType,
+    pub success: bool,
+    /// The state root after the transaction, which receipts held instead of
+    /// the status before Byzantium.
+    pub state_root: Option<H256>,
+    pub cumulative_gas_used: u64,
+    pub logs: Vec<Log>,
+}
+
+impl Receipt {
+    pub fn bloom(&self) -> Bloom {
+        logs_bloom(&self.logs)
+    }
+
+    /// The EIP-2718 encoding the receipts root commits to.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut stream = RlpStream::new_list(4);
+        match self.state_root {
+            Some(ref root) => stream.append(root),
+            None => stream.append(&(self.success as u8)),
+        };
+        stream.append(&self.cumulative_gas_used);
+        stream.append(&self.bloom().as_slice());
+        stream.append_list(&self.logs);
+        match self.tx_type {
+            TxType::Legacy => stream.out().to_vec(),
+            tx_type => [&[tx_type as u8][..], &stream.out()].concat(),
+        }
+    }
+}
+
+/// The bloom filter of the addresses and topics of `logs`.
+pub fn logs_bloom<'a>(logs: impl IntoIterator<Item = &'a Log>) -> Bloom {
+    let mut bloom = [0u8; 256];
+    for log in logs {
+        let entries = std::iter::once(log.address.as_bytes()).chain(log.topics.iter().map(H256::as_bytes));
+        for entry in entries {
+            let hash = keccak256(entry);
+            for pair in hash[..6].chunks(2) {
+                let bit = (usize::from(pair[0]) << 8 | usize::from(pair[1])) & 2047;
+                bloom[255 - bit / 8] |= 1 << (bit % 8);
+            }
+        }
+    }
+    bloom
+}
+
+/// The base fee of the block after `parent` (EIP-1559).
+pub fn calc_base_fee(parent: &Header) -> U256 {
+    let Some(parent_base_fee) = parent.base_fee else {
+        return U256::from(INITIAL_BASE_FEE);
+    };
+    let target = parent.gas_limit / 2;
+    if target == 0 || parent.gas_used == target {
+        return parent_base_fee;
+    }
+    let delta = |gas: u64| parent_base_fee * U256::from(gas) / U256::from(target) / U256::from(8);
+    match parent.gas_used > target {
+        true => parent_base_fee + delta(parent.gas_used - target).max(U256::one()),
+        false => parent_base_fee.saturating_sub(delta(target - parent.gas_used)),
+    }
+}
+
+/// The excess blob gas of the block after `parent` (EIP-4844).
+pub fn calc_excess_blob_gas(parent: &Header) -> u64 {
+    let total = parent.excess_blob_gas.unwrap_or_default() + parent.blob_gas_used.unwrap_or_default();
+    total.saturating_sub(TARGET_BLOB_GAS_PER_BLOCK)
+}
+
+/// The proof of work difficulty of a block at `timestamp` after `parent`,
+/// including the difficulty bomb and its delays.
+pub fn calc_difficulty(parent: &Header, timestamp: u64, spec: SpecId) -> U256 {
+    let step = parent.difficulty / 2048;
+    let elapsed = timestamp.saturating_sub(parent.timestamp) as i64;
+    let factor = if spec.is_enabled(SpecId::Byzantium) {
+        let ommers = if parent.ommers_hash == EMPTY_OMMERS_HASH { 1 } else { 2 };
+        (ommers - elapsed / 9).max(-99)
+    } else if spec.is_enabled(SpecId::Homestead) {
+        (1 - elapsed / 10).max(-99)
+    } else if elapsed < 13 {
+        1
+    } else {
+        -1
+    };
+    let adjustment = step * U256::from(factor.unsigned_abs());
+    let mut difficulty = match factor >= 0 {
+        true => parent.difficulty.saturating_add(adjustment),
+        false => parent.difficulty.saturating_sub(adjustment),
+    };
+    difficulty = difficulty.max(U256::from(MIN_DIFFICULTY));
+
+    let bomb_delay = match spec {
+        _ if spec.is_enabled(SpecId::London) => 9_700_000,
+        _ if spec.is_enabled(SpecId::Berlin) => 9_000_000,
+        _ if spec.is_enabled(SpecId::Constantinople) => 5_000_000,
+        _ if spec.is_enabled(SpecId::Byzantium) => 3_000_000,
+        _ => 0,
+    };
+    let period = (parent.number + 1).saturating_sub(bomb_delay) / 100_000;
+    if period >= 2 {
+        difficulty = difficulty.saturating_add(U256::one() << (period - 2));
+    }
+    difficulty
+}
+
+/// The reward for mining a block, before the merge ended mining.
+pub fn block_reward(spec: SpecId) -> U256 {
+    let ether = U256::exp10(18);
+    match spec {
+        _ if spec.is_enabled(SpecId::Paris) => U256::zero(),
+        _ if spec.is_enabled(SpecId::Constantinople) => ether * 2,
+        _ if spec.is_enabled(SpecId::Byzantium) => ether * 3,
+        _ => ether * 5,
+    }
+}
+
+/// Stores the parent beacon block root in the beacon roots contract before
+/// the block's transactions run (EIP-4788). The call is free, and the
+/// system address it comes from is left as it was.
+pub fn apply_beacon_root(state: &mut State, cfg: &CfgEnv, block: &BlockEnv, root: H256) {
+    let system = state.account(&SYSTEM_ADDRESS).cloned();
+    let mut evm = Evm::builder()
+        .with_state(std::mem::take(state))
+        .with_cfg_env(CfgEnv {
+            disable_balance_check: true,
+            disable_nonce_check: true,
+            disable_base_fee: true,
+            ..cfg.clone()
+        })
+        .with_block_env(BlockEnv {
+            gas_limit: SYSTEM_CALL_GAS,
+            ..block.clone()
+        })
+        .with_tx_env(TxEnv {
+            caller: SYSTEM_ADDRESS,
+            kind: TxKind::Call(BEACON_ROOTS_ADDRESS),
+            data: root.as_bytes().to_vec().into(),
+            gas_limit: SYSTEM_CALL_GAS,
+            gas_price: U256::zero(),
+            nonce: None,
+            ..Default::default()
+        })
+        .build();
+    // The call has no gas price and skips the checks that could fail.
+    let _ = evm.transact();
+    *state = evm.into_state();
+    match system {
+        Some(account) => state.insert(SYSTEM_ADDRESS, account),
+        None => {
+            state.remove(&SYSTEM_ADDRESS);
+        }
+    }
+}
+
+/// Pays `reward` to `coinbase`, plus a 32nd of it for each ommer. The
+/// miner of an ommer `generations` blocks older than its nephew gets
+/// `(8 - generations) / 8` of `reward`.
+pub fn apply_rewards(state: &mut State, reward: U256, coinbase: &Address, ommers: &[(u64, Address)]) {
+    let miner = state.account_mut(coinbase);
+    miner.balance = miner.balance.saturating_add(reward + reward / 32 * ommers.len());
+    for (generations, address) in ommers {
+        let ommer_reward = reward * U256::from(8u64.saturating_sub(*generations)) / 8;
+        let account = state.account_mut(address);
+        account.balance = account.balance.saturating_add(ommer_reward);
+    }
+}
+
+/// Credits each withdrawal to its address. Zero amounts leave the state
+/// untouched.
+pub fn apply_withdrawals(state: &mut State, withdrawals: &[Withdrawal]) {
+    for withdrawal in withdrawals.iter().filter(|withdrawal| withdrawal.amount > 0) {
+        let account = state.account_mut(&withdrawal.address);
+        account.balance = account.balance.saturating_add(withdrawal.amount_wei());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_ommers_hash() {
+        assert_eq!(
+            EMPTY_OMMERS_HASH,
+            H256(keccak256(&rlp::encode_list::<Header, Header>(&[])))
+        );
+    }
+
+    #[test]
+    fn base_fee_follows_gas_used() {
+        let mut parent = Header::decode(&Rlp::new(&rlp::encode(&genesis()))).unwrap();
+        parent.base_fee = Some(U256::from(INITIAL_BASE_FEE));
+        parent.gas_limit = 30_000_000;
+        parent.gas_used = 15_000_000;
+        assert_eq!(calc_base_fee(&parent), U256::from(INITIAL_BASE_FEE));
+        parent.gas_used = 30_000_000;
+        assert_eq!(calc_base_fee(&parent), U256::from(1_125_000_000));
+        parent.gas_used = 0;
+        assert_eq!(calc_base_fee(&parent), U256::from(875_000_000));
+    }
+
+    #[test]
+    fn mainnet_genesis_hash() {
+        assert_eq!(
+            genesis().hash(),
+            crate::parse_h256("0xd4e56740f876aef8c010b86a40d5f56745a118d0906a34e69aec8c0db1cb8fa3").unwrap()
+        );
+    }
+
+    fn genesis() -> Header {
+        let hash = |hex: &str| crate::parse_h256(hex).unwrap();
+        Header {
+            parent_hash: H256::zero(),
+            ommers_hash: EMPTY_OMMERS_HASH,
+            coinbase: Address::zero(),
+            state_root: hash("0xd7f8974fb5ac78d9ac099b9ad5018bedc2ce0a72dad1827a1709da30580f0544"),
+            transactions_root: hash("0x56e81f171bcc55a6ff8345e692c0f86e5b48e01b996cadc001622fb5e363b421"),
+            receipts_root: hash("0x56e81f171bcc55a6ff8345e692c0f86e5b48e01b996cadc001622fb5e363b421"),
+            logs_bloom: [0; 256],
+            difficulty: U256::from(0x400000000u64),
+            number: 0,
+            gas_limit: 5000,
+            gas_used: 0,
+            timestamp: 0,
+            extra_data: hex::decode("11bbe8db4e347b4e8c937c1c8370e4b5ed33adb3db69cbdb7a38e1e50b1b82fa")
+                .unwrap()
+                .into(),
+            mix_hash: H256::zero(),
+            nonce: 0x42u64.to_be_bytes(),
+            base_fee: None,
+            withdrawals_root: None,
+            blob_gas_used: None,
+            excess_blob_gas: None,
+            parent_beacon_block_root: None,
+        }
+    }
+}
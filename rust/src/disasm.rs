@@ -0,0 +1,139 @@
+//! Turns bytecode back into the mnemonic form used by the `asm` field of
+//! `evm.json`: one instruction per line, PUSH data as `0x` hex, in a
+//! canonical form that [`assemble`](crate::assemble) reads back.
+
+use crate::{opcode, Bytes};
+use std::fmt;
+
+/// One decoded instruction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Instruction {
+    /// Offset of the opcode in the code.
+    pub pc: usize,
+    pub opcode: u8,
+    /// `None` for bytes that are not a defined opcode.
+    pub mnemonic: Option<&'static str>,
+    /// PUSH data. Shorter than the PUSH size when the code ends early.
+    pub immediate: Bytes,
+}
+
+impl Instruction {
+    /// Number of PUSH data bytes cut off by the end of the code. The
+    /// interpreter reads them as zeros.
+    pub fn missing_bytes(&self) -> usize {
+        let size = opcode::info(self.opcode).map(|info| info.immediate).unwrap_or_default();
+        size - self.immediate.len()
+    }
+}
+
+/// Renders like `PUSH1 0x42`. Undefined opcodes render as `UNKNOWN 0xef`,
+/// and truncated PUSH data is flagged with a trailing comment.
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Some(mnemonic) = self.mnemonic else {
+            return write!(f, "UNKNOWN {:#04x}", self.opcode);
+        };
+        write!(f, "{}", mnemonic)?;
+        if self.immediate.is_empty() && self.missing_bytes() == 0 {
+            return Ok(());
+        }
+        write!(f, " 0x{}", hex::encode(&self.immediate[..]))?;
+        match self.missing_bytes() {
+            0 => Ok(()),
+            missing => write!(f, " ; truncated, {} missing byte(s) read as zero", missing),
+        }
+    }
+}
+
+/// Decodes `code` into instructions, skipping over PUSH data.
+pub fn disassemble(code: &[u8]) -> Vec<Instruction> {
+    let mut instructions = Vec::new();
+    let mut pc = 0;
+    while pc < code.len() {
+        let opcode = code[pc];
+        let info = opcode::info(opcode);
+        let size = info.map(|info| info.immediate).unwrap_or_default();
+        let end = (pc + 1 + size).min(code.len());
+        instructions.push(Instruction {
+            pc,
+            opcode,
+            mnemonic: info.map(|info| info.name),
+            immediate: code[pc + 1..end].into(),
+        });
+        pc += 1 + size;
+    }
+    instructions
+}
+
+/// Renders `instructions` one per line, like the `asm` field of `evm.json`.
+///
+/// The output is canonical rather than identical to that field: PUSH data
+/// is always hex with one digit pair per byte, where the fixtures often
+/// write decimal or drop leading zeros. [`assemble`](crate::assemble)
+/// turns it back into the same bytecode.
+pub fn render_asm(instructions: &[Instruction]) -> String {
+    instructions
+        .iter()
+        .map(Instruction::to_string)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn skips_push_data() {
+        let instructions = disassemble(&[0x60, 0x5b, 0x5b, 0x00]);
+        assert_eq!(
+            instructions.iter().map(|i| (i.pc, i.mnemonic)).collect::<Vec<_>>(),
+            vec![(0, Some("PUSH1")), (2, Some("JUMPDEST")), (3, Some("STOP"))]
+        );
+        assert_eq!(render_asm(&instructions), "PUSH1 0x5b\nJUMPDEST\nSTOP");
+    }
+
+    #[test]
+    fn truncated_trailing_push() {
+        let instructions = disassemble(&[0x00, 0x62, 0xaa]);
+        assert_eq!(instructions.len(), 2);
+        let push = &instructions[1];
+        assert_eq!(
+            (push.pc, push.immediate.to_vec(), push.missing_bytes()),
+            (1, vec![0xaa], 2)
+        );
+        assert_eq!(
+            push.to_string(),
+            "PUSH3 0xaa ; truncated, 2 missing byte(s) read as zero"
+        );
+
+        let empty = &disassemble(&[0x7f])[0];
+        assert_eq!(empty.missing_bytes(), 32);
+        assert_eq!(
+            empty.to_string(),
+            "PUSH32 0x ; truncated, 32 missing byte(s) read as zero"
+        );
+    }
+
+    #[test]
+    fn invalid_and_unknown_opcodes() {
+        let instructions = disassemble(&[0xfe, 0xef, 0x0c, 0x01]);
+        assert_eq!(
+            instructions.iter().map(Instruction::to_string).collect::<Vec<_>>(),
+            vec!["INVALID", "UNKNOWN 0xef", "UNKNOWN 0x0c", "ADD"]
+        );
+        assert_eq!(instructions[1].mnemonic, None);
+        assert_eq!(instructions[1].missing_bytes(), 0);
+    }
+
+    #[test]
+    fn reassembles_every_fixture() {
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("../evm.json");
+        let tests: Vec<serde_json::Value> = serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+        for test in &tests {
+            let code = hex::decode(test["code"]["bin"].as_str().unwrap()).unwrap();
+            let asm = render_asm(&disassemble(&code));
+            assert_eq!(crate::assemble(&asm).unwrap(), code, "{}:\n{}", test["name"], asm);
+        }
+    }
+}
@@ -0,0 +1,201 @@
+//! Configuration, block and transaction environment for [`Evm`](crate::Evm).
+
+use crate::{Address, Block, Bytes, SpecId, Txn};
+use primitive_types::{H256, U256};
+use std::collections::BTreeMap;
+
+/// Gas available to a fixture transaction that gives no gas limit. Gas is
+/// still counted, only hidden from GAS and CALL, so execution stays bounded.
+pub const UNMETERED_GAS_LIMIT: u64 = 30_000_000;
+
+/// Chain-wide settings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CfgEnv {
+    pub chain_id: u64,
+    pub spec: SpecId,
+    /// Maximum size of deployed code. Defaults to the EIP-170 limit.
+    pub limit_contract_code_size: Option<usize>,
+    /// Skips the check that the sender can pay for gas and value.
+    pub disable_balance_check: bool,
+    /// Skips the check of the transaction nonce against the sender's.
+    pub disable_nonce_check: bool,
+    /// Lets the gas price go below the block's base fee.
+    pub disable_base_fee: bool,
+    /// Hides the gas limit from the code: GAS reports `U256::MAX` and calls
+    /// ignore their gas argument. Gas is still charged against the limit.
+    pub disable_gas: bool,
+}
+
+impl Default for CfgEnv {
+    fn default() -> Self {
+        CfgEnv {
+            chain_id: 1,
+            spec: SpecId::default(),
+            limit_contract_code_size: None,
+            disable_balance_check: false,
+            disable_nonce_check: false,
+            disable_base_fee: false,
+            disable_gas: false,
+        }
+    }
+}
+
+/// The block the transaction is included in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockEnv {
+    pub number: u64,
+    pub coinbase: Address,
+    pub timestamp: u64,
+    pub gas_limit: u64,
+    pub basefee: U256,
+    pub difficulty: U256,
+    /// Beacon chain randomness, which replaces `difficulty` from Paris onward.
+    pub prevrandao: Option<H256>,
+    pub excess_blob_gas: Option<u64>,
+    /// Hashes of recent blocks, for BLOCKHASH. Missing blocks hash to zero.
+    pub block_hashes: BTreeMap<u64, H256>,
+}
+
+impl Default for BlockEnv {
+    fn default() -> Self {
+        BlockEnv {
+            number: 0,
+            coinbase: Address::zero(),
+            timestamp: 1,
+            gas_limit: u64::MAX,
+            basefee: U256::zero(),
+            difficulty: U256::zero(),
+            prevrandao: None,
+            excess_blob_gas: None,
+            block_hashes: BTreeMap::new(),
+        }
+    }
+}
+
+/// Whether a transaction calls an account or creates a contract.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TxKind {
+    Call(Address),
+    Create,
+}
+
+impl Default for TxKind {
+    fn default() -> Self {
+        TxKind::Call(Address::zero())
+    }
+}
+
+/// An EIP-2930 access list entry: an account and the storage keys to warm up.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AccessListItem {
+    pub address: Address,
+    pub storage_keys: Vec<U256>,
+}
+
+/// The transaction being executed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TxEnv {
+    pub caller: Address,
+    pub kind: TxKind,
+    pub value: U256,
+    pub data: Bytes,
+    pub gas_limit: u64,
+    /// Gas price of a legacy transaction, or the max fee per gas (EIP-1559).
+    pub gas_price: U256,
+    /// Max priority fee per gas. `None` for legacy transactions.
+    pub gas_priority_fee: Option<U256>,
+    /// Expected sender nonce. `None` skips the check.
+    pub nonce: Option<u64>,
+    pub access_list: Vec<AccessListItem>,
+    pub blob_hashes: Vec<H256>,
+    pub max_fee_per_blob_gas: Option<U256>,
+    /// ORIGIN, when it differs from `caller`. Only test fixtures set this.
+    pub origin: Option<Address>,
+}
+
+impl Default for TxEnv {
+    fn default() -> Self {
+        TxEnv {
+            caller: Address::zero(),
+            kind: TxKind::default(),
+            value: U256::zero(),
+            data: Bytes::new(),
+            gas_limit: 30_000_000,
+            gas_price: U256::zero(),
+            gas_priority_fee: None,
+            nonce: None,
+            access_list: Vec::new(),
+            blob_hashes: Vec::new(),
+            max_fee_per_blob_gas: None,
+            origin: None,
+        }
+    }
+}
+
+impl TxEnv {
+    /// Price actually paid per unit of gas under `basefee` (EIP-1559).
+    pub fn effective_gas_price(&self, basefee: U256) -> U256 {
+        match self.gas_priority_fee {
+            Some(priority_fee) => self.gas_price.min(basefee.saturating_add(priority_fee)),
+            None => self.gas_price,
+        }
+    }
+}
+
+/// Everything the interpreter can learn about its surroundings.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Env {
+    pub cfg: CfgEnv,
+    pub block: BlockEnv,
+    pub tx: TxEnv,
+}
+
+impl From<&Block> for BlockEnv {
+    fn from(block: &Block) -> Self {
+        BlockEnv {
+            number: block.number,
+            coinbase: block.coinbase,
+            timestamp: block.timestamp,
+            gas_limit: block.gaslimit,
+            basefee: block.basefee,
+            difficulty: block.difficulty,
+            prevrandao: block.prevrandao,
+            excess_blob_gas: Some(block.excess_blob_gas),
+            block_hashes: BTreeMap::new(),
+        }
+    }
+}
+
+impl From<&Txn> for TxEnv {
+    fn from(tx: &Txn) -> Self {
+        TxEnv {
+            caller: tx.from,
+            kind: TxKind::Call(tx.to.unwrap_or_default()),
+            value: tx.value,
+            data: tx.data.clone(),
+            gas_limit: tx.gas.unwrap_or(UNMETERED_GAS_LIMIT),
+            gas_price: tx.gasprice,
+            blob_hashes: tx.blob_versioned_hashes.clone(),
+            origin: tx.origin,
+            ..Default::default()
+        }
+    }
+}
+
+impl Env {
+    /// The environment described by an `evm.json`-style fixture. Without a
+    /// gas limit in `tx`, gas is not metered and the transaction gets
+    /// [`UNMETERED_GAS_LIMIT`].
+    pub fn from_fixture(tx: &Txn, block: &Block, spec: SpecId) -> Self {
+        Env {
+            cfg: CfgEnv {
+                chain_id: block.chainid.low_u64(),
+                spec,
+                disable_gas: tx.gas.is_none(),
+                ..Default::default()
+            },
+            block: block.into(),
+            tx: tx.into(),
+        }
+    }
+}
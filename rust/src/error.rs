@@ -0,0 +1,137 @@
+use primitive_types::U256;
+use std::fmt;
+
+/// Why a call frame halted exceptionally. Every halt consumes all gas given
+/// to the frame and reverts its state changes, except `CallTooDeep`,
+/// `OutOfFunds`, `OverflowPayment` and `NonceOverflow`, which refuse a call
+/// or creation before it starts and hand all gas back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Halt {
+    OutOfGas,
+    StackUnderflow,
+    StackOverflow,
+    InvalidJump,
+    InvalidOpcode(u8),
+    /// A state-modifying instruction ran inside STATICCALL.
+    StaticStateChange,
+    /// RETURNDATACOPY read past the end of the return data (EIP-211).
+    ReturnDataOutOfBounds,
+    /// CREATE or CREATE2 targeted an address that already has code or a nonce.
+    CreateCollision,
+    /// Init code returned more than the maximum contract size (EIP-170).
+    CreateContractSizeLimit,
+    /// Init code returned code starting with 0xef (EIP-3541).
+    CreateContractStartingWithEF,
+    /// Init code longer than the maximum init code size (EIP-3860).
+    CreateInitCodeSizeLimit,
+    /// A precompiled contract rejected its input.
+    PrecompileFailure,
+    /// The call would exceed the depth limit of 1024.
+    CallTooDeep,
+    /// The caller cannot afford the value it sends.
+    OutOfFunds,
+    /// The value would overflow the recipient's balance.
+    OverflowPayment,
+    /// The creator's nonce is already at its maximum (EIP-2681).
+    NonceOverflow,
+}
+
+impl fmt::Display for Halt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Halt::OutOfGas => write!(f, "out of gas"),
+            Halt::StackUnderflow => write!(f, "stack underflow"),
+            Halt::StackOverflow => write!(f, "stack limit reached 1024"),
+            Halt::InvalidJump => write!(f, "invalid jump destination"),
+            Halt::InvalidOpcode(opcode) => write!(f, "invalid opcode 0x{:02x}", opcode),
+            Halt::StaticStateChange => write!(f, "write protection"),
+            Halt::ReturnDataOutOfBounds => write!(f, "return data out of bounds"),
+            Halt::CreateCollision => write!(f, "contract address collision"),
+            Halt::CreateContractSizeLimit => write!(f, "max code size exceeded"),
+            Halt::CreateContractStartingWithEF => write!(f, "invalid code: must not begin with 0xef"),
+            Halt::CreateInitCodeSizeLimit => write!(f, "max initcode size exceeded"),
+            Halt::PrecompileFailure => write!(f, "precompiled contract failed"),
+            Halt::CallTooDeep => write!(f, "max call depth exceeded"),
+            Halt::OutOfFunds => write!(f, "insufficient balance for transfer"),
+            Halt::OverflowPayment => write!(f, "balance overflow in transfer"),
+            Halt::NonceOverflow => write!(f, "nonce uint64 overflow"),
+        }
+    }
+}
+
+impl std::error::Error for Halt {}
+
+/// Why a transaction could not be executed at all. Nothing is charged and
+/// the state is left untouched.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InvalidTransaction {
+    NonceTooLow {
+        tx: u64,
+        state: u64,
+    },
+    NonceTooHigh {
+        tx: u64,
+        state: u64,
+    },
+    /// The sender's nonce would overflow.
+    NonceOverflow,
+    /// The sender has code, so it cannot sign transactions (EIP-3607).
+    SenderHasCode,
+    IntrinsicGasTooLow {
+        intrinsic: u64,
+        limit: u64,
+    },
+    GasLimitAboveBlockLimit {
+        limit: u64,
+        block: u64,
+    },
+    GasPriceBelowBaseFee {
+        price: U256,
+        basefee: U256,
+    },
+    PriorityFeeAboveMaxFee,
+    InsufficientFunds {
+        needed: U256,
+        balance: U256,
+    },
+    InitCodeSizeLimit,
+    BlobFeeBelowBaseFee,
+}
+
+impl fmt::Display for InvalidTransaction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InvalidTransaction::NonceTooLow { tx, state } => {
+                write!(f, "nonce too low: tx {}, state {}", tx, state)
+            }
+            InvalidTransaction::NonceTooHigh { tx, state } => {
+                write!(f, "nonce too high: tx {}, state {}", tx, state)
+            }
+            InvalidTransaction::NonceOverflow => write!(f, "nonce has max value"),
+            InvalidTransaction::SenderHasCode => write!(f, "sender not an eoa"),
+            InvalidTransaction::IntrinsicGasTooLow { intrinsic, limit } => {
+                write!(f, "intrinsic gas too low: have {}, want {}", limit, intrinsic)
+            }
+            InvalidTransaction::GasLimitAboveBlockLimit { limit, block } => {
+                write!(f, "gas limit reached: tx {}, block {}", limit, block)
+            }
+            InvalidTransaction::GasPriceBelowBaseFee { price, basefee } => {
+                write!(f, "max fee per gas less than block base fee: {} < {}", price, basefee)
+            }
+            InvalidTransaction::PriorityFeeAboveMaxFee => {
+                write!(f, "max priority fee per gas higher than max fee per gas")
+            }
+            InvalidTransaction::InsufficientFunds { needed, balance } => {
+                write!(
+                    f,
+                    "insufficient funds for gas * price + value: have {}, want {}",
+                    balance, needed
+                )
+            }
+            InvalidTransaction::InitCodeSizeLimit => write!(f, "max initcode size exceeded"),
+            InvalidTransaction::BlobFeeBelowBaseFee => write!(f, "max fee per blob gas less than block blob gas fee"),
+        }
+    }
+}
+
+impl std::error::Error for InvalidTransaction {}
@@ -0,0 +1,438 @@
+use crate::gas;
+use crate::host::{CallInputs, CallScheme, CreateInputs, CreateScheme, Host, Log};
+use crate::{
+    Account, Address, BlockEnv, Bytes, CfgEnv, Env, Halt, Inspector, InvalidTransaction, SpecId, State, StateHost,
+    TxEnv, TxKind,
+};
+use primitive_types::U256;
+use std::collections::HashMap;
+
+/// How a transaction's top-level frame ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitReason {
+    /// STOP, RETURN or SELFDESTRUCT.
+    Success,
+    Revert,
+    Halt(Halt),
+}
+
+/// Outcome of a transaction that was valid and therefore executed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExecutionResult {
+    pub exit_reason: ExitReason,
+    /// RETURN or REVERT data. For a successful creation, the deployed code.
+    pub output: Bytes,
+    pub logs: Vec<Log>,
+    /// Gas charged to the sender, after refunds.
+    pub gas_used: u64,
+    pub gas_refunded: u64,
+    /// The deployed contract of a successful creation.
+    pub created_address: Option<Address>,
+    /// New value of every account the transaction changed, including gas
+    /// payments. `None` marks a deleted account.
+    pub state_changes: HashMap<Address, Option<Account>>,
+}
+
+impl ExecutionResult {
+    pub fn is_success(&self) -> bool {
+        self.exit_reason == ExitReason::Success
+    }
+}
+
+/// Builds an [`Evm`]. Anything not set keeps its default: an empty state and
+/// the default configuration, block and transaction.
+#[derive(Debug, Clone, Default)]
+pub struct EvmBuilder {
+    state: State,
+    env: Env,
+}
+
+impl EvmBuilder {
+    pub fn with_state(mut self, state: State) -> Self {
+        self.state = state;
+        self
+    }
+
+    pub fn with_cfg_env(mut self, cfg: CfgEnv) -> Self {
+        self.env.cfg = cfg;
+        self
+    }
+
+    pub fn with_block_env(mut self, block: BlockEnv) -> Self {
+        self.env.block = block;
+        self
+    }
+
+    pub fn with_tx_env(mut self, tx: TxEnv) -> Self {
+        self.env.tx = tx;
+        self
+    }
+
+    pub fn with_spec(mut self, spec: SpecId) -> Self {
+        self.env.cfg.spec = spec;
+        self
+    }
+
+    pub fn build(self) -> Evm {
+        Evm {
+            state: self.state,
+            env: self.env,
+        }
+    }
+}
+
+/// Executes transactions against a world state.
+#[derive(Debug, Clone, Default)]
+pub struct Evm {
+    state: State,
+    env: Env,
+}
+
+impl Evm {
+    pub fn builder() -> EvmBuilder {
+        EvmBuilder::default()
+    }
+
+    pub fn state(&self) -> &State {
+        &self.state
+    }
+
+    pub fn state_mut(&mut self) -> &mut State {
+        &mut self.state
+    }
+
+    pub fn into_state(self) -> State {
+        self.state
+    }
+
+    pub fn env(&self) -> &Env {
+        &self.env
+    }
+
+    /// The environment, for example to set up the next transaction.
+    pub fn env_mut(&mut self) -> &mut Env {
+        &mut self.env
+    }
+
+    /// Executes the transaction and commits its changes to the state.
+    pub fn transact(&mut self) -> Result<ExecutionResult, InvalidTransaction> {
+        transact(&mut self.state, &self.env, None)
+    }
+
+    /// Executes the transaction without changing the state.
+    pub fn call(&self) -> Result<ExecutionResult, InvalidTransaction> {
+        transact(&mut self.state.clone(), &self.env, None)
+    }
+
+    /// Executes the transaction like [`transact`](Self::transact), reporting
+    /// its execution to `inspector`.
+    pub fn inspect(&mut self, inspector: &mut dyn Inspector) -> Result<ExecutionResult, InvalidTransaction> {
+        transact(&mut self.state, &self.env, Some(inspector))
+    }
+
+    /// Executes the transaction like [`call`](Self::call), reporting its
+    /// execution to `inspector`.
+    pub fn inspect_call(&self, inspector: &mut dyn Inspector) -> Result<ExecutionResult, InvalidTransaction> {
+        transact(&mut self.state.clone(), &self.env, Some(inspector))
+    }
+}
+
+/// Validates the transaction in `env`, then buys its gas, runs it and pays
+/// the sender's change and the coinbase's fee.
+fn transact(
+    state: &mut State,
+    env: &Env,
+    inspector: Option<&mut dyn Inspector>,
+) -> Result<ExecutionResult, InvalidTransaction> {
+    let (cfg, block, tx) = (&env.cfg, &env.block, &env.tx);
+    let spec = cfg.spec;
+    let is_create = tx.kind == TxKind::Create;
+
+    if tx
+        .gas_priority_fee
+        .is_some_and(|priority_fee| priority_fee > tx.gas_price)
+    {
+        return Err(InvalidTransaction::PriorityFeeAboveMaxFee);
+    }
+    if spec.is_enabled(SpecId::London) && !cfg.disable_base_fee && tx.gas_price < block.basefee {
+        return Err(InvalidTransaction::GasPriceBelowBaseFee {
+            price: tx.gas_price,
+            basefee: block.basefee,
+        });
+    }
+    let intrinsic = gas::intrinsic_gas(spec, &tx.data, is_create, &tx.access_list);
+    if !cfg.disable_gas {
+        if tx.gas_limit > block.gas_limit {
+            return Err(InvalidTransaction::GasLimitAboveBlockLimit {
+                limit: tx.gas_limit,
+                block: block.gas_limit,
+            });
+        }
+        if intrinsic > tx.gas_limit {
+            return Err(InvalidTransaction::IntrinsicGasTooLow {
+                intrinsic,
+                limit: tx.gas_limit,
+            });
+        }
+    }
+    if is_create && spec.is_enabled(SpecId::Shanghai) && tx.data.len() > gas::MAX_INITCODE_SIZE {
+        return Err(InvalidTransaction::InitCodeSizeLimit);
+    }
+
+    let sender = state.account(&tx.caller).cloned().unwrap_or_default();
+    if !sender.code.is_empty() {
+        return Err(InvalidTransaction::SenderHasCode);
+    }
+    if let Some(nonce) = tx.nonce.filter(|_| !cfg.disable_nonce_check) {
+        if nonce < sender.nonce {
+            return Err(InvalidTransaction::NonceTooLow {
+                tx: nonce,
+                state: sender.nonce,
+            });
+        }
+        if nonce > sender.nonce {
+            return Err(InvalidTransaction::NonceTooHigh {
+                tx: nonce,
+                state: sender.nonce,
+            });
+        }
+    }
+    if sender.nonce == u64::MAX {
+        return Err(InvalidTransaction::NonceOverflow);
+    }
+
+    let blob_gas = U256::from(gas::BLOB_GAS_PER_BLOB * tx.blob_hashes.len() as u64);
+    let blob_price = gas::blob_base_fee(U256::from(block.excess_blob_gas.unwrap_or_default()));
+    let max_blob_price = tx.max_fee_per_blob_gas.unwrap_or_default();
+    if !tx.blob_hashes.is_empty() && max_blob_price < blob_price {
+        return Err(InvalidTransaction::BlobFeeBelowBaseFee);
+    }
+    let needed = U256::from(tx.gas_limit)
+        .saturating_mul(tx.gas_price)
+        .saturating_add(blob_gas.saturating_mul(max_blob_price))
+        .saturating_add(tx.value);
+    if !cfg.disable_balance_check && sender.balance < needed {
+        return Err(InvalidTransaction::InsufficientFunds {
+            needed,
+            balance: sender.balance,
+        });
+    }
+
+    // Everything from here goes through the host's journal, which yields the
+    // state changes at the end.
+    let mut host = StateHost::new(state, env);
+    let gas_price = tx.effective_gas_price(block.basefee);
+    let gas_cost = U256::from(tx.gas_limit).saturating_mul(gas_price);
+    let blob_cost = blob_gas.saturating_mul(blob_price);
+    host.set_balance(
+        tx.caller,
+        sender.balance.saturating_sub(gas_cost.saturating_add(blob_cost)),
+    );
+    if !is_create {
+        // CREATE bumps the nonce itself, after deriving the address from it.
+        host.set_nonce(tx.caller, sender.nonce + 1);
+    }
+
+    if let Some(inspector) = inspector {
+        host = host.with_inspector(inspector);
+    }
+    let gas_limit = tx.gas_limit.saturating_sub(intrinsic);
+    let (outcome, created_address) = match tx.kind {
+        TxKind::Call(to) => {
+            let outcome = host.call(CallInputs {
+                scheme: CallScheme::Call,
+                code_address: to,
+                address: to,
+                caller: tx.caller,
+                value: tx.value,
+                input: tx.data.clone(),
+                gas_limit,
+                is_static: false,
+                depth: 0,
+            });
+            (outcome, None)
+        }
+        TxKind::Create => {
+            let outcome = host.create(CreateInputs {
+                scheme: CreateScheme::Create,
+                caller: tx.caller,
+                value: tx.value,
+                init_code: tx.data.clone(),
+                gas_limit,
+                depth: 0,
+            });
+            (outcome.result, outcome.address)
+        }
+    };
+
+    // At most a fifth of the gas used is refunded, or a half before London (EIP-3529).
+    let mut gas_used = tx.gas_limit.saturating_sub(outcome.gas_left);
+    let refund_quotient = if spec.is_enabled(SpecId::London) { 5 } else { 2 };
+    let gas_refunded = (outcome.refund.max(0) as u64).min(gas_used / refund_quotient);
+    gas_used -= gas_refunded;
+
+    let change = U256::from(tx.gas_limit - gas_used).saturating_mul(gas_price);
+    let balance = host.balance(tx.caller);
+    host.set_balance(tx.caller, balance.saturating_add(change));
+    // The base fee is burnt from London on; the coinbase only gets the tip.
+    let tip = if spec.is_enabled(SpecId::London) {
+        gas_price.saturating_sub(block.basefee)
+    } else {
+        gas_price
+    };
+    let balance = host.balance(block.coinbase);
+    host.set_balance(
+        block.coinbase,
+        balance.saturating_add(U256::from(gas_used).saturating_mul(tip)),
+    );
+    host.touch(block.coinbase);
+    let (logs, state_changes) = host.finish_with_changes();
+
+    let exit_reason = match (outcome.success, outcome.halt) {
+        (true, _) => ExitReason::Success,
+        (false, Some(halt)) => ExitReason::Halt(halt),
+        (false, None) => ExitReason::Revert,
+    };
+    Ok(ExecutionResult {
+        exit_reason,
+        output: outcome.output,
+        logs: if outcome.success { logs } else { Vec::new() },
+        gas_used,
+        gas_refunded,
+        created_address,
+        state_changes,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contract_call() -> EvmBuilder {
+        let (sender, contract) = (Address::repeat_byte(0x10), Address::repeat_byte(0x20));
+        let mut state = State::new();
+        state.insert(
+            sender,
+            Account {
+                balance: U256::from(1_000_000_000u64),
+                ..Default::default()
+            },
+        );
+        state.insert(
+            contract,
+            Account {
+                // PUSH1 1 PUSH1 0 SSTORE STOP
+                code: vec![0x60, 0x01, 0x60, 0x00, 0x55, 0x00],
+                ..Default::default()
+            },
+        );
+        Evm::builder()
+            .with_state(state)
+            .with_spec(SpecId::Cancun)
+            .with_tx_env(TxEnv {
+                caller: sender,
+                kind: TxKind::Call(contract),
+                gas_limit: 100_000,
+                gas_price: U256::from(10),
+                ..Default::default()
+            })
+    }
+
+    #[test]
+    fn transact_commits_and_call_does_not() {
+        let contract = Address::repeat_byte(0x20);
+        let mut evm = contract_call().build();
+        let result = evm.call().unwrap();
+        assert!(result.is_success());
+        // 21000 intrinsic, two PUSH1 and a cold SSTORE of a new slot.
+        assert_eq!(result.gas_used, 21_000 + 3 + 3 + 22_100);
+        assert!(evm.state().account(&contract).unwrap().storage.is_empty());
+
+        let result = evm.transact().unwrap();
+        assert_eq!(result.gas_used, 43_106);
+        let sender = evm.state().account(&Address::repeat_byte(0x10)).unwrap();
+        assert_eq!(sender.nonce, 1);
+        assert_eq!(sender.balance, U256::from(1_000_000_000u64 - 431_060));
+        assert_eq!(
+            evm.state().account(&contract).unwrap().storage.get(&U256::zero()),
+            Some(&U256::one())
+        );
+        assert!(result.state_changes.contains_key(&contract));
+    }
+
+    #[test]
+    fn builder_settings_reach_validation() {
+        let evm = contract_call()
+            .with_block_env(BlockEnv {
+                gas_limit: 50_000,
+                ..Default::default()
+            })
+            .build();
+        assert_eq!(
+            evm.call(),
+            Err(InvalidTransaction::GasLimitAboveBlockLimit {
+                limit: 100_000,
+                block: 50_000
+            })
+        );
+
+        let mut evm = contract_call().build();
+        evm.env_mut().tx.nonce = Some(3);
+        assert_eq!(evm.call(), Err(InvalidTransaction::NonceTooHigh { tx: 3, state: 0 }));
+    }
+
+    #[test]
+    fn selfdestruct_refund_ends_in_london() {
+        let gas_used = |spec| {
+            let mut evm = contract_call().with_spec(spec).build();
+            // CALLER SELFDESTRUCT
+            evm.state_mut().account_mut(&Address::repeat_byte(0x20)).code = vec![0x33, 0xff];
+            let result = evm.call().unwrap();
+            (result.gas_used, result.gas_refunded)
+        };
+        assert_eq!(gas_used(SpecId::London), (26_002, 0));
+        // The 24000 refund is capped at half the gas used.
+        assert_eq!(gas_used(SpecId::Berlin), (13_001, 13_001));
+    }
+
+    #[test]
+    fn state_changes_are_what_a_diff_of_the_state_finds() {
+        let contract = Address::repeat_byte(0x20);
+        let changes = |spec, code: &str, storage: &[(u64, u64)]| {
+            let mut evm = contract_call().with_spec(spec).build();
+            let account = evm.state_mut().account_mut(&contract);
+            account.code = hex::decode(code).unwrap();
+            account.storage = storage
+                .iter()
+                .map(|&(key, value)| (U256::from(key), U256::from(value)))
+                .collect();
+            let pre = evm.state().clone();
+            let result = evm.transact().unwrap();
+
+            let post = evm.state();
+            let mut diff: HashMap<Address, Option<Account>> = post
+                .iter()
+                .filter(|(address, account)| pre.account(address) != Some(*account))
+                .map(|(address, account)| (*address, Some(account.clone())))
+                .collect();
+            for (address, _) in pre.iter() {
+                if post.account(address).is_none() {
+                    diff.insert(*address, None);
+                }
+            }
+            assert_eq!(result.state_changes, diff);
+            result.state_changes
+        };
+
+        // PUSH1 1 PUSH1 0 SSTORE STOP
+        let stored = changes(SpecId::Cancun, "600160005500", &[]);
+        assert_eq!(stored.len(), 3);
+        assert!(stored.contains_key(&contract));
+        // The same, then REVERT.
+        assert!(!changes(SpecId::Cancun, "600160005560006000fd", &[]).contains_key(&contract));
+        // Writes 2 over 1, then 1 again.
+        assert!(!changes(SpecId::Cancun, "60026000556001600055", &[(0, 1)]).contains_key(&contract));
+        // CALLER SELFDESTRUCT deletes the contract before Cancun.
+        assert_eq!(changes(SpecId::Shanghai, "33ff", &[(0, 1)]).get(&contract), Some(&None));
+    }
+}
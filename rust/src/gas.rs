@@ -0,0 +1,231 @@
+//! Gas schedule for the interpreter.
+//!
+//! Static costs are charged before an instruction runs; dynamic parts
+//! (memory expansion, copies, account access) are charged by the
+//! instruction itself.
+
+use crate::{AccessListItem, SpecId};
+use primitive_types::U256;
+
+pub const WARM_STORAGE_READ_COST: u64 = 100;
+pub const COLD_ACCOUNT_ACCESS_COST: u64 = 2600;
+pub const COLD_SLOAD_COST: u64 = 2100;
+pub const COPY: u64 = 3;
+pub const KECCAK_WORD: u64 = 6;
+pub const CALL_VALUE: u64 = 9000;
+pub const CALL_STIPEND: u64 = 2300;
+pub const NEW_ACCOUNT: u64 = 25000;
+pub const MEMORY: u64 = 3;
+pub const LOG_DATA: u64 = 8;
+pub const INITCODE_WORD: u64 = 2;
+pub const CODE_DEPOSIT: u64 = 200;
+
+pub const TX: u64 = 21000;
+pub const TX_CREATE: u64 = 32000;
+pub const TX_DATA_ZERO: u64 = 4;
+pub const ACCESS_LIST_ADDRESS: u64 = 2400;
+pub const ACCESS_LIST_STORAGE_KEY: u64 = 1900;
+pub const BLOB_GAS_PER_BLOB: u64 = 131072;
+
+pub const SSTORE_SET: u64 = 20000;
+pub const SSTORE_RESET: u64 = 5000 - COLD_SLOAD_COST;
+pub const SSTORE_CLEARS_SCHEDULE: i64 = 4800;
+/// Refund for clearing a slot before London lowered it (EIP-3529).
+pub const SSTORE_CLEARS_SCHEDULE_BEFORE_LONDON: i64 = 15000;
+/// Refund for destroying a contract, removed in London (EIP-3529).
+pub const SELFDESTRUCT_REFUND: i64 = 24000;
+/// SSTORE fails when no more than the call stipend is left (EIP-2200).
+pub const SSTORE_SENTRY: u64 = CALL_STIPEND;
+
+pub const MAX_CODE_SIZE: usize = 24576;
+pub const MAX_INITCODE_SIZE: usize = 2 * MAX_CODE_SIZE;
+
+pub const MIN_BASE_FEE_PER_BLOB_GAS: u64 = 1;
+pub const BLOB_BASE_FEE_UPDATE_FRACTION: u64 = 3338477;
+
+/// Cost that does not depend on the operands of `opcode`.
+pub fn static_cost(opcode: u8) -> u64 {
+    match opcode {
+        0x00 => 0,
+        0x01 | 0x03 => 3,
+        0x02 | 0x04..=0x07 => 5,
+        0x08 | 0x09 => 8,
+        0x0a => 10,
+        0x0b => 5,
+        0x10..=0x1d => 3,
+        0x20 => 30,
+        0x30 | 0x32..=0x34 => 2,
+        0x35 => 3,
+        0x36 => 2,
+        0x37 => 3,
+        0x38 => 2,
+        0x39 => 3,
+        0x3a => 2,
+        0x3d => 2,
+        0x3e => 3,
+        0x40 => 20,
+        0x41..=0x46 => 2,
+        0x47 => 5,
+        0x48 => 2,
+        0x49 => 3,
+        0x4a => 2,
+        0x50 => 2,
+        0x51..=0x53 => 3,
+        0x56 => 8,
+        0x57 => 10,
+        0x58..=0x5a => 2,
+        0x5b => 1,
+        0x5c | 0x5d => WARM_STORAGE_READ_COST,
+        0x5e => 3,
+        0x5f => 2,
+        0x60..=0x9f => 3,
+        0xa0..=0xa4 => 375 * (opcode - 0x9f) as u64,
+        0xf0 | 0xf5 => 32000,
+        0xff => 5000,
+        // Account and storage access opcodes (BALANCE, EXTCODE*, SLOAD,
+        // SSTORE, CALL*) are priced entirely by the EIP-2929 cold/warm rules.
+        _ => 0,
+    }
+}
+
+/// Cost of EXP's exponent on top of its static cost: per byte of the
+/// exponent, 50 gas from Spurious Dragon (EIP-160) and 10 before.
+pub fn exp_cost(spec: SpecId, exponent: U256) -> u64 {
+    let per_byte = if spec.is_enabled(SpecId::SpuriousDragon) {
+        50
+    } else {
+        10
+    };
+    per_byte * exponent.bits().div_ceil(8) as u64
+}
+
+/// Cost of copying `size` bytes, rounded up to whole words.
+pub fn copy_cost(size: usize) -> u64 {
+    COPY * words(size)
+}
+
+/// Gas charged before a transaction runs: the base cost, calldata, access
+/// list and init code words.
+pub fn intrinsic_gas(spec: SpecId, data: &[u8], is_create: bool, access_list: &[AccessListItem]) -> u64 {
+    let mut gas = TX;
+    if is_create && spec.is_enabled(SpecId::Homestead) {
+        gas += TX_CREATE;
+    }
+    let zeros = data.iter().filter(|byte| **byte == 0).count() as u64;
+    let non_zero_cost = if spec.is_enabled(SpecId::Istanbul) { 16 } else { 68 };
+    gas += zeros * TX_DATA_ZERO + (data.len() as u64 - zeros) * non_zero_cost;
+    for item in access_list {
+        gas += ACCESS_LIST_ADDRESS + ACCESS_LIST_STORAGE_KEY * item.storage_keys.len() as u64;
+    }
+    if is_create && spec.is_enabled(SpecId::Shanghai) {
+        gas += INITCODE_WORD * words(data.len());
+    }
+    gas
+}
+
+/// Cost of an SSTORE on a warm slot (EIP-2200 with EIP-2929 pricing).
+pub fn sstore_cost(original: U256, current: U256, new: U256) -> u64 {
+    if current == new || original != current {
+        WARM_STORAGE_READ_COST
+    } else if original.is_zero() {
+        SSTORE_SET
+    } else {
+        SSTORE_RESET
+    }
+}
+
+/// Refund earned, or taken back when negative, by an SSTORE (EIP-2200, with
+/// the lower clearing refund of EIP-3529 from London).
+pub fn sstore_refund(spec: SpecId, original: U256, current: U256, new: U256) -> i64 {
+    let clears_schedule = match spec.is_enabled(SpecId::London) {
+        true => SSTORE_CLEARS_SCHEDULE,
+        false => SSTORE_CLEARS_SCHEDULE_BEFORE_LONDON,
+    };
+    if current == new {
+        return 0;
+    }
+    if original == current {
+        return if new.is_zero() { clears_schedule } else { 0 };
+    }
+    let mut refund = 0;
+    if !original.is_zero() {
+        if current.is_zero() {
+            refund -= clears_schedule;
+        } else if new.is_zero() {
+            refund += clears_schedule;
+        }
+    }
+    if original == new {
+        let restored = if original.is_zero() { SSTORE_SET } else { SSTORE_RESET };
+        refund += (restored - WARM_STORAGE_READ_COST) as i64;
+    }
+    refund
+}
+
+/// Blob gas price for a block with `excess_blob_gas`, using the EIP-4844
+/// `fake_exponential` approximation of `MIN_BASE_FEE * e^(excess / FRACTION)`.
+/// Saturates at `U256::MAX` instead of overflowing.
+pub fn blob_base_fee(excess_blob_gas: U256) -> U256 {
+    let factor = U256::from(MIN_BASE_FEE_PER_BLOB_GAS);
+    let denominator = U256::from(BLOB_BASE_FEE_UPDATE_FRACTION);
+
+    let mut output = U256::zero();
+    let mut accumulator = factor * denominator;
+    let mut i = U256::one();
+    while !accumulator.is_zero() {
+        output = output.saturating_add(accumulator);
+        accumulator = match accumulator.checked_mul(excess_blob_gas) {
+            Some(product) => product / (denominator * i),
+            None => return U256::MAX,
+        };
+        i += U256::one();
+    }
+    output / denominator
+}
+
+/// Total cost of a memory of `size` bytes (yellow paper `C_mem`).
+pub fn memory_cost(size: usize) -> u64 {
+    let words = words(size);
+    MEMORY * words + words * words / 512
+}
+
+fn words(size: usize) -> u64 {
+    (size as u64).div_ceil(32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TARGET_BLOB_GAS_PER_BLOCK;
+
+    #[test]
+    fn exp_charges_per_exponent_byte() {
+        assert_eq!(exp_cost(SpecId::Cancun, U256::zero()), 0);
+        assert_eq!(exp_cost(SpecId::Cancun, U256::from(2)), 50);
+        assert_eq!(exp_cost(SpecId::Cancun, U256::from(0x100)), 100);
+        assert_eq!(exp_cost(SpecId::Cancun, U256::MAX), 32 * 50);
+        assert_eq!(exp_cost(SpecId::Homestead, U256::from(0x100)), 20);
+    }
+
+    #[test]
+    fn sstore_clearing_refund_drops_in_london() {
+        let (zero, one) = (U256::zero(), U256::one());
+        assert_eq!(sstore_refund(SpecId::London, one, one, zero), 4800);
+        assert_eq!(sstore_refund(SpecId::Berlin, one, one, zero), 15000);
+        // Writing the slot back after clearing it takes the refund back.
+        assert_eq!(sstore_refund(SpecId::Berlin, one, zero, one), -15000 + 2800);
+        assert_eq!(sstore_refund(SpecId::Berlin, zero, one, zero), 19900);
+    }
+
+    #[test]
+    fn blob_base_fee_follows_fake_exponential() {
+        assert_eq!(blob_base_fee(U256::zero()), U256::one());
+        assert_eq!(blob_base_fee(U256::from(TARGET_BLOB_GAS_PER_BLOCK)), U256::one());
+        assert_eq!(blob_base_fee(U256::from(10_000_000)), U256::from(19));
+        assert_eq!(
+            blob_base_fee(U256::from(100_000_000)),
+            U256::from(10_203_769_476_395u64)
+        );
+        assert_eq!(blob_base_fee(U256::MAX), U256::MAX);
+    }
+}
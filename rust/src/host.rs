@@ -0,0 +1,180 @@
+//! The boundary between the interpreter and the world it runs in.
+//!
+//! The interpreter only computes. Everything it reads from or writes to
+//! accounts, storage, logs and the block or transaction environment goes
+//! through a [`Host`]. Sub-calls and contract creation are handed to the host
+//! too, which runs the callee with [`execute`](crate::execute) and decides what
+//! survives when it fails.
+
+use crate::primitives::de;
+use crate::{Address, Bytes, Halt, Inspector, SpecId};
+use primitive_types::{H256, U256};
+use serde::Deserialize;
+
+/// An event emitted by LOG0 to LOG4.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct Log {
+    #[serde(deserialize_with = "de::address")]
+    pub address: Address,
+    #[serde(default, deserialize_with = "de::h256_list")]
+    pub topics: Vec<H256>,
+    #[serde(default)]
+    pub data: Bytes,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CallScheme {
+    Call,
+    CallCode,
+    DelegateCall,
+    StaticCall,
+}
+
+/// A message call made by CALL, CALLCODE, DELEGATECALL or STATICCALL.
+#[derive(Debug, Clone)]
+pub struct CallInputs {
+    pub scheme: CallScheme,
+    /// Account whose code runs.
+    pub code_address: Address,
+    /// Account the code runs as: its storage, balance and ADDRESS.
+    pub address: Address,
+    pub caller: Address,
+    /// CALLVALUE seen by the callee. Only CALL moves it between accounts.
+    pub value: U256,
+    pub input: Bytes,
+    pub gas_limit: u64,
+    pub is_static: bool,
+    /// Depth of the callee. The transaction's own frame is depth 0.
+    pub depth: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CreateScheme {
+    Create,
+    Create2 { salt: U256 },
+}
+
+/// A contract creation made by CREATE or CREATE2.
+#[derive(Debug, Clone)]
+pub struct CreateInputs {
+    pub scheme: CreateScheme,
+    pub caller: Address,
+    pub value: U256,
+    pub init_code: Bytes,
+    pub gas_limit: u64,
+    /// Depth of the init code frame.
+    pub depth: usize,
+}
+
+/// How a sub-call or creation ended, as seen by the frame that made it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CallOutcome {
+    pub success: bool,
+    /// Becomes the caller's return data buffer, except after a successful
+    /// creation, where it holds the deployed code.
+    pub output: Bytes,
+    /// Gas handed back to the caller.
+    pub gas_left: u64,
+    /// Gas refund earned by the callee. Zero unless it succeeded.
+    pub refund: i64,
+    pub halt: Option<Halt>,
+}
+
+impl CallOutcome {
+    /// A call that never started, for example because the caller cannot
+    /// afford the value or the depth limit is reached. All gas is returned.
+    pub fn refused(gas_limit: u64, reason: Halt) -> Self {
+        CallOutcome {
+            gas_left: gas_limit,
+            halt: Some(reason),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CreateOutcome {
+    /// The new contract, or `None` when creation failed.
+    pub address: Option<Address>,
+    pub result: CallOutcome,
+}
+
+/// Values of a storage slot around an SSTORE, which together decide its gas
+/// cost and refund (EIP-2200, EIP-3529).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SStoreResult {
+    /// Value at the start of the transaction.
+    pub original: U256,
+    /// Value before this SSTORE.
+    pub current: U256,
+    pub new: U256,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SelfDestructResult {
+    /// Whether the destroyed account held a balance.
+    pub had_value: bool,
+    /// Whether the beneficiary existed and was not empty.
+    pub target_exists: bool,
+    /// Whether the account had already self-destructed in this transaction.
+    pub previously_destroyed: bool,
+}
+
+/// Everything the interpreter needs from outside a call frame.
+pub trait Host {
+    /// The fork whose rules apply.
+    fn spec(&self) -> SpecId;
+
+    /// Whether gas is limited. Without a limit GAS reports `U256::MAX` and
+    /// calls ignore their gas argument.
+    fn metered(&self) -> bool {
+        true
+    }
+
+    fn origin(&self) -> Address;
+    fn gas_price(&self) -> U256;
+    /// The transaction's blob versioned hash at `index`, if there is one.
+    fn blob_hash(&self, index: usize) -> Option<H256>;
+
+    fn coinbase(&self) -> Address;
+    fn timestamp(&self) -> U256;
+    fn number(&self) -> U256;
+    fn difficulty(&self) -> U256;
+    /// Beacon chain randomness, `None` before the Merge.
+    fn prevrandao(&self) -> Option<H256>;
+    fn gas_limit(&self) -> U256;
+    fn chain_id(&self) -> U256;
+    fn base_fee(&self) -> U256;
+    fn blob_base_fee(&self) -> U256;
+    /// Hash of block `number`. Only asked for the 256 most recent blocks.
+    fn block_hash(&mut self, number: u64) -> H256;
+
+    /// Marks `address` as accessed, returning whether it was cold (EIP-2929).
+    fn access_account(&mut self, address: Address) -> bool;
+    /// Marks a storage slot as accessed, returning whether it was cold (EIP-2929).
+    fn access_storage(&mut self, address: Address, key: U256) -> bool;
+
+    /// Whether `address` is missing or empty (EIP-161).
+    fn is_empty(&mut self, address: Address) -> bool;
+    fn balance(&mut self, address: Address) -> U256;
+    fn code(&mut self, address: Address) -> Bytes;
+    /// Keccak of the code, or zero for a missing or empty account (EIP-1052).
+    fn code_hash(&mut self, address: Address) -> H256;
+
+    fn sload(&mut self, address: Address, key: U256) -> U256;
+    fn sstore(&mut self, address: Address, key: U256, value: U256) -> SStoreResult;
+    fn tload(&mut self, address: Address, key: U256) -> U256;
+    fn tstore(&mut self, address: Address, key: U256, value: U256);
+
+    fn log(&mut self, log: Log);
+    /// Destroys `address`, sending its balance to `target`.
+    fn selfdestruct(&mut self, address: Address, target: Address) -> SelfDestructResult;
+
+    fn call(&mut self, inputs: CallInputs) -> CallOutcome;
+    fn create(&mut self, inputs: CreateInputs) -> CreateOutcome;
+
+    /// The inspector to report each instruction to, if any.
+    fn inspector(&mut self) -> Option<&mut dyn Inspector> {
+        None
+    }
+}
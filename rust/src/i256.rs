@@ -0,0 +1,234 @@
+//! Two's-complement signed arithmetic on `U256` words, as used by SDIV,
+//! SMOD, SLT, SGT, SAR and SIGNEXTEND.
+
+use primitive_types::U256;
+use std::cmp::Ordering;
+
+/// The most negative value, -2^255.
+pub const MIN: U256 = U256([0, 0, 0, 0x8000_0000_0000_0000]);
+
+/// All bits set, which is -1.
+pub const MINUS_ONE: U256 = U256::MAX;
+
+pub fn is_negative(value: U256) -> bool {
+    value.bit(255)
+}
+
+/// Two's-complement negation. MIN negates to itself.
+pub fn neg(value: U256) -> U256 {
+    (!value).overflowing_add(U256::one()).0
+}
+
+/// Magnitude of `value` as an unsigned number. The magnitude of MIN is 2^255.
+fn abs(value: U256) -> U256 {
+    if is_negative(value) {
+        neg(value)
+    } else {
+        value
+    }
+}
+
+/// Signed comparison of two words.
+pub fn cmp(a: U256, b: U256) -> Ordering {
+    match (is_negative(a), is_negative(b)) {
+        (true, false) => Ordering::Less,
+        (false, true) => Ordering::Greater,
+        // With equal signs the two's-complement bits order like the numbers.
+        _ => a.cmp(&b),
+    }
+}
+
+/// Signed division rounding towards zero (SDIV). Division by zero gives 0
+/// and MIN / -1 overflows back to MIN.
+pub fn div(a: U256, b: U256) -> U256 {
+    if b.is_zero() {
+        return U256::zero();
+    }
+    if a == MIN && b == MINUS_ONE {
+        return MIN;
+    }
+    let quotient = abs(a) / abs(b);
+    if is_negative(a) != is_negative(b) {
+        neg(quotient)
+    } else {
+        quotient
+    }
+}
+
+/// Signed remainder (SMOD). The result takes the sign of the dividend, and a
+/// zero divisor gives 0.
+pub fn rem(a: U256, b: U256) -> U256 {
+    if b.is_zero() {
+        return U256::zero();
+    }
+    let remainder = abs(a) % abs(b);
+    if is_negative(a) {
+        neg(remainder)
+    } else {
+        remainder
+    }
+}
+
+/// Arithmetic right shift (SAR). Shifts of 256 or more leave only the sign.
+pub fn sar(value: U256, shift: U256) -> U256 {
+    let negative = is_negative(value);
+    if shift >= U256::from(256) {
+        return if negative { MINUS_ONE } else { U256::zero() };
+    }
+    let shift = shift.as_usize();
+    if negative {
+        !(!value >> shift)
+    } else {
+        value >> shift
+    }
+}
+
+/// Sign-extends `value` from its low `byte + 1` bytes (SIGNEXTEND). From
+/// byte 31 on the value is returned unchanged.
+pub fn sign_extend(byte: U256, value: U256) -> U256 {
+    if byte >= U256::from(31) {
+        return value;
+    }
+    let sign_bit = byte.as_usize() * 8 + 7;
+    let mask = (U256::one() << (sign_bit + 1)) - 1;
+    if value.bit(sign_bit) {
+        value | !mask
+    } else {
+        value & mask
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAX: U256 = U256([u64::MAX, u64::MAX, u64::MAX, 0x7fff_ffff_ffff_ffff]);
+
+    fn int(value: i64) -> U256 {
+        if value < 0 {
+            neg(U256::from(value.unsigned_abs()))
+        } else {
+            U256::from(value)
+        }
+    }
+
+    #[test]
+    fn neg_and_sign() {
+        assert_eq!(neg(U256::zero()), U256::zero());
+        assert_eq!(neg(U256::one()), MINUS_ONE);
+        assert_eq!(neg(MINUS_ONE), U256::one());
+        assert_eq!(neg(MIN), MIN);
+        assert_eq!(neg(MAX), MIN + 1);
+        assert!(is_negative(MIN));
+        assert!(is_negative(MINUS_ONE));
+        assert!(!is_negative(MAX));
+        assert!(!is_negative(U256::zero()));
+    }
+
+    #[test]
+    fn div_cases() {
+        let cases = [
+            (int(10), int(3), int(3)),
+            (int(-10), int(3), int(-3)),
+            (int(10), int(-3), int(-3)),
+            (int(-10), int(-3), int(3)),
+            (int(-1), int(2), int(0)),
+            (int(7), int(0), int(0)),
+            (int(-7), int(0), int(0)),
+            (MIN, MINUS_ONE, MIN),
+            (MIN, int(1), MIN),
+            (MIN, MIN, int(1)),
+            (MIN, MAX, int(-1)),
+            (MAX, MIN, int(0)),
+            (MAX, MINUS_ONE, MIN + 1),
+            (MIN, int(2), neg(U256::one() << 254)),
+        ];
+        for (a, b, expected) in cases {
+            assert_eq!(div(a, b), expected, "{:x} / {:x}", a, b);
+        }
+    }
+
+    #[test]
+    fn rem_cases() {
+        let cases = [
+            (int(10), int(3), int(1)),
+            (int(-10), int(3), int(-1)),
+            (int(10), int(-3), int(1)),
+            (int(-10), int(-3), int(-1)),
+            (int(-8), int(3), int(-2)),
+            (int(7), int(0), int(0)),
+            (int(-7), int(0), int(0)),
+            (MIN, MINUS_ONE, int(0)),
+            (MIN, int(3), int(-2)),
+            (MIN, MIN, int(0)),
+            (MIN, MAX, int(-1)),
+            (MAX, MIN, MAX),
+            (MAX, int(-2), int(1)),
+        ];
+        for (a, b, expected) in cases {
+            assert_eq!(rem(a, b), expected, "{:x} % {:x}", a, b);
+        }
+    }
+
+    #[test]
+    fn cmp_cases() {
+        let ordered = [MIN, MIN + 1, int(-2), MINUS_ONE, int(0), int(1), int(2), MAX - 1, MAX];
+        for (i, &a) in ordered.iter().enumerate() {
+            for (j, &b) in ordered.iter().enumerate() {
+                assert_eq!(cmp(a, b), i.cmp(&j), "{:x} vs {:x}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn sar_cases() {
+        let cases = [
+            (int(16), U256::from(2), int(4)),
+            (int(-16), U256::from(2), int(-4)),
+            (int(-1), U256::from(1), int(-1)),
+            (int(-3), U256::from(1), int(-2)),
+            (MIN, U256::from(255), int(-1)),
+            (MIN, U256::from(254), int(-2)),
+            (MAX, U256::from(254), int(1)),
+            (MAX, U256::from(255), int(0)),
+            (int(5), U256::zero(), int(5)),
+            (MIN, U256::from(256), int(-1)),
+            (MAX, U256::from(256), int(0)),
+            // Only the low 32 bits of these shifts are 1.
+            (int(-16), (U256::one() << 32) + 1, int(-1)),
+            (int(16), (U256::one() << 32) + 1, int(0)),
+            (int(16), (U256::one() << 200) + 1, int(0)),
+            (MIN, U256::MAX, int(-1)),
+        ];
+        for (value, shift, expected) in cases {
+            assert_eq!(sar(value, shift), expected, "{:x} >> {:x}", value, shift);
+        }
+    }
+
+    #[test]
+    fn sign_extend_cases() {
+        let cases = [
+            (U256::zero(), U256::from(0xff), int(-1)),
+            (U256::zero(), U256::from(0x7f), int(0x7f)),
+            (U256::zero(), U256::from(0x12_80), int(-0x80)),
+            (U256::one(), U256::from(0x80_00), int(-0x8000)),
+            (U256::one(), U256::from(0xff_7f_ff), int(0x7fff)),
+            (U256::from(30), MIN >> 8, neg(MIN >> 8)),
+            (U256::from(30), MAX >> 8, MAX >> 8),
+            (U256::from(31), U256::from(0xff), U256::from(0xff)),
+            (U256::from(31), MIN, MIN),
+            (U256::from(32), U256::from(0xff), U256::from(0xff)),
+            (U256::MAX, U256::from(0xff), U256::from(0xff)),
+            (U256::one() << 64, U256::from(0xff), U256::from(0xff)),
+        ];
+        for (byte, value, expected) in cases {
+            assert_eq!(
+                sign_extend(byte, value),
+                expected,
+                "signextend({:x}, {:x})",
+                byte,
+                value
+            );
+        }
+    }
+}
@@ -0,0 +1,278 @@
+//! Hooks for watching and steering execution, for tracers, coverage and
+//! profiling tools.
+//!
+//! The interpreter reports every instruction to an [`Inspector`] twice: in
+//! [`step`](Inspector::step) before it runs and in
+//! [`step_end`](Inspector::step_end) after it. Both receive a [`FrameState`].
+//! Calls, creations, logs and self-destructs come right after the `step` of
+//! the instruction that made them, logs and self-destructs with its frame.
+//! The transaction's own call or creation comes first of all, at depth 0.
+//!
+//! Besides watching, an inspector can steer: `step` can halt the frame, and
+//! `call` and `create` can change their inputs or replace their outcomes.
+
+use crate::host::{CallInputs, CallOutcome, CreateInputs, CreateOutcome, Log};
+use crate::{Address, Halt};
+use primitive_types::U256;
+
+/// The interpreter's view of a call frame around one instruction.
+#[derive(Debug, Clone, Copy)]
+pub struct FrameState<'a> {
+    pub pc: usize,
+    pub opcode: u8,
+    /// Stack items, bottom first.
+    pub stack: &'a [U256],
+    pub memory: &'a [u8],
+    pub gas_left: u64,
+    /// Gas refund earned so far by this frame.
+    pub refund: i64,
+    /// Depth of the frame. The transaction's own frame is depth 0.
+    pub depth: usize,
+    /// Account the code runs as.
+    pub address: Address,
+    /// The code the frame runs: the callee's, or the init code of a creation.
+    pub code: &'a [u8],
+    /// Output of the frame's most recent sub-call.
+    pub return_data: &'a [u8],
+}
+
+/// What the interpreter does after [`Inspector::step`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StepControl {
+    /// Runs the instruction.
+    #[default]
+    Continue,
+    /// Halts the frame exceptionally instead, as if the instruction had.
+    Halt(Halt),
+}
+
+/// Receives execution events. Every method does nothing by default.
+pub trait Inspector {
+    /// Called before the instruction at `frame.pc` runs, before its gas is
+    /// charged. Its answer decides whether the instruction runs.
+    fn step(&mut self, _frame: &FrameState) -> StepControl {
+        StepControl::Continue
+    }
+
+    /// Called after the instruction ran, including one that ended the frame.
+    /// `frame.pc` and `frame.opcode` still refer to that instruction.
+    fn step_end(&mut self, _frame: &FrameState) {}
+
+    /// Called when the CALL or CREATE family instruction in `frame` has been
+    /// charged and hands over to its callee, before [`call`](Self::call) or
+    /// [`create`](Self::create). `frame.gas_left` no longer includes the gas
+    /// given to the callee. [`step_end`](Self::step_end) follows once the
+    /// callee has returned.
+    fn step_suspend(&mut self, _frame: &FrameState) {}
+
+    /// Called before a message call. The inputs can be changed, and returning
+    /// an outcome skips the call in favour of it.
+    fn call(&mut self, _inputs: &mut CallInputs) -> Option<CallOutcome> {
+        None
+    }
+
+    /// Called after a message call, with the outcome its caller will see.
+    fn call_end(&mut self, _inputs: &CallInputs, outcome: CallOutcome) -> CallOutcome {
+        outcome
+    }
+
+    /// Called before a contract creation. The inputs can be changed, and
+    /// returning an outcome skips the creation in favour of it.
+    fn create(&mut self, _inputs: &mut CreateInputs) -> Option<CreateOutcome> {
+        None
+    }
+
+    /// Called after a contract creation, with the outcome its caller will see.
+    fn create_end(&mut self, _inputs: &CreateInputs, outcome: CreateOutcome) -> CreateOutcome {
+        outcome
+    }
+
+    /// Called when LOG0 to LOG4 in `frame` emit `log`. It is dropped again if
+    /// the frame or one of its callers fails.
+    fn log(&mut self, _frame: &FrameState, _log: &Log) {}
+
+    /// Called when `address` self-destructs in `frame`, sending `value` to
+    /// `target`.
+    fn selfdestruct(&mut self, _frame: &FrameState, _address: Address, _target: Address, _value: U256) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Account, Evm, ExitReason, State, TxEnv, TxKind};
+
+    /// Contract 0x20 calls 0x30 with all its gas; 0x30 emits an empty LOG0.
+    fn nested_call() -> Evm {
+        let mut state = State::new();
+        state.insert(
+            Address::from_low_u64_be(0x20),
+            Account {
+                // PUSH1 0 (x5) PUSH1 0x30 GAS CALL STOP
+                code: hex::decode("6000600060006000600060305af100").unwrap(),
+                ..Default::default()
+            },
+        );
+        state.insert(
+            Address::from_low_u64_be(0x30),
+            Account {
+                // PUSH1 0 PUSH1 0 LOG0 STOP
+                code: hex::decode("60006000a000").unwrap(),
+                ..Default::default()
+            },
+        );
+        Evm::builder()
+            .with_state(state)
+            .with_tx_env(TxEnv {
+                caller: Address::from_low_u64_be(0x10),
+                kind: TxKind::Call(Address::from_low_u64_be(0x20)),
+                ..Default::default()
+            })
+            .build()
+    }
+
+    #[derive(Default)]
+    struct Recorder {
+        steps: Vec<(usize, u8)>,
+        step_ends: usize,
+        calls: Vec<(usize, Address)>,
+        call_ends: Vec<bool>,
+        /// Depth and pc of each log.
+        logs: Vec<(usize, usize)>,
+        selfdestructs: Vec<(usize, Address, U256)>,
+        /// Pc of each suspended instruction and the number of calls seen by then.
+        suspends: Vec<(usize, usize)>,
+        skip: Option<Address>,
+        /// Halts every frame at this opcode.
+        halt_at: Option<u8>,
+    }
+
+    impl Inspector for Recorder {
+        fn step(&mut self, frame: &FrameState) -> StepControl {
+            self.steps.push((frame.depth, frame.opcode));
+            match self.halt_at {
+                Some(opcode) if opcode == frame.opcode => StepControl::Halt(Halt::InvalidOpcode(opcode)),
+                _ => StepControl::Continue,
+            }
+        }
+
+        fn step_end(&mut self, _frame: &FrameState) {
+            self.step_ends += 1;
+        }
+
+        fn step_suspend(&mut self, frame: &FrameState) {
+            self.suspends.push((frame.pc, self.calls.len()));
+        }
+
+        fn call(&mut self, inputs: &mut CallInputs) -> Option<CallOutcome> {
+            self.calls.push((inputs.depth, inputs.address));
+            (Some(inputs.address) == self.skip).then(|| CallOutcome {
+                success: false,
+                output: Default::default(),
+                gas_left: inputs.gas_limit,
+                refund: 0,
+                halt: None,
+            })
+        }
+
+        fn call_end(&mut self, _inputs: &CallInputs, outcome: CallOutcome) -> CallOutcome {
+            self.call_ends.push(outcome.success);
+            outcome
+        }
+
+        fn log(&mut self, frame: &FrameState, _log: &Log) {
+            self.logs.push((frame.depth, frame.pc));
+        }
+
+        fn selfdestruct(&mut self, frame: &FrameState, _address: Address, target: Address, value: U256) {
+            self.selfdestructs.push((frame.pc, target, value));
+        }
+    }
+
+    #[test]
+    fn reports_steps_calls_and_logs() {
+        let mut recorder = Recorder::default();
+        let result = nested_call().inspect(&mut recorder).unwrap();
+        assert!(result.is_success());
+        assert_eq!(
+            recorder.calls,
+            vec![(0, Address::from_low_u64_be(0x20)), (1, Address::from_low_u64_be(0x30))]
+        );
+        assert_eq!(recorder.call_ends, vec![true, true]);
+        assert_eq!(recorder.steps.len(), 9 + 4);
+        assert_eq!(recorder.step_ends, recorder.steps.len());
+        assert_eq!(recorder.steps[7], (0, 0xf1));
+        assert_eq!(recorder.steps[8], (1, 0x60));
+        assert_eq!(recorder.steps.last(), Some(&(0, 0x00)));
+        assert_eq!(recorder.logs, vec![(1, 4)]);
+        assert_eq!(result.logs.len(), 1);
+        // The CALL is suspended after the transaction's call, before its own.
+        assert_eq!(recorder.suspends, vec![(13, 1)]);
+    }
+
+    #[test]
+    fn call_hook_can_skip_a_call() {
+        let mut recorder = Recorder {
+            skip: Some(Address::from_low_u64_be(0x30)),
+            ..Default::default()
+        };
+        let result = nested_call().inspect(&mut recorder).unwrap();
+        assert!(result.is_success());
+        assert!(recorder.steps.iter().all(|&(depth, _)| depth == 0));
+        assert!(recorder.logs.is_empty());
+        assert!(result.logs.is_empty());
+    }
+
+    #[test]
+    fn step_can_halt_a_frame() {
+        let mut recorder = Recorder {
+            halt_at: Some(0xa0),
+            ..Default::default()
+        };
+        let result = nested_call().inspect(&mut recorder).unwrap();
+        // The callee halts at LOG0, so its log is never made and its gas is
+        // gone, but the caller carries on.
+        assert!(result.is_success());
+        assert_eq!(recorder.call_ends, vec![false, true]);
+        assert!(recorder.logs.is_empty());
+        assert!(result.logs.is_empty());
+        assert_eq!(recorder.steps.len(), 9 + 3);
+        assert_eq!(recorder.step_ends, recorder.steps.len());
+
+        let mut recorder = Recorder {
+            halt_at: Some(0xf1),
+            ..Default::default()
+        };
+        let result = nested_call().inspect(&mut recorder).unwrap();
+        assert_eq!(result.exit_reason, ExitReason::Halt(Halt::InvalidOpcode(0xf1)));
+        assert_eq!(recorder.calls.len(), 1);
+    }
+
+    #[test]
+    fn selfdestruct_sees_the_frame_and_the_value() {
+        let mut state = State::new();
+        state.insert(
+            Address::from_low_u64_be(0x20),
+            Account {
+                // PUSH1 0 PUSH1 0 LOG0 PUSH1 0x40 SELFDESTRUCT
+                code: hex::decode("60006000a06040ff").unwrap(),
+                balance: U256::from(5),
+                ..Default::default()
+            },
+        );
+        let mut recorder = Recorder::default();
+        let mut evm = Evm::builder()
+            .with_state(state)
+            .with_tx_env(TxEnv {
+                caller: Address::from_low_u64_be(0x10),
+                kind: TxKind::Call(Address::from_low_u64_be(0x20)),
+                ..Default::default()
+            })
+            .build();
+        assert!(evm.inspect(&mut recorder).unwrap().is_success());
+        assert_eq!(recorder.logs, vec![(0, 4)]);
+        assert_eq!(
+            recorder.selfdestructs,
+            vec![(7, Address::from_low_u64_be(0x40), U256::from(5))]
+        );
+    }
+}
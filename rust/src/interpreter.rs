@@ -0,0 +1,1128 @@
+//! The bytecode interpreter. It runs one call frame at a time and reaches
+//! the outside world only through a [`Host`].
+
+use crate::gas;
+use crate::host::{CallInputs, CallScheme, CreateInputs, CreateScheme, Host, Log};
+use crate::{address_to_u256, arith, i256, keccak256, opcode, u256_to_address};
+use crate::{Address, FrameState, Halt, SpecId, Stack, StepControl};
+use primitive_types::{H256, U256};
+use std::cmp::Ordering;
+
+/// Marks the positions of valid JUMPDEST instructions, skipping PUSH data.
+fn analyze_jumpdests(code: &[u8]) -> Vec<bool> {
+    let mut jumpdests = vec![false; code.len()];
+    let mut pc = 0;
+    while pc < code.len() {
+        let opcode = code[pc];
+        if opcode == 0x5b {
+            jumpdests[pc] = true;
+        } else if (0x60..=0x7f).contains(&opcode) {
+            pc += (opcode - 0x5f) as usize;
+        }
+        pc += 1;
+    }
+    jumpdests
+}
+
+/// Unwraps a `Result<T, Halt>` inside the interpreter loop, halting the frame on error.
+macro_rules! try_halt {
+    ($result:expr) => {
+        match $result {
+            Ok(value) => value,
+            Err(halt) => break Err(halt),
+        }
+    };
+}
+
+/// Deducts `cost` from the remaining gas.
+fn charge(gas_left: &mut u64, cost: u64) -> Result<(), Halt> {
+    match gas_left.checked_sub(cost) {
+        Some(remaining) => {
+            *gas_left = remaining;
+            Ok(())
+        }
+        None => Err(Halt::OutOfGas),
+    }
+}
+
+/// Largest memory a frame may allocate, whatever its gas. Growing this far
+/// already costs over two billion gas, more than any block holds, so going
+/// past it is treated as out of gas.
+const MEMORY_LIMIT: usize = 32 * 1024 * 1024;
+
+/// Turns a stack-supplied `offset`/`size` pair into a memory range, charges
+/// for growing memory over it and grows it. An empty range never touches
+/// memory, whatever its offset.
+fn access_memory(memory: &mut Vec<u8>, gas_left: &mut u64, offset: U256, size: U256) -> Result<(usize, usize), Halt> {
+    if size.is_zero() {
+        return Ok((0, 0));
+    }
+    let limit = U256::from(MEMORY_LIMIT);
+    if offset > limit || size > limit || offset + size > limit {
+        return Err(Halt::OutOfGas);
+    }
+    let (offset, size) = (offset.as_usize(), size.as_usize());
+    charge(gas_left, memory_expansion_cost(memory, offset, size))?;
+    expand_memory(memory, offset, size);
+    Ok((offset, size))
+}
+
+/// Gas needed to grow `memory` so it covers `offset..offset + size`.
+fn memory_expansion_cost(memory: &[u8], offset: usize, size: usize) -> u64 {
+    if size == 0 || offset + size <= memory.len() {
+        return 0;
+    }
+    gas::memory_cost(offset + size) - gas::memory_cost(memory.len())
+}
+
+/// Grows `memory` in whole words so it covers `offset..offset + size`.
+fn expand_memory(memory: &mut Vec<u8>, offset: usize, size: usize) {
+    if size == 0 {
+        return;
+    }
+    let end = (offset + size).div_ceil(32) * 32;
+    if memory.len() < end {
+        memory.resize(end, 0);
+    }
+}
+
+/// Copies `size` bytes of `source` starting at `offset` into memory at `dest`,
+/// which must already be expanded. Bytes past the end of `source` are filled with zeros.
+fn copy_to_memory(memory: &mut [u8], dest: usize, source: &[u8], offset: U256, size: usize) {
+    if size == 0 {
+        return;
+    }
+    let target = &mut memory[dest..dest + size];
+    target.fill(0);
+    if offset < U256::from(source.len()) {
+        let offset = offset.as_usize();
+        let available = (source.len() - offset).min(size);
+        target[..available].copy_from_slice(&source[offset..offset + available]);
+    }
+}
+
+/// The message that started a call frame.
+#[derive(Debug, Clone)]
+pub struct CallContext<'a> {
+    pub code: &'a [u8],
+    pub input: &'a [u8],
+    /// Account the code runs as.
+    pub address: Address,
+    pub caller: Address,
+    pub value: U256,
+    pub gas: u64,
+    pub is_static: bool,
+    pub depth: usize,
+}
+
+/// Outcome of running a single call frame.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FrameResult {
+    /// Final stack, bottom first.
+    pub stack: Vec<U256>,
+    pub success: bool,
+    pub output: Vec<u8>,
+    pub gas_left: u64,
+    /// Gas refund earned by this frame and its successful sub-calls.
+    pub refund: i64,
+    pub halt: Option<Halt>,
+}
+
+/// Runs the code of one call frame to completion. Sub-calls and creations
+/// go through `host`, which runs them with `execute` in turn.
+pub fn execute<H: Host>(context: &CallContext, host: &mut H) -> FrameResult {
+    let mut stack = Stack::new();
+    let mut pc = 0; // Program Counter
+    let mut memory: Vec<u8> = vec![];
+    let code = context.code;
+    let jumpdests = analyze_jumpdests(code);
+    let spec = host.spec();
+    let metered = host.metered();
+    let mut gas_left = context.gas;
+    let mut refund: i64 = 0;
+
+    // Output of this frame (RETURN/REVERT) and of its most recent sub-call.
+    let mut output: Vec<u8> = Vec::new();
+    let mut return_data: Vec<u8> = Vec::new();
+
+    // What the inspector sees of the frame, at the instruction `pc`.
+    macro_rules! frame_state {
+        ($pc:expr, $opcode:expr) => {
+            FrameState {
+                pc: $pc,
+                opcode: $opcode,
+                stack: stack.data(),
+                memory: &memory,
+                gas_left,
+                refund,
+                depth: context.depth,
+                address: context.address,
+                code,
+                return_data: &return_data,
+            }
+        };
+    }
+    // The instruction reported by `step` and still waiting for its `step_end`.
+    let mut stepped: Option<(usize, u8)> = None;
+
+    // Ok(true) for STOP/RETURN, Ok(false) for REVERT.
+    let result: Result<bool, Halt> = loop {
+        if let Some((step_pc, step_opcode)) = stepped.take() {
+            if let Some(inspector) = host.inspector() {
+                inspector.step_end(&frame_state!(step_pc, step_opcode));
+            }
+        }
+        if pc >= code.len() {
+            break Ok(true);
+        }
+        let opcode = code[pc];
+        if let Some(inspector) = host.inspector() {
+            let control = inspector.step(&frame_state!(pc, opcode));
+            stepped = Some((pc, opcode));
+            if let StepControl::Halt(halt) = control {
+                break Err(halt);
+            }
+        }
+        pc += 1;
+
+        // Opcodes from later forks are undefined until then.
+        if !spec.is_enabled(opcode::fork(opcode)) {
+            break Err(Halt::InvalidOpcode(opcode));
+        }
+        try_halt!(charge(&mut gas_left, gas::static_cost(opcode)));
+        if let Some(info) = opcode::info(opcode) {
+            if stack.len() < info.inputs {
+                break Err(Halt::StackUnderflow);
+            }
+            if stack.len() - info.inputs + info.outputs > Stack::LIMIT {
+                break Err(Halt::StackOverflow);
+            }
+        }
+
+        match opcode {
+            0x00 => {
+                break Ok(true);
+            }
+            0x5f => {
+                stack.push(U256::zero());
+            }
+
+            0x60..=0x7f => {
+                // PUSH1 to PUSH32. Data cut off by the end of the code reads as zeros.
+                let num_bytes = (opcode - 0x5f) as usize;
+                let mut buffer = [0u8; 32];
+                let available = code.len().saturating_sub(pc).min(num_bytes);
+                buffer[32 - num_bytes..32 - num_bytes + available].copy_from_slice(&code[pc..pc + available]);
+                pc += num_bytes;
+                stack.push(U256::from_big_endian(&buffer));
+            }
+            0x50 => {
+                stack.pop();
+            }
+            0x01 => {
+                let a = stack.pop();
+                let b = stack.pop();
+
+                let (result, _overflow) = a.overflowing_add(b);
+
+                stack.push(result);
+            }
+            0x02 => {
+                let a = stack.pop();
+                let b = stack.pop();
+
+                let (result, _overflow) = a.overflowing_mul(b);
+
+                stack.push(result);
+            }
+            0x03 => {
+                // SUB
+
+                let a = stack.pop();
+                let b = stack.pop();
+
+                let (result, _underflow) = a.overflowing_sub(b);
+
+                stack.push(result);
+            }
+            0x04 => {
+                let a = stack.pop();
+                let b = stack.pop();
+
+                if b == U256::zero() {
+                    stack.push(U256::from(0));
+                } else {
+                    stack.push(a / b);
+                }
+            }
+            0x06 => {
+                let a = stack.pop();
+                let b = stack.pop();
+
+                if b == U256::zero() {
+                    stack.push(U256::from(0));
+                } else {
+                    stack.push(a % b);
+                }
+            }
+            0x08 => {
+                let a = stack.pop();
+                let b = stack.pop();
+                let n = stack.pop();
+                stack.push(arith::addmod(a, b, n));
+            }
+            0x09 => {
+                let a = stack.pop();
+                let b = stack.pop();
+                let n = stack.pop();
+                stack.push(arith::mulmod(a, b, n));
+            }
+            0x0a => {
+                let base = stack.pop();
+                let exponent = stack.pop();
+                try_halt!(charge(&mut gas_left, gas::exp_cost(spec, exponent)));
+                stack.push(arith::exp(base, exponent));
+            }
+            0x0b => {
+                let byte = stack.pop();
+                let value = stack.pop();
+                stack.push(i256::sign_extend(byte, value));
+            }
+            0x05 => {
+                let a = stack.pop();
+                let b = stack.pop();
+                stack.push(i256::div(a, b));
+            }
+            0x07 => {
+                let a = stack.pop();
+                let b = stack.pop();
+                stack.push(i256::rem(a, b));
+            }
+            0x10 => {
+                let a = stack.pop();
+                let b = stack.pop();
+                if a < b {
+                    stack.push(U256::one());
+                } else {
+                    stack.push(U256::zero());
+                }
+            }
+            0x11 => {
+                let a = stack.pop();
+                let b = stack.pop();
+                if a > b {
+                    stack.push(U256::one());
+                } else {
+                    stack.push(U256::zero());
+                }
+            }
+            0x12 => {
+                let a = stack.pop();
+                let b = stack.pop();
+                if i256::cmp(a, b) == Ordering::Less {
+                    stack.push(U256::one());
+                } else {
+                    stack.push(U256::zero());
+                }
+            }
+            0x13 => {
+                let a = stack.pop();
+                let b = stack.pop();
+                if i256::cmp(a, b) == Ordering::Greater {
+                    stack.push(U256::one());
+                } else {
+                    stack.push(U256::zero());
+                }
+            }
+            0x14 => {
+                let a = stack.pop();
+                let b = stack.pop();
+                if a == b {
+                    stack.push(U256::one());
+                } else {
+                    stack.push(U256::zero());
+                }
+            }
+            0x15 => {
+                let a = stack.pop();
+                if a == U256::zero() {
+                    stack.push(U256::one());
+                } else {
+                    stack.push(U256::zero());
+                }
+            }
+            0x19 => {
+                let a = stack.pop();
+                stack.push(!a);
+            }
+            0x16 => {
+                let a = stack.pop();
+                let b = stack.pop();
+                stack.push(a & b);
+            }
+            0x17 => {
+                let a = stack.pop();
+                let b = stack.pop();
+                stack.push(a | b);
+            }
+            0x18 => {
+                let a = stack.pop();
+                let b = stack.pop();
+                stack.push(a ^ b);
+            }
+            0x1b => {
+                let shift = stack.pop();
+                let value = stack.pop();
+                stack.push(arith::shl(shift, value));
+            }
+            0x1c => {
+                let shift = stack.pop();
+                let value = stack.pop();
+                stack.push(arith::shr(shift, value));
+            }
+            0x1d => {
+                let shift = stack.pop();
+                let value = stack.pop();
+                stack.push(i256::sar(value, shift));
+            }
+            0x1a => {
+                let index = stack.pop();
+                let value = stack.pop();
+                stack.push(arith::byte(index, value));
+            }
+            0x80..=0x8f => {
+                let depth = (opcode - 0x80 + 1) as usize;
+                stack.dup(depth);
+            }
+            0x90..=0x9f => {
+                let depth = (opcode - 0x90 + 1) as usize;
+                stack.swap(depth);
+            }
+            0xfe => {
+                break Err(Halt::InvalidOpcode(opcode));
+            }
+            0x58 => {
+                stack.push(U256::from(pc - 1));
+            }
+            0x5a => {
+                if metered {
+                    stack.push(U256::from(gas_left));
+                } else {
+                    stack.push(U256::max_value());
+                }
+            }
+            0x56 => {
+                let dest = stack.pop();
+                if dest >= U256::from(code.len()) || !jumpdests[dest.as_usize()] {
+                    break Err(Halt::InvalidJump);
+                }
+                pc = dest.as_usize();
+            }
+            0x57 => {
+                let first = stack.pop();
+                let second = stack.pop();
+                if second == U256::zero() {
+                    continue;
+                } else {
+                    if first >= U256::from(code.len()) || !jumpdests[first.as_usize()] {
+                        break Err(Halt::InvalidJump);
+                    }
+                    pc = first.as_usize();
+                }
+            }
+            0x5b => {}
+            0x52 => {
+                let offset = stack.pop();
+                let value = stack.pop();
+
+                let (offset, _) = try_halt!(access_memory(&mut memory, &mut gas_left, offset, U256::from(32)));
+
+                let mut buffer = [0u8; 32];
+                value.to_big_endian(&mut buffer);
+                memory[offset..offset + 32].copy_from_slice(&buffer);
+            }
+
+            0x51 => {
+                let offset = stack.pop();
+
+                let (offset, _) = try_halt!(access_memory(&mut memory, &mut gas_left, offset, U256::from(32)));
+                stack.push(U256::from_big_endian(&memory[offset..offset + 32]));
+            }
+            0x53 => {
+                let offset = stack.pop();
+                let value = stack.pop();
+
+                let (offset, _) = try_halt!(access_memory(&mut memory, &mut gas_left, offset, U256::one()));
+
+                memory[offset] = value.low_u32() as u8;
+            }
+            0x59 => {
+                stack.push(U256::from(memory.len()));
+            }
+            0x20 => {
+                let offset = stack.pop();
+                let size = stack.pop();
+                let (offset, size) = try_halt!(access_memory(&mut memory, &mut gas_left, offset, size));
+                try_halt!(charge(&mut gas_left, gas::KECCAK_WORD * (size as u64).div_ceil(32)));
+                let data = &memory[offset..offset + size];
+                let hashed_value = U256::from_big_endian(&keccak256(data));
+                stack.push(hashed_value);
+            }
+            0x30 => {
+                stack.push(address_to_u256(&context.address));
+            }
+            0x33 => {
+                stack.push(address_to_u256(&context.caller));
+            }
+            0x31 => {
+                let address = u256_to_address(stack.pop());
+                try_halt!(charge(&mut gas_left, account_access_cost(host, address)));
+                stack.push(host.balance(address));
+            }
+            0x34 => {
+                stack.push(context.value);
+            }
+            0x32 => {
+                stack.push(address_to_u256(&host.origin()));
+            }
+            0x38 => {
+                stack.push(U256::from(code.len()));
+            }
+            0x39 => {
+                let dest = stack.pop();
+                let offset = stack.pop();
+                let size = stack.pop();
+
+                let (dest, size) = try_halt!(access_memory(&mut memory, &mut gas_left, dest, size));
+                try_halt!(charge(&mut gas_left, gas::copy_cost(size)));
+                copy_to_memory(&mut memory, dest, code, offset, size);
+            }
+            0x3a => {
+                stack.push(host.gas_price());
+            }
+            0x3b => {
+                let address = u256_to_address(stack.pop());
+                try_halt!(charge(&mut gas_left, account_access_cost(host, address)));
+                stack.push(U256::from(host.code(address).len()));
+            }
+            0x3c => {
+                let address = u256_to_address(stack.pop());
+                let dest = stack.pop();
+                let offset = stack.pop();
+                let size = stack.pop();
+
+                try_halt!(charge(&mut gas_left, account_access_cost(host, address)));
+                let (dest, size) = try_halt!(access_memory(&mut memory, &mut gas_left, dest, size));
+                try_halt!(charge(&mut gas_left, gas::copy_cost(size)));
+                copy_to_memory(&mut memory, dest, &host.code(address), offset, size);
+            }
+            0x3f => {
+                let address = u256_to_address(stack.pop());
+                try_halt!(charge(&mut gas_left, account_access_cost(host, address)));
+                stack.push(U256::from_big_endian(host.code_hash(address).as_bytes()));
+            }
+            0x35 => {
+                let offset = stack.pop();
+                let mut buffer = [0u8; 32];
+                if offset < U256::from(context.input.len()) {
+                    let offset = offset.as_usize();
+                    let available = (context.input.len() - offset).min(32);
+                    buffer[..available].copy_from_slice(&context.input[offset..offset + available]);
+                }
+                stack.push(U256::from_big_endian(&buffer));
+            }
+            0x36 => {
+                stack.push(U256::from(context.input.len()));
+            }
+            0x37 => {
+                let dest = stack.pop();
+                let offset = stack.pop();
+                let size = stack.pop();
+
+                let (dest, size) = try_halt!(access_memory(&mut memory, &mut gas_left, dest, size));
+                try_halt!(charge(&mut gas_left, gas::copy_cost(size)));
+                copy_to_memory(&mut memory, dest, context.input, offset, size);
+            }
+            0x3d => {
+                stack.push(U256::from(return_data.len()));
+            }
+            0x3e => {
+                let dest = stack.pop();
+                let offset = stack.pop();
+                let size = stack.pop();
+
+                // Unlike the other copies, reading past the end of the return
+                // data is an exceptional halt rather than zero padding (EIP-211).
+                let (end, overflow) = offset.overflowing_add(size);
+                if overflow || end > U256::from(return_data.len()) {
+                    break Err(Halt::ReturnDataOutOfBounds);
+                }
+                let (dest, size) = try_halt!(access_memory(&mut memory, &mut gas_left, dest, size));
+                try_halt!(charge(&mut gas_left, gas::copy_cost(size)));
+                copy_to_memory(&mut memory, dest, &return_data, offset, size);
+            }
+            0x47 => {
+                stack.push(host.balance(context.address));
+            }
+            0x49 => {
+                let index = stack.pop();
+                let hash = if index < U256::from(usize::MAX) {
+                    host.blob_hash(index.as_usize()).unwrap_or_default()
+                } else {
+                    H256::zero()
+                };
+                stack.push(U256::from_big_endian(hash.as_bytes()));
+            }
+            0x4a => {
+                stack.push(host.blob_base_fee());
+            }
+            0x48 => {
+                stack.push(host.base_fee());
+            }
+            0x41 => {
+                stack.push(address_to_u256(&host.coinbase()));
+            }
+            0x42 => {
+                stack.push(host.timestamp());
+            }
+            0x43 => {
+                stack.push(host.number());
+            }
+            0x44 => {
+                // PREVRANDAO from Paris onward. Fixtures that only carry a
+                // difficulty keep working, as it held the same slot pre-Merge.
+                match host.prevrandao() {
+                    Some(prevrandao) if spec.is_enabled(SpecId::Paris) => {
+                        stack.push(U256::from_big_endian(prevrandao.as_bytes()));
+                    }
+                    _ => {
+                        stack.push(host.difficulty());
+                    }
+                }
+            }
+            0x45 => {
+                stack.push(host.gas_limit());
+            }
+            0x46 => {
+                stack.push(host.chain_id());
+            }
+            0x40 => {
+                // Only the 256 most recent blocks are available.
+                let requested = stack.pop();
+                let current = host.number();
+                let available = current.saturating_sub(U256::from(256));
+                if requested < current && requested >= available {
+                    let hash = host.block_hash(requested.as_u64());
+                    stack.push(U256::from_big_endian(hash.as_bytes()));
+                } else {
+                    stack.push(U256::zero());
+                }
+            }
+            0x54 => {
+                let key = stack.pop();
+                try_halt!(charge(&mut gas_left, storage_access_cost(host, context.address, key)));
+                stack.push(host.sload(context.address, key));
+            }
+            0x55 => {
+                let key = stack.pop();
+                let value = stack.pop();
+                if context.is_static {
+                    break Err(Halt::StaticStateChange);
+                }
+                if gas_left <= gas::SSTORE_SENTRY {
+                    break Err(Halt::OutOfGas);
+                }
+                let cold = host.access_storage(context.address, key);
+                let slot = host.sstore(context.address, key, value);
+                let mut cost = gas::sstore_cost(slot.original, slot.current, slot.new);
+                if cold {
+                    cost += gas::COLD_SLOAD_COST;
+                }
+                try_halt!(charge(&mut gas_left, cost));
+                refund += gas::sstore_refund(spec, slot.original, slot.current, slot.new);
+            }
+            0x5c => {
+                let key = stack.pop();
+                stack.push(host.tload(context.address, key));
+            }
+            0x5d => {
+                let key = stack.pop();
+                let value = stack.pop();
+                if context.is_static {
+                    break Err(Halt::StaticStateChange);
+                }
+                host.tstore(context.address, key, value);
+            }
+            0x5e => {
+                let dest = stack.pop();
+                let source = stack.pop();
+                let size = stack.pop();
+
+                // Memory grows to cover both ranges.
+                try_halt!(access_memory(&mut memory, &mut gas_left, source, size));
+                let (dest, size) = try_halt!(access_memory(&mut memory, &mut gas_left, dest, size));
+                try_halt!(charge(&mut gas_left, gas::copy_cost(size)));
+                if size > 0 {
+                    let source = source.as_usize();
+                    memory.copy_within(source..source + size, dest);
+                }
+            }
+            0xa0..=0xa4 => {
+                let offset = stack.pop();
+                let size = stack.pop();
+                let topics = (0..opcode - 0xa0)
+                    .map(|_| {
+                        let mut topic = [0u8; 32];
+                        stack.pop().to_big_endian(&mut topic);
+                        H256(topic)
+                    })
+                    .collect();
+                if context.is_static {
+                    break Err(Halt::StaticStateChange);
+                }
+
+                let (offset, size) = try_halt!(access_memory(&mut memory, &mut gas_left, offset, size));
+                try_halt!(charge(&mut gas_left, gas::LOG_DATA * size as u64));
+                let log = Log {
+                    address: context.address,
+                    topics,
+                    data: memory[offset..offset + size].into(),
+                };
+                if let Some(inspector) = host.inspector() {
+                    inspector.log(&frame_state!(pc - 1, opcode), &log);
+                }
+                host.log(log);
+            }
+
+            0xf1 | 0xf2 | 0xf4 | 0xfa => {
+                // CALL, CALLCODE, DELEGATECALL and STATICCALL
+                let requested_gas = stack.pop();
+                let target = u256_to_address(stack.pop());
+                let value = if opcode == 0xf1 || opcode == 0xf2 {
+                    stack.pop()
+                } else {
+                    U256::zero()
+                };
+                let args_offset = stack.pop();
+                let args_size = stack.pop();
+                let ret_offset = stack.pop();
+                let ret_size = stack.pop();
+
+                if context.is_static && opcode == 0xf1 && !value.is_zero() {
+                    break Err(Halt::StaticStateChange);
+                }
+
+                let mut cost = account_access_cost(host, target);
+                if !value.is_zero() {
+                    cost += gas::CALL_VALUE;
+                    if opcode == 0xf1 && host.is_empty(target) {
+                        cost += gas::NEW_ACCOUNT;
+                    }
+                }
+                try_halt!(charge(&mut gas_left, cost));
+                let (args_offset, args_size) =
+                    try_halt!(access_memory(&mut memory, &mut gas_left, args_offset, args_size));
+                let (ret_offset, ret_size) = try_halt!(access_memory(&mut memory, &mut gas_left, ret_offset, ret_size));
+
+                // The callee gets at most all but one 64th of the remaining gas (EIP-150).
+                // Without a gas limit the requested amount is ignored.
+                let available = gas_left - gas_left / 64;
+                let mut callee_gas = if !metered || requested_gas > U256::from(available) {
+                    available
+                } else {
+                    requested_gas.as_u64()
+                };
+                gas_left -= callee_gas;
+                if let Some(inspector) = host.inspector() {
+                    inspector.step_suspend(&frame_state!(pc - 1, opcode));
+                }
+                if !value.is_zero() {
+                    callee_gas += gas::CALL_STIPEND;
+                }
+
+                let (scheme, address, caller, call_value) = match opcode {
+                    0xf1 => (CallScheme::Call, target, context.address, value),
+                    0xfa => (CallScheme::StaticCall, target, context.address, value),
+                    0xf2 => (CallScheme::CallCode, context.address, context.address, value),
+                    _ => (CallScheme::DelegateCall, context.address, context.caller, context.value),
+                };
+                let outcome = host.call(CallInputs {
+                    scheme,
+                    code_address: target,
+                    address,
+                    caller,
+                    value: call_value,
+                    input: memory[args_offset..args_offset + args_size].into(),
+                    gas_limit: callee_gas,
+                    is_static: context.is_static || opcode == 0xfa,
+                    depth: context.depth + 1,
+                });
+
+                gas_left += outcome.gas_left;
+                refund += outcome.refund;
+                let copied = outcome.output.len().min(ret_size);
+                memory[ret_offset..ret_offset + copied].copy_from_slice(&outcome.output[..copied]);
+                return_data = outcome.output.0;
+                stack.push(U256::from(outcome.success as u8));
+            }
+            0xf0 | 0xf5 => {
+                // CREATE and CREATE2
+                let value = stack.pop();
+                let offset = stack.pop();
+                let size = stack.pop();
+                let scheme = if opcode == 0xf5 {
+                    CreateScheme::Create2 { salt: stack.pop() }
+                } else {
+                    CreateScheme::Create
+                };
+                if context.is_static {
+                    break Err(Halt::StaticStateChange);
+                }
+
+                let (offset, size) = try_halt!(access_memory(&mut memory, &mut gas_left, offset, size));
+                let words = (size as u64).div_ceil(32);
+                if spec.is_enabled(SpecId::Shanghai) {
+                    if size > gas::MAX_INITCODE_SIZE {
+                        break Err(Halt::CreateInitCodeSizeLimit);
+                    }
+                    try_halt!(charge(&mut gas_left, gas::INITCODE_WORD * words));
+                }
+                if opcode == 0xf5 {
+                    // CREATE2 hashes the init code to derive the address.
+                    try_halt!(charge(&mut gas_left, gas::KECCAK_WORD * words));
+                }
+
+                let callee_gas = gas_left - gas_left / 64;
+                gas_left -= callee_gas;
+                if let Some(inspector) = host.inspector() {
+                    inspector.step_suspend(&frame_state!(pc - 1, opcode));
+                }
+                let outcome = host.create(CreateInputs {
+                    scheme,
+                    caller: context.address,
+                    value,
+                    init_code: memory[offset..offset + size].into(),
+                    gas_limit: callee_gas,
+                    depth: context.depth + 1,
+                });
+
+                gas_left += outcome.result.gas_left;
+                refund += outcome.result.refund;
+                // A successful creation leaves no return data (EIP-211).
+                return_data = if outcome.result.success {
+                    Vec::new()
+                } else {
+                    outcome.result.output.0
+                };
+                stack.push(
+                    outcome
+                        .address
+                        .map(|address| address_to_u256(&address))
+                        .unwrap_or_default(),
+                );
+            }
+            0xff => {
+                let target = u256_to_address(stack.pop());
+                if context.is_static {
+                    break Err(Halt::StaticStateChange);
+                }
+
+                if host.access_account(target) {
+                    try_halt!(charge(&mut gas_left, gas::COLD_ACCOUNT_ACCESS_COST));
+                }
+                if host.inspector().is_some() {
+                    let value = host.balance(context.address);
+                    if let Some(inspector) = host.inspector() {
+                        inspector.selfdestruct(&frame_state!(pc - 1, opcode), context.address, target, value);
+                    }
+                }
+                let result = host.selfdestruct(context.address, target);
+                if result.had_value && !result.target_exists {
+                    try_halt!(charge(&mut gas_left, gas::NEW_ACCOUNT));
+                }
+                if !spec.is_enabled(SpecId::London) && !result.previously_destroyed {
+                    refund += gas::SELFDESTRUCT_REFUND;
+                }
+                break Ok(true);
+            }
+            0xf3 | 0xfd => {
+                // RETURN and REVERT
+                let offset = stack.pop();
+                let size = stack.pop();
+
+                let (offset, size) = try_halt!(access_memory(&mut memory, &mut gas_left, offset, size));
+                output = memory[offset..offset + size].to_vec();
+                if opcode == 0xfd {
+                    break Ok(false);
+                }
+                break Ok(true);
+            }
+            _ => {
+                break Err(Halt::InvalidOpcode(opcode));
+            }
+        }
+    };
+    if let Some((step_pc, step_opcode)) = stepped {
+        if let Some(inspector) = host.inspector() {
+            inspector.step_end(&frame_state!(step_pc, step_opcode));
+        }
+    }
+
+    let (success, halt) = match result {
+        Ok(success) => (success, None),
+        Err(halt) => (false, Some(halt)),
+    };
+    // An exceptional halt consumes all gas and discards any output.
+    if halt.is_some() {
+        gas_left = 0;
+        output.clear();
+    }
+    // Refunds only survive a successful frame.
+    if !success {
+        refund = 0;
+    }
+    FrameResult {
+        stack: stack.into_vec(),
+        success,
+        output,
+        gas_left,
+        refund,
+        halt,
+    }
+}
+
+/// Gas for touching `address`, warming it up for the rest of the transaction (EIP-2929).
+fn account_access_cost<H: Host>(host: &mut H, address: Address) -> u64 {
+    if host.access_account(address) {
+        gas::COLD_ACCOUNT_ACCESS_COST
+    } else {
+        gas::WARM_STORAGE_READ_COST
+    }
+}
+
+/// Gas for reading a storage slot, warming it up for the rest of the transaction (EIP-2929).
+fn storage_access_cost<H: Host>(host: &mut H, address: Address, key: U256) -> u64 {
+    if host.access_storage(address, key) {
+        gas::COLD_SLOAD_COST
+    } else {
+        gas::WARM_STORAGE_READ_COST
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{evm, evm_with_state, keccak256, Account, Block, Halt, SpecId, State, Txn};
+    use primitive_types::{H160, H256, U256};
+
+    fn run(code: &str, gas: Option<u64>) -> crate::EvmResult {
+        let tx = Txn {
+            gas,
+            ..Default::default()
+        };
+        evm(hex::decode(code).unwrap(), &Some(tx), &None)
+    }
+
+    fn assert_out_of_gas(code: &str) {
+        for gas in [None, Some(u64::MAX)] {
+            let result = run(code, gas);
+            assert!(!result.success, "{} with gas {:?}", code, gas);
+            assert_eq!(result.halt, Some(Halt::OutOfGas), "{} with gas {:?}", code, gas);
+        }
+    }
+
+    #[test]
+    fn huge_mload_is_out_of_gas() {
+        // PUSH4 0xfffffe00 MLOAD
+        assert_out_of_gas("63fffffe0051");
+    }
+
+    #[test]
+    fn huge_mstore_is_out_of_gas() {
+        // PUSH1 1 PUSH4 0xfffffe00 MSTORE
+        assert_out_of_gas("600163fffffe0052");
+    }
+
+    #[test]
+    fn huge_calldatacopy_is_out_of_gas() {
+        // PUSH1 32 PUSH1 0 PUSH4 0xfffffe00 CALLDATACOPY
+        assert_out_of_gas("6020600063fffffe0037");
+    }
+
+    #[test]
+    fn huge_return_is_out_of_gas() {
+        // PUSH1 32 PUSH4 0xfffffe00 RETURN
+        assert_out_of_gas("602063fffffe00f3");
+    }
+
+    #[test]
+    fn returndatacopy_past_the_end_halts() {
+        // PUSH1 1 PUSH1 0 PUSH1 0 RETURNDATACOPY: one byte of empty return data
+        let result = run("6001600060003e", None);
+        assert_eq!(result.halt, Some(Halt::ReturnDataOutOfBounds));
+        // PUSH1 0 PUSH1 1 PUSH1 0 RETURNDATACOPY: an empty copy still checks its offset
+        let result = run("6000600160003e", None);
+        assert_eq!(result.halt, Some(Halt::ReturnDataOutOfBounds));
+        // PUSH1 0 PUSH1 0 PUSH1 0 RETURNDATACOPY
+        let result = run("6000600060003e", None);
+        assert!(result.success);
+    }
+
+    #[test]
+    fn blobhash_reads_the_versioned_hashes() {
+        let hashes = vec![H256::repeat_byte(1), H256::repeat_byte(2)];
+        let blobhash = |index: &str, hashes: &[H256]| {
+            let tx = Txn {
+                blob_versioned_hashes: hashes.to_vec(),
+                ..Default::default()
+            };
+            // PUSHn index BLOBHASH
+            let code = format!("{:02x}{}49", 0x5f + index.len() / 2, index);
+            evm(hex::decode(code).unwrap(), &Some(tx), &None).stack
+        };
+        assert_eq!(blobhash("01", &hashes), vec![U256::from_big_endian(&[2; 32])]);
+        assert_eq!(blobhash("02", &hashes), vec![U256::zero()]);
+        assert_eq!(blobhash("ffffffffffffffffffff", &hashes), vec![U256::zero()]);
+        assert_eq!(blobhash("00", &[]), vec![U256::zero()]);
+    }
+
+    #[test]
+    fn prevrandao_replaces_difficulty_from_paris() {
+        let block = Block {
+            difficulty: U256::from(0x20000),
+            prevrandao: Some(H256::repeat_byte(0xab)),
+            ..Default::default()
+        };
+        // DIFFICULTY / PREVRANDAO
+        let run = |spec| evm_with_state([0x44], &None, &Some(block.clone()), &mut State::new(), spec).stack;
+        assert_eq!(run(SpecId::London), vec![U256::from(0x20000)]);
+        assert_eq!(run(SpecId::Paris), vec![U256::from_big_endian(&[0xab; 32])]);
+        assert_eq!(run(SpecId::Cancun), vec![U256::from_big_endian(&[0xab; 32])]);
+    }
+
+    #[test]
+    fn opcodes_wait_for_their_fork() {
+        let run = |code: &[u8], spec| evm_with_state(code, &None, &None, &mut State::new(), spec);
+        // PUSH0
+        assert_eq!(run(&[0x5f], SpecId::Berlin).halt, Some(Halt::InvalidOpcode(0x5f)));
+        assert!(run(&[0x5f], SpecId::Shanghai).success);
+        // BASEFEE
+        assert_eq!(run(&[0x48], SpecId::Berlin).halt, Some(Halt::InvalidOpcode(0x48)));
+        assert!(run(&[0x48], SpecId::London).success);
+        // PUSH1 0 TLOAD
+        assert_eq!(
+            run(&[0x60, 0x00, 0x5c], SpecId::Shanghai).halt,
+            Some(Halt::InvalidOpcode(0x5c))
+        );
+        assert!(run(&[0x60, 0x00, 0x5c], SpecId::Cancun).success);
+    }
+
+    #[test]
+    fn exp_charges_for_its_exponent() {
+        // PUSH1 2 PUSH1 0xff EXP: 3 + 3 + 10 + 50 for the one-byte exponent
+        let result = run("600260ff0a", Some(66));
+        assert_eq!(result.stack, vec![U256::from(0xfe01)]);
+        assert_eq!(run("600260ff0a", Some(65)).halt, Some(Halt::OutOfGas));
+    }
+
+    #[test]
+    fn stack_is_reported_top_first() {
+        // PUSH1 1 PUSH1 2 PUSH1 3 DUP3 SWAP1
+        let result = run("6001600260038290", None);
+        assert_eq!(result.stack, vec![3.into(), 1.into(), 2.into(), 1.into()]);
+        // PUSH1 1 PUSH1 3 SUB: the top of the stack is the first operand
+        let result = run("6001600303", None);
+        assert_eq!(result.stack, vec![2.into()]);
+    }
+
+    #[test]
+    fn dup16_and_swap16_reach_the_sixteenth_item() {
+        // PUSH1 1 .. PUSH1 16 DUP16, then PUSH1 17 SWAP16
+        let pushes: String = (1..=16).map(|n| format!("60{:02x}", n)).collect();
+        let result = run(&format!("{}8f", pushes), None);
+        assert_eq!(result.stack[0], 1.into());
+        let result = run(&format!("{}60119f", pushes), None);
+        assert_eq!(result.stack[0], 1.into());
+        assert_eq!(result.stack[16], 17.into());
+    }
+
+    #[test]
+    fn add_wraps_and_takes_two_operands() {
+        // PUSH1 5 PUSH32 0xff..ff PUSH1 2 ADD
+        let result = run(&format!("60057f{}600201", "ff".repeat(32)), None);
+        assert_eq!(result.stack, vec![1.into(), 5.into()]);
+    }
+
+    #[test]
+    fn jumps_need_a_jumpdest_outside_push_data() {
+        // PUSH1 3 JUMP JUMPDEST PUSH1 1
+        let result = run("6003565b6001", None);
+        assert_eq!(result.stack, vec![1.into()]);
+        // PUSH1 4 JUMP PUSH1 0x5b: the target is PUSH data
+        assert_eq!(run("600456605b", None).halt, Some(Halt::InvalidJump));
+        // PUSH1 1 PUSH1 6 JUMPI PUSH1 0x5b
+        assert_eq!(run("6001600657605b", None).halt, Some(Halt::InvalidJump));
+        // PUSH1 0 PUSH1 6 JUMPI PUSH1 0x5b: a jump not taken is not checked
+        assert!(run("6000600657605b", None).success);
+        // PUSH1 0xff JUMP: past the end of the code
+        assert_eq!(run("60ff56", None).halt, Some(Halt::InvalidJump));
+    }
+
+    #[test]
+    fn memory_expansion_is_charged_in_words() {
+        // PUSH1 0 MLOAD: 3 + 3 and one word of memory
+        assert!(run("600051", Some(9)).success);
+        assert_eq!(run("600051", Some(8)).halt, Some(Halt::OutOfGas));
+        // PUSH1 1 PUSH1 32 MSTORE8 MSIZE GAS
+        let result = run("6001602053595a", Some(100));
+        assert_eq!(result.stack, vec![(100 - 3 - 3 - 3 - 6 - 2 - 2).into(), 64.into()]);
+    }
+
+    #[test]
+    fn extcode_opcodes_read_other_accounts() {
+        let address = "aa".repeat(20);
+        let mut state = State::new();
+        state.insert(
+            H160::repeat_byte(0xaa),
+            Account {
+                code: vec![0x60, 0x01, 0x60, 0x02],
+                ..Default::default()
+            },
+        );
+        let run = |code: String, gas| {
+            let tx = Txn {
+                gas,
+                ..Default::default()
+            };
+            evm_with_state(
+                hex::decode(code).unwrap(),
+                &Some(tx),
+                &None,
+                &mut state.clone(),
+                SpecId::Cancun,
+            )
+        };
+        // PUSH20 address EXTCODESIZE
+        assert_eq!(run(format!("73{}3b", address), None).stack, vec![4.into()]);
+        // PUSH1 8 PUSH1 1 PUSH1 0 PUSH20 address EXTCODECOPY PUSH1 0 MLOAD
+        let result = run(format!("60086001600073{}3c600051", address), None);
+        let mut word = [0u8; 32];
+        word[..3].copy_from_slice(&[0x01, 0x60, 0x02]);
+        assert_eq!(result.stack, vec![U256::from_big_endian(&word)]);
+        // PUSH20 address EXTCODEHASH, and of an account that does not exist
+        let hash = U256::from_big_endian(&keccak256(&[0x60, 0x01, 0x60, 0x02]));
+        assert_eq!(run(format!("73{}3f", address), None).stack, vec![hash]);
+        assert_eq!(run(format!("73{}3f", "bb".repeat(20)), None).stack, vec![U256::zero()]);
+        // The first access is cold, the second warm: 3 + 2600 + 3 + 100
+        let twice = format!("73{0}3b73{0}3b", address);
+        assert!(run(twice.clone(), Some(2706)).success);
+        assert_eq!(run(twice, Some(2705)).halt, Some(Halt::OutOfGas));
+    }
+
+    #[test]
+    fn memory_below_the_limit_still_grows() {
+        // PUSH3 0x100000 MLOAD MSIZE
+        let result = run("621000005159", None);
+        assert!(result.success);
+        assert_eq!(result.stack, vec![0x100020.into(), 0.into()]);
+    }
+}
@@ -1,4 +1,4 @@
-/**
+/*
  * EVM From Scratch
  * Rust template
  *
@@ -7,111 +7,162 @@
  * - Install Rust: https://www.rust-lang.org/tools/install
  * - Edit `rust/lib.rs`
  * - Run `cd rust && cargo run` to run the tests
+ *   (`cargo run -- --help` lists the options)
  *
  * Hint: most people who were trying to learn Rust and EVM at the same
  * gave up and switched to JavaScript, Python, or Go. If you are new
  * to Rust, implement EVM in another programming language first.
  */
 
-use evm::evm;
-use primitive_types::U256;
-use serde::Deserialize;
-use evm::Txn;
-use evm::Block;
+mod run;
+mod runner;
+mod t8n;
 
- 
+use clap::{Args, Parser, Subcommand};
+use evm::SpecId;
+use glob::Pattern;
+use runner::{Options, Outcome};
+use std::path::PathBuf;
+use std::process::ExitCode;
 
-#[derive(Debug, Deserialize)]
-struct Evmtest {
-    name: String,
-    hint: String,
-    code: Code,
-    expect: Expect,
-    tx: Option<Txn>,
-    block: Option<Block>,
-    
+/// Runs evm.json fixtures and reports every failure, not just the first.
+#[derive(Parser)]
+#[command(args_conflicts_with_subcommands = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// Fixture files, directories or glob patterns. Files ending in .yaml
+    /// are read like scripts/evm.yaml.
+    #[arg(default_value = "../evm.json")]
+    paths: Vec<String>,
+    /// Fork to run tests under when they do not name one. The course's
+    /// tests target Shanghai.
+    #[arg(long, value_name = "FORK", default_value_t = SpecId::Shanghai)]
+    fork: SpecId,
+    #[command(flatten)]
+    run: RunArgs,
 }
 
-#[derive(Debug, Deserialize)]
-struct Code {
-    asm: String,
-    bin: String,
+#[derive(Subcommand)]
+enum Command {
+    /// Runs GeneralStateTests from an ethereum/tests checkout.
+    Statetest {
+        /// Fixture files, directories or glob patterns.
+        #[arg(required = true)]
+        paths: Vec<String>,
+        /// Only runs this fork. Repeatable. Defaults to every fork the
+        /// interpreter implements.
+        #[arg(long, value_name = "FORK")]
+        fork: Vec<SpecId>,
+        #[command(flatten)]
+        run: RunArgs,
+    },
+    /// Runs BlockchainTests from an ethereum/tests checkout.
+    Blocktest {
+        /// Fixture files, directories or glob patterns.
+        #[arg(required = true)]
+        paths: Vec<String>,
+        /// Only runs tests for this fork. Repeatable. Defaults to every fork
+        /// the interpreter implements.
+        #[arg(long, value_name = "FORK")]
+        fork: Vec<SpecId>,
+        #[command(flatten)]
+        run: RunArgs,
+    },
+    /// Applies transactions to a state like geth's `evm t8n`, the transition
+    /// tool execution-spec-tests fills tests with.
+    T8n(t8n::T8nArgs),
+    /// Runs bytecode as the receiver's code in a call from the sender, like
+    /// geth's `evm run`, and prints the output, gas used, logs and exit
+    /// reason.
+    Run(run::RunCodeArgs),
 }
 
-#[derive(Debug, Deserialize)]
-struct Expect {
-    stack: Option<Vec<String>>,
-    success: bool,
-    // #[serde(rename = "return")]
-    // ret: Option<String>,
+/// Options shared by every kind of fixture.
+#[derive(Args)]
+struct RunArgs {
+    /// Only runs tests whose name matches this glob pattern. Repeatable.
+    #[arg(long, value_name = "PATTERN")]
+    filter: Vec<Pattern>,
+    /// Skips tests whose name matches this glob pattern. Repeatable.
+    #[arg(long, value_name = "PATTERN")]
+    skip: Vec<Pattern>,
+    /// Only prints failures and the summary.
+    #[arg(short, long)]
+    quiet: bool,
+    /// Writes a JUnit XML report to this file.
+    #[arg(long, value_name = "FILE")]
+    junit: Option<PathBuf>,
+    /// Writes a JSON report to this file.
+    #[arg(long, value_name = "FILE")]
+    json: Option<PathBuf>,
 }
 
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match cli.command {
+        None => run_suite(&cli.paths, cli.run, |files, options| {
+            runner::run(files, options, cli.fork)
+        }),
+        Some(Command::Statetest { paths, fork, run }) => run_suite(&paths, run, |files, options| {
+            runner::run_cases(files, options, |path| runner::state_test::load(path, &fork))
+        }),
+        Some(Command::Blocktest { paths, fork, run }) => run_suite(&paths, run, |files, options| {
+            runner::run_cases(files, options, |path| runner::block_test::load(path, &fork))
+        }),
+        Some(Command::T8n(args)) => exit_with(t8n::run(args)),
+        Some(Command::Run(args)) => exit_with(run::run(args)),
+    }
+}
 
-// #[derive(Debug, Deserialize)]
-// pub struct Txn{
-//     value: Option<String>,
-//     data: Option<String>,
-//     from: Option<String>,
-//     to: Option<String>,
-//     gas: Option<String>,
-    
-//     gasprice:Option<String>,
-// }
-fn main() {
-    let text = std::fs::read_to_string("../evm.json").unwrap();
-    let data: Vec<Evmtest> = serde_json::from_str(&text).unwrap();
-
-    let total = data.len();
-
-    for (index, test) in data.iter().enumerate() {
-        println!("Test {} of {}: {}", index + 1, total, test.name);
-
-        let code: Vec<u8> = hex::decode(&test.code.bin).unwrap();
-         let txn: &Option<Txn>=&test.tx;
-         let block = &test.block;
-        let result = evm(&code,txn,block);
-
-        let mut expected_stack: Vec<U256> = Vec::new();
-        if let Some(ref stacks) = test.expect.stack {
-            for value in stacks {
-                expected_stack.push(U256::from_str_radix(value, 16).unwrap());
-            }
+/// Exits with 2 after printing the error of a tool that failed.
+fn exit_with(result: Result<(), String>) -> ExitCode {
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            ExitCode::from(2)
         }
+    }
+}
 
-        let mut matching = result.stack.len() == expected_stack.len();
-        if matching {
-            for i in 0..result.stack.len() {
-                if result.stack[i] != expected_stack[i] {
-                    matching = false;
-                    break;
-                }
-            }
+/// Runs the fixtures in `paths` with `run`, then prints the summary and
+/// writes the reports `args` asks for.
+fn run_suite(paths: &[String], args: RunArgs, run: impl FnOnce(&[PathBuf], &Options) -> Vec<Outcome>) -> ExitCode {
+    let files = match runner::expand(paths) {
+        Ok(files) => files,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            return ExitCode::from(2);
         }
-        
-        matching = matching && result.success == test.expect.success;
-
-        if !matching {
-            println!("Instructions: \n{}\n", test.code.asm);
+    };
+    let options = Options {
+        filter: args.filter,
+        skip: args.skip,
+        quiet: args.quiet,
+    };
 
-            println!("Expected success: {:?}", test.expect.success);
-            println!("Expected stack: [");
-            for v in expected_stack {
-                println!("  {:#X},", v);
-            }
-            println!("]\n");
-            
-            println!("Actual success: {:?}", result.success);
-            println!("Actual stack: [");
-            for v in result.stack {
-                println!("  {:#X},", v);
-            }
-            println!("]\n");
+    let outcomes = run(&files, &options);
+    println!();
+    runner::print_summary(&outcomes);
 
-            println!("\nHint: {}\n", test.hint);
-            println!("Progress: {}/{}\n\n", index, total);
-            panic!("Test failed");
+    let reports = [
+        (args.junit, runner::report::junit as fn(&[Outcome]) -> String),
+        (args.json, |outcomes| runner::report::json(outcomes).to_string()),
+    ];
+    for (path, render) in reports {
+        let Some(path) = path else { continue };
+        if let Err(err) = std::fs::write(&path, render(&outcomes)) {
+            eprintln!("error: cannot write {}: {}", path.display(), err);
+            return ExitCode::from(2);
         }
-        println!("PASS");
     }
-    println!("Congratulations!");
+
+    let failed = outcomes
+        .iter()
+        .any(|outcome| matches!(outcome.status, runner::Status::Failed(_)));
+    match failed {
+        true => ExitCode::FAILURE,
+        false => ExitCode::SUCCESS,
+    }
 }
@@ -0,0 +1,162 @@
+//! Static description of every opcode the interpreter knows about.
+
+use crate::SpecId;
+
+/// Mnemonic and stack effect of an opcode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OpcodeInfo {
+    pub name: &'static str,
+    /// Number of stack items the instruction reads.
+    pub inputs: usize,
+    /// Number of stack items the instruction leaves behind.
+    pub outputs: usize,
+    /// Number of immediate bytes following the opcode (PUSH data).
+    pub immediate: usize,
+}
+
+const fn op(name: &'static str, inputs: usize, outputs: usize) -> Option<OpcodeInfo> {
+    Some(OpcodeInfo {
+        name,
+        inputs,
+        outputs,
+        immediate: 0,
+    })
+}
+
+const PUSH_NAMES: [&str; 32] = [
+    "PUSH1", "PUSH2", "PUSH3", "PUSH4", "PUSH5", "PUSH6", "PUSH7", "PUSH8", "PUSH9", "PUSH10", "PUSH11", "PUSH12",
+    "PUSH13", "PUSH14", "PUSH15", "PUSH16", "PUSH17", "PUSH18", "PUSH19", "PUSH20", "PUSH21", "PUSH22", "PUSH23",
+    "PUSH24", "PUSH25", "PUSH26", "PUSH27", "PUSH28", "PUSH29", "PUSH30", "PUSH31", "PUSH32",
+];
+const DUP_NAMES: [&str; 16] = [
+    "DUP1", "DUP2", "DUP3", "DUP4", "DUP5", "DUP6", "DUP7", "DUP8", "DUP9", "DUP10", "DUP11", "DUP12", "DUP13",
+    "DUP14", "DUP15", "DUP16",
+];
+const SWAP_NAMES: [&str; 16] = [
+    "SWAP1", "SWAP2", "SWAP3", "SWAP4", "SWAP5", "SWAP6", "SWAP7", "SWAP8", "SWAP9", "SWAP10", "SWAP11", "SWAP12",
+    "SWAP13", "SWAP14", "SWAP15", "SWAP16",
+];
+const LOG_NAMES: [&str; 5] = ["LOG0", "LOG1", "LOG2", "LOG3", "LOG4"];
+
+/// Looks up `opcode`, returning `None` for undefined opcodes.
+pub const fn info(opcode: u8) -> Option<OpcodeInfo> {
+    match opcode {
+        0x00 => op("STOP", 0, 0),
+        0x01 => op("ADD", 2, 1),
+        0x02 => op("MUL", 2, 1),
+        0x03 => op("SUB", 2, 1),
+        0x04 => op("DIV", 2, 1),
+        0x05 => op("SDIV", 2, 1),
+        0x06 => op("MOD", 2, 1),
+        0x07 => op("SMOD", 2, 1),
+        0x08 => op("ADDMOD", 3, 1),
+        0x09 => op("MULMOD", 3, 1),
+        0x0a => op("EXP", 2, 1),
+        0x0b => op("SIGNEXTEND", 2, 1),
+        0x10 => op("LT", 2, 1),
+        0x11 => op("GT", 2, 1),
+        0x12 => op("SLT", 2, 1),
+        0x13 => op("SGT", 2, 1),
+        0x14 => op("EQ", 2, 1),
+        0x15 => op("ISZERO", 1, 1),
+        0x16 => op("AND", 2, 1),
+        0x17 => op("OR", 2, 1),
+        0x18 => op("XOR", 2, 1),
+        0x19 => op("NOT", 1, 1),
+        0x1a => op("BYTE", 2, 1),
+        0x1b => op("SHL", 2, 1),
+        0x1c => op("SHR", 2, 1),
+        0x1d => op("SAR", 2, 1),
+        0x20 => op("SHA3", 2, 1),
+        0x30 => op("ADDRESS", 0, 1),
+        0x31 => op("BALANCE", 1, 1),
+        0x32 => op("ORIGIN", 0, 1),
+        0x33 => op("CALLER", 0, 1),
+        0x34 => op("CALLVALUE", 0, 1),
+        0x35 => op("CALLDATALOAD", 1, 1),
+        0x36 => op("CALLDATASIZE", 0, 1),
+        0x37 => op("CALLDATACOPY", 3, 0),
+        0x38 => op("CODESIZE", 0, 1),
+        0x39 => op("CODECOPY", 3, 0),
+        0x3a => op("GASPRICE", 0, 1),
+        0x3b => op("EXTCODESIZE", 1, 1),
+        0x3c => op("EXTCODECOPY", 4, 0),
+        0x3d => op("RETURNDATASIZE", 0, 1),
+        0x3e => op("RETURNDATACOPY", 3, 0),
+        0x3f => op("EXTCODEHASH", 1, 1),
+        0x40 => op("BLOCKHASH", 1, 1),
+        0x41 => op("COINBASE", 0, 1),
+        0x42 => op("TIMESTAMP", 0, 1),
+        0x43 => op("NUMBER", 0, 1),
+        0x44 => op("DIFFICULTY", 0, 1),
+        0x45 => op("GASLIMIT", 0, 1),
+        0x46 => op("CHAINID", 0, 1),
+        0x47 => op("SELFBALANCE", 0, 1),
+        0x48 => op("BASEFEE", 0, 1),
+        0x49 => op("BLOBHASH", 1, 1),
+        0x4a => op("BLOBBASEFEE", 0, 1),
+        0x50 => op("POP", 1, 0),
+        0x51 => op("MLOAD", 1, 1),
+        0x52 => op("MSTORE", 2, 0),
+        0x53 => op("MSTORE8", 2, 0),
+        0x54 => op("SLOAD", 1, 1),
+        0x55 => op("SSTORE", 2, 0),
+        0x56 => op("JUMP", 1, 0),
+        0x57 => op("JUMPI", 2, 0),
+        0x58 => op("PC", 0, 1),
+        0x59 => op("MSIZE", 0, 1),
+        0x5a => op("GAS", 0, 1),
+        0x5b => op("JUMPDEST", 0, 0),
+        0x5c => op("TLOAD", 1, 1),
+        0x5d => op("TSTORE", 2, 0),
+        0x5e => op("MCOPY", 3, 0),
+        0x5f => op("PUSH0", 0, 1),
+        0x60..=0x7f => {
+            let size = (opcode - 0x5f) as usize;
+            Some(OpcodeInfo {
+                name: PUSH_NAMES[size - 1],
+                inputs: 0,
+                outputs: 1,
+                immediate: size,
+            })
+        }
+        0x80..=0x8f => {
+            let depth = (opcode - 0x7f) as usize;
+            op(DUP_NAMES[depth - 1], depth, depth + 1)
+        }
+        0x90..=0x9f => {
+            let depth = (opcode - 0x8f) as usize;
+            op(SWAP_NAMES[depth - 1], depth + 1, depth + 1)
+        }
+        0xa0..=0xa4 => {
+            let topics = (opcode - 0xa0) as usize;
+            op(LOG_NAMES[topics], topics + 2, 0)
+        }
+        0xf0 => op("CREATE", 3, 1),
+        0xf1 => op("CALL", 7, 1),
+        0xf2 => op("CALLCODE", 7, 1),
+        0xf3 => op("RETURN", 2, 0),
+        0xf4 => op("DELEGATECALL", 6, 1),
+        0xf5 => op("CREATE2", 4, 1),
+        0xfa => op("STATICCALL", 6, 1),
+        0xfd => op("REVERT", 2, 0),
+        0xfe => op("INVALID", 0, 0),
+        0xff => op("SELFDESTRUCT", 1, 0),
+        _ => None,
+    }
+}
+
+/// The fork that introduced `opcode`. Frontier's opcodes, and undefined
+/// ones, report Frontier.
+pub const fn fork(opcode: u8) -> SpecId {
+    match opcode {
+        0xf4 => SpecId::Homestead,
+        0x3d | 0x3e | 0xfa | 0xfd => SpecId::Byzantium,
+        0x1b..=0x1d | 0x3f | 0xf5 => SpecId::Constantinople,
+        0x46 | 0x47 => SpecId::Istanbul,
+        0x48 => SpecId::London,
+        0x5f => SpecId::Shanghai,
+        0x49 | 0x4a | 0x5c..=0x5e => SpecId::Cancun,
+        _ => SpecId::Frontier,
+    }
+}
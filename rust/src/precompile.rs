@@ -0,0 +1,554 @@
+//! Precompiled contracts at addresses 0x01 to 0x0a. They run native code
+//! instead of EVM bytecode, and a failure consumes all the gas they were
+//! given.
+
+use crate::interpreter::FrameResult;
+use crate::transaction::VERSIONED_HASH_VERSION_KZG;
+use crate::{keccak256, Address, Halt, SpecId};
+use bn::Group;
+use k256::ecdsa::{RecoveryId, Signature, VerifyingKey};
+use num_bigint::BigUint;
+use primitive_types::{H160, U256};
+use ripemd::Ripemd160;
+use sha2::{Digest, Sha256};
+
+const ECRECOVER: u64 = 3000;
+const SHA256_BASE: u64 = 60;
+const SHA256_WORD: u64 = 12;
+const RIPEMD160_BASE: u64 = 600;
+const RIPEMD160_WORD: u64 = 120;
+const IDENTITY_BASE: u64 = 15;
+const IDENTITY_WORD: u64 = 3;
+const MODEXP_MIN: u64 = 200;
+const POINT_EVALUATION: u64 = 50000;
+
+/// BLS12-381 scalar field modulus, returned by the point evaluation
+/// precompile along with the number of field elements in a blob.
+const BLS_MODULUS: [u8; 32] = [
+    0x73, 0xed, 0xa7, 0x53, 0x29, 0x9d, 0x7d, 0x48, 0x33, 0x39, 0xd8, 0x08, 0x09, 0xa1, 0xd8, 0x05, 0x53, 0xbd, 0xa4,
+    0x02, 0xff, 0xfe, 0x5b, 0xfe, 0xff, 0xff, 0xff, 0xff, 0x00, 0x00, 0x00, 0x01,
+];
+
+/// Number of precompiles under `spec`. They occupy the addresses from 0x01
+/// up.
+fn count(spec: SpecId) -> u64 {
+    if spec.is_enabled(SpecId::Cancun) {
+        10
+    } else if spec.is_enabled(SpecId::Istanbul) {
+        9
+    } else if spec.is_enabled(SpecId::Byzantium) {
+        8
+    } else {
+        4
+    }
+}
+
+/// Addresses of the precompiles under `spec`.
+pub fn addresses(spec: SpecId) -> impl Iterator<Item = Address> {
+    (1..=count(spec)).map(H160::from_low_u64_be)
+}
+
+/// Runs the precompile at `address` with `gas_limit` gas, or returns `None`
+/// when there is none there under `spec`.
+pub fn run(spec: SpecId, address: &Address, input: &[u8], gas_limit: u64) -> Option<FrameResult> {
+    let (prefix, index) = (&address.as_bytes()[..19], address.as_bytes()[19]);
+    if prefix.iter().any(|byte| *byte != 0) || index == 0 || u64::from(index) > count(spec) {
+        return None;
+    }
+    let cost = match index {
+        1 => ECRECOVER,
+        2 => SHA256_BASE + SHA256_WORD * words(input.len()),
+        3 => RIPEMD160_BASE + RIPEMD160_WORD * words(input.len()),
+        4 => IDENTITY_BASE + IDENTITY_WORD * words(input.len()),
+        5 => modexp_cost(spec, input),
+        6 if spec.is_enabled(SpecId::Istanbul) => 150,
+        6 => 500,
+        7 if spec.is_enabled(SpecId::Istanbul) => 6000,
+        7 => 40000,
+        8 if spec.is_enabled(SpecId::Istanbul) => 45000 + 34000 * (input.len() / 192) as u64,
+        8 => 100000 + 80000 * (input.len() / 192) as u64,
+        // One unit per round. Malformed input fails below.
+        9 => u32::from_be_bytes(padded(input, 0)).into(),
+        _ => POINT_EVALUATION,
+    };
+    if cost > gas_limit {
+        return Some(failure(Halt::OutOfGas));
+    }
+    let output = match index {
+        1 => Some(ecrecover(input)),
+        2 => Some(Sha256::digest(input).to_vec()),
+        3 => {
+            let mut output = vec![0; 12];
+            output.extend_from_slice(&Ripemd160::digest(input));
+            Some(output)
+        }
+        4 => Some(input.to_vec()),
+        5 => Some(modexp(input)),
+        6 => bn_add(input),
+        7 => bn_mul(input),
+        8 => bn_pairing(input),
+        9 => blake2f(input),
+        _ => point_evaluation(input),
+    };
+    Some(match output {
+        Some(output) => FrameResult {
+            success: true,
+            output,
+            gas_left: gas_limit - cost,
+            ..Default::default()
+        },
+        None => failure(Halt::PrecompileFailure),
+    })
+}
+
+fn failure(halt: Halt) -> FrameResult {
+    FrameResult {
+        halt: Some(halt),
+        ..Default::default()
+    }
+}
+
+fn words(size: usize) -> u64 {
+    (size as u64).div_ceil(32)
+}
+
+/// `N` bytes of `input` from `offset`, padded with zeros past its end.
+fn padded<const N: usize>(input: &[u8], offset: usize) -> [u8; N] {
+    let mut bytes = [0; N];
+    let input = input.get(offset..).unwrap_or_default();
+    let len = input.len().min(N);
+    bytes[..len].copy_from_slice(&input[..len]);
+    bytes
+}
+
+/// Like [`padded`], for a length known only at run time.
+fn padded_vec(input: &[u8], offset: usize, len: usize) -> Vec<u8> {
+    let mut bytes = vec![0; len];
+    let input = input.get(offset..).unwrap_or_default();
+    let copied = input.len().min(len);
+    bytes[..copied].copy_from_slice(&input[..copied]);
+    bytes
+}
+
+/// The signer of a message hash, left-padded to 32 bytes. Invalid
+/// signatures give empty output rather than failing.
+fn ecrecover(input: &[u8]) -> Vec<u8> {
+    let input: [u8; 128] = padded(input, 0);
+    let v = U256::from_big_endian(&input[32..64]);
+    if v != U256::from(27) && v != U256::from(28) {
+        return Vec::new();
+    }
+    let Ok(mut signature) = Signature::from_slice(&input[64..]) else {
+        return Vec::new();
+    };
+    let mut id = RecoveryId::new(v == U256::from(28), false);
+    // k256 only recovers from low-s signatures. Negating s recovers the same
+    // key from the other point.
+    if let Some(normalized) = signature.normalize_s() {
+        signature = normalized;
+        id = RecoveryId::new(!id.is_y_odd(), false);
+    }
+    let Ok(key) = VerifyingKey::recover_from_prehash(&input[..32], &signature, id) else {
+        return Vec::new();
+    };
+    let mut output = keccak256(&key.to_encoded_point(false).as_bytes()[1..]);
+    output[..12].fill(0);
+    output.to_vec()
+}
+
+/// The three length fields of a MODEXP input, saturated to `u64`.
+fn modexp_lengths(input: &[u8]) -> [u64; 3] {
+    [0, 32, 64].map(|offset| {
+        let len = U256::from_big_endian(&padded::<32>(input, offset));
+        len.try_into().unwrap_or(u64::MAX)
+    })
+}
+
+/// Gas for MODEXP: EIP-2565 from Berlin, EIP-198 before.
+fn modexp_cost(spec: SpecId, input: &[u8]) -> u64 {
+    let [base_len, exp_len, mod_len] = modexp_lengths(input);
+    let exp_offset = usize::try_from(base_len).map_or(usize::MAX, |len| len.saturating_add(96));
+    let head = padded_vec(input, exp_offset, exp_len.min(32) as usize);
+    let head_bits = BigUint::from_bytes_be(&head).bits().saturating_sub(1) as u128;
+    let iterations = match exp_len {
+        0..=32 => head_bits,
+        _ => 8 * (exp_len as u128 - 32) + head_bits,
+    }
+    .max(1);
+
+    let len = base_len.max(mod_len) as u128;
+    let cost = if spec.is_enabled(SpecId::Berlin) {
+        let words = len.div_ceil(8);
+        (words.saturating_mul(words).saturating_mul(iterations) / 3).max(MODEXP_MIN as u128)
+    } else {
+        let complexity = match len {
+            0..=64 => len * len,
+            65..=1024 => len * len / 4 + 96 * len - 3072,
+            _ => (len.saturating_mul(len) / 16).saturating_add(480 * len) - 199680,
+        };
+        complexity.saturating_mul(iterations) / 20
+    };
+    cost.try_into().unwrap_or(u64::MAX)
+}
+
+/// `base ^ exp % mod`, left-padded to the length of `mod`. The gas grows
+/// only linearly with the exponent length, so the exponent is never padded
+/// out: one that runs past the end of the input leaves the modulus beyond it
+/// too, and the result is zero.
+fn modexp(input: &[u8]) -> Vec<u8> {
+    let [base_len, exp_len, mod_len] = modexp_lengths(input).map(|len| usize::try_from(len).unwrap_or(usize::MAX));
+    if mod_len == 0 {
+        return Vec::new();
+    }
+    // Offsets past the end of the input read zeros, so saturating is exact.
+    let exp_offset = base_len.saturating_add(96);
+    let mod_offset = exp_offset.saturating_add(exp_len);
+    let modulus = BigUint::from_bytes_be(&padded_vec(input, mod_offset, mod_len));
+    let result = match modulus == BigUint::default() {
+        true => Vec::new(),
+        false => {
+            // A non-zero modulus starts inside the input, which therefore
+            // holds the whole base and exponent.
+            let base = BigUint::from_bytes_be(&input[96..exp_offset]);
+            let exp = BigUint::from_bytes_be(&input[exp_offset..mod_offset]);
+            base.modpow(&exp, &modulus).to_bytes_be()
+        }
+    };
+    let mut output = vec![0; mod_len];
+    // A zero result encodes as a single zero byte.
+    let result = &result[result.len().saturating_sub(mod_len)..];
+    output[mod_len - result.len()..].copy_from_slice(result);
+    output
+}
+
+/// A point on the alt_bn128 curve G1 from its two coordinates. The
+/// point at infinity is encoded as zeros.
+fn bn_g1(input: &[u8], offset: usize) -> Option<bn::G1> {
+    let x = bn::Fq::from_slice(&padded::<32>(input, offset)).ok()?;
+    let y = bn::Fq::from_slice(&padded::<32>(input, offset + 32)).ok()?;
+    if x.is_zero() && y.is_zero() {
+        return Some(bn::G1::zero());
+    }
+    bn::AffineG1::new(x, y).ok().map(Into::into)
+}
+
+fn bn_encode(point: bn::G1) -> Vec<u8> {
+    let mut output = vec![0; 64];
+    if let Some(point) = bn::AffineG1::from_jacobian(point) {
+        point.x().to_big_endian(&mut output[..32]).expect("32 bytes");
+        point.y().to_big_endian(&mut output[32..]).expect("32 bytes");
+    }
+    output
+}
+
+/// Point addition on alt_bn128 (EIP-196).
+fn bn_add(input: &[u8]) -> Option<Vec<u8>> {
+    Some(bn_encode(bn_g1(input, 0)? + bn_g1(input, 64)?))
+}
+
+/// Scalar multiplication on alt_bn128 (EIP-196).
+fn bn_mul(input: &[u8]) -> Option<Vec<u8>> {
+    let point = bn_g1(input, 0)?;
+    let scalar = bn::Fr::from_slice(&padded::<32>(input, 64)).ok()?;
+    Some(bn_encode(point * scalar))
+}
+
+/// Pairing check on alt_bn128 (EIP-197): one if the product of the pairings
+/// of each (G1, G2) pair is the identity.
+fn bn_pairing(input: &[u8]) -> Option<Vec<u8>> {
+    if !input.len().is_multiple_of(192) {
+        return None;
+    }
+    let mut pairs = Vec::new();
+    for offset in (0..input.len()).step_by(192) {
+        let g1 = bn_g1(input, offset)?;
+        // Each coordinate of the G2 point is written imaginary part first.
+        let fq = |at: usize| bn::Fq::from_slice(&input[offset + at..offset + at + 32]).ok();
+        let x = bn::Fq2::new(fq(96)?, fq(64)?);
+        let y = bn::Fq2::new(fq(160)?, fq(128)?);
+        let g2 = match x.is_zero() && y.is_zero() {
+            true => bn::G2::zero(),
+            false => bn::AffineG2::new(x, y).ok()?.into(),
+        };
+        pairs.push((g1, g2));
+    }
+    let mut output = vec![0; 32];
+    output[31] = (bn::pairing_batch(&pairs) == bn::Gt::one()) as u8;
+    Some(output)
+}
+
+const BLAKE2B_IV: [u64; 8] = [
+    0x6a09e667f3bcc908,
+    0xbb67ae8584caa73b,
+    0x3c6ef372fe94f82b,
+    0xa54ff53a5f1d36f1,
+    0x510e527fade682d1,
+    0x9b05688c2b3e6c1f,
+    0x1f83d9abfb41bd6b,
+    0x5be0cd19137e2179,
+];
+
+/// Message word order of each BLAKE2b round.
+const BLAKE2B_SIGMA: [[usize; 16]; 10] = [
+    [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15],
+    [14, 10, 4, 8, 9, 15, 13, 6, 1, 12, 0, 2, 11, 7, 5, 3],
+    [11, 8, 12, 0, 5, 2, 15, 13, 10, 14, 3, 6, 7, 1, 9, 4],
+    [7, 9, 3, 1, 13, 12, 11, 14, 2, 6, 5, 10, 4, 0, 15, 8],
+    [9, 0, 5, 7, 2, 4, 10, 15, 14, 1, 11, 12, 6, 8, 3, 13],
+    [2, 12, 6, 10, 0, 11, 8, 3, 4, 13, 7, 5, 15, 14, 1, 9],
+    [12, 5, 1, 15, 14, 13, 4, 10, 0, 7, 6, 3, 9, 2, 8, 11],
+    [13, 11, 7, 14, 12, 1, 3, 9, 5, 0, 15, 4, 8, 6, 2, 10],
+    [6, 15, 14, 9, 11, 3, 0, 8, 12, 2, 13, 7, 1, 4, 10, 5],
+    [10, 2, 8, 4, 7, 6, 1, 5, 15, 11, 9, 14, 3, 12, 13, 0],
+];
+
+/// The BLAKE2b compression function F with a caller-chosen number of
+/// rounds (EIP-152).
+fn blake2f(input: &[u8]) -> Option<Vec<u8>> {
+    if input.len() != 213 || input[212] > 1 {
+        return None;
+    }
+    let rounds = u32::from_be_bytes(padded(input, 0));
+    let word = |offset: usize| u64::from_le_bytes(padded(input, offset));
+    let mut h: [u64; 8] = std::array::from_fn(|i| word(4 + 8 * i));
+    let m: [u64; 16] = std::array::from_fn(|i| word(68 + 8 * i));
+
+    let mut v = [0; 16];
+    v[..8].copy_from_slice(&h);
+    v[8..].copy_from_slice(&BLAKE2B_IV);
+    v[12] ^= word(196);
+    v[13] ^= word(204);
+    if input[212] == 1 {
+        v[14] = !v[14];
+    }
+    for round in 0..rounds as usize {
+        let s = &BLAKE2B_SIGMA[round % 10];
+        for (i, [a, b, c, d]) in [
+            [0, 4, 8, 12],
+            [1, 5, 9, 13],
+            [2, 6, 10, 14],
+            [3, 7, 11, 15],
+            [0, 5, 10, 15],
+            [1, 6, 11, 12],
+            [2, 7, 8, 13],
+            [3, 4, 9, 14],
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let (x, y) = (m[s[2 * i]], m[s[2 * i + 1]]);
+            v[a] = v[a].wrapping_add(v[b]).wrapping_add(x);
+            v[d] = (v[d] ^ v[a]).rotate_right(32);
+            v[c] = v[c].wrapping_add(v[d]);
+            v[b] = (v[b] ^ v[c]).rotate_right(24);
+            v[a] = v[a].wrapping_add(v[b]).wrapping_add(y);
+            v[d] = (v[d] ^ v[a]).rotate_right(16);
+            v[c] = v[c].wrapping_add(v[d]);
+            v[b] = (v[b] ^ v[c]).rotate_right(63);
+        }
+    }
+    for i in 0..8 {
+        h[i] ^= v[i] ^ v[i + 8];
+    }
+    Some(h.iter().flat_map(|word| word.to_le_bytes()).collect())
+}
+
+/// Verifies a KZG proof that a blob's polynomial takes value `y` at `z`
+/// (EIP-4844).
+fn point_evaluation(input: &[u8]) -> Option<Vec<u8>> {
+    if input.len() != 192 {
+        return None;
+    }
+    let commitment = &input[96..144];
+    let mut versioned_hash = Sha256::digest(commitment);
+    versioned_hash[0] = VERSIONED_HASH_VERSION_KZG;
+    if input[..32] != versioned_hash[..] {
+        return None;
+    }
+    let verified = c_kzg::KzgProof::verify_kzg_proof(
+        &c_kzg::Bytes48::from_bytes(commitment).ok()?,
+        &c_kzg::Bytes32::from_bytes(&input[32..64]).ok()?,
+        &c_kzg::Bytes32::from_bytes(&input[64..96]).ok()?,
+        &c_kzg::Bytes48::from_bytes(&input[144..]).ok()?,
+        c_kzg::ethereum_kzg_settings(),
+    );
+    if !matches!(verified, Ok(true)) {
+        return None;
+    }
+    let mut output = vec![0; 64];
+    U256::from(c_kzg::FIELD_ELEMENTS_PER_BLOB).to_big_endian(&mut output[..32]);
+    output[32..].copy_from_slice(&BLS_MODULUS);
+    Some(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Evm, State, TxEnv, TxKind};
+
+    const ECRECOVER_INPUT: &str = "456e9aea5e197a1f1af7a3e85a3212fa4049a3ba34c2289b4c860fc0b0c64ef3\
+        000000000000000000000000000000000000000000000000000000000000001c\
+        9242685bf161793cc25603c231bc2f568eb630ea16aa137d2664ac8038825608\
+        4f8ae3bd7535248d0bd448298cc2e2071e56992d0774dc340c368ae950852ada";
+
+    /// Runs precompile `index` under Cancun, returning the gas it used and
+    /// its output.
+    fn call(index: u64, input: &str) -> (u64, Result<String, Halt>) {
+        let input = hex::decode(input).unwrap();
+        let result = run(SpecId::Cancun, &H160::from_low_u64_be(index), &input, 1_000_000).unwrap();
+        let output = match result.halt {
+            Some(halt) => Err(halt),
+            None => Ok(hex::encode(result.output)),
+        };
+        (1_000_000 - result.gas_left, output)
+    }
+
+    fn ok(gas: u64, output: &str) -> (u64, Result<String, Halt>) {
+        (gas, Ok(output.to_string()))
+    }
+
+    #[test]
+    fn precompiles_arrive_with_their_forks() {
+        assert_eq!(addresses(SpecId::Frontier).count(), 4);
+        assert_eq!(addresses(SpecId::Berlin).count(), 9);
+        assert_eq!(addresses(SpecId::Cancun).last(), Some(H160::from_low_u64_be(10)));
+        assert!(run(SpecId::Shanghai, &H160::from_low_u64_be(10), &[], 100_000).is_none());
+        assert!(run(SpecId::Cancun, &H160::zero(), &[], 100_000).is_none());
+        assert!(run(SpecId::Cancun, &H160::repeat_byte(1), &[], 100_000).is_none());
+    }
+
+    #[test]
+    fn ecrecover_recovers_the_signer() {
+        let signer = "0000000000000000000000007156526fbd7a3c72969b54f64e42c10fbb768c8a";
+        assert_eq!(call(1, ECRECOVER_INPUT), ok(3000, signer));
+        // The same signature with s negated and the parity flipped.
+        let high_s = ECRECOVER_INPUT.replace("1c9242", "1b9242").replace(
+            "4f8ae3bd7535248d0bd448298cc2e2071e56992d0774dc340c368ae950852ada",
+            "b0751c428acadb72f42bb7d6733d1df79c5843b9a7d3c407b39bd3a37fb11667",
+        );
+        assert_eq!(call(1, &high_s), ok(3000, signer));
+        // Any other v is invalid, which still succeeds with empty output.
+        assert_eq!(call(1, &ECRECOVER_INPUT.replace("1c9242", "1d9242")), ok(3000, ""));
+    }
+
+    #[test]
+    fn hashes_and_identity() {
+        let empty_sha256 = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+        assert_eq!(call(2, ""), ok(60, empty_sha256));
+        let empty_ripemd160 = "0000000000000000000000009c1185a5c5e9fc54612808977ee8f548b2258d31";
+        assert_eq!(call(3, ""), ok(600, empty_ripemd160));
+        assert_eq!(call(4, "0102"), ok(18, "0102"));
+    }
+
+    #[test]
+    fn modexp_prices_by_fork() {
+        // 3 ^ (p - 1) mod p for the secp256k1 field prime p (EIP-198).
+        let input = format!(
+            "{:064x}{:064x}{:064x}03{}{}",
+            1,
+            32,
+            32,
+            "fffffffffffffffffffffffffffffffffffffffffffffffffffffffefffffc2e",
+            "fffffffffffffffffffffffffffffffffffffffffffffffffffffffefffffc2f"
+        );
+        assert_eq!(call(5, &input), ok(1360, &format!("{:064x}", 1)));
+        let input = hex::decode(input).unwrap();
+        assert_eq!(modexp_cost(SpecId::Byzantium, &input), 13056);
+        // A zero modulus gives zeros, and a short exponent still costs the minimum.
+        assert_eq!(call(5, &format!("{:064x}{:064x}{:064x}0200", 1, 1, 1)), ok(200, "00"));
+        // Lengths that cannot be paid for run out of gas before reading the input.
+        assert_eq!(call(5, &"ff".repeat(96)), (1_000_000, Err(Halt::OutOfGas)));
+    }
+
+    #[test]
+    fn modexp_reads_only_the_input_it_has() {
+        let modexp = H160::from_low_u64_be(5);
+        // An exponent of 2^61 bytes costs about 2^64 / 3 gas, and a base
+        // near 2^64 bytes pushes the offsets past `usize`. Both can be paid
+        // with enough gas, and leave the modulus, so the result, zero.
+        for [base_len, exp_len] in [[0, 1 << 61], [u64::MAX - 64, 1]] {
+            let input = hex::decode(format!("{:064x}{:064x}{:064x}ff", base_len, exp_len, 1)).unwrap();
+            let result = run(SpecId::Cancun, &modexp, &input, u64::MAX).unwrap();
+            assert!(result.success, "{:?}", result.halt);
+            assert_eq!(result.output, vec![0]);
+            assert_eq!(u64::MAX - result.gas_left, modexp_cost(SpecId::Cancun, &input));
+        }
+    }
+
+    #[test]
+    fn alt_bn128_operations() {
+        let generator = format!("{:064x}{:064x}", 1, 2);
+        let doubled = "030644e72e131a029b85045b68181585d97816a916871ca8d3c208c16d87cfd3\
+            15ed738c0e0a7c92e7845f96b2ae9c0a68a6a449e3538fc7ff3ebf7a5a18a2c4";
+        assert_eq!(call(6, &format!("{}{}", generator, generator)), ok(150, doubled));
+        let tripled = "0769bf9ac56bea3ff40232bcb1b6bd159315d84715b8e679f2d355961915abf0\
+            2ab799bee0489429554fdb7c8d086475319e63b40b9c5b57cdf1ff3dd9fe2261";
+        assert_eq!(call(7, &format!("{}{:064x}", generator, 3)), ok(6000, tripled));
+        // (1, 3) is not on the curve.
+        assert_eq!(
+            call(6, &format!("{:064x}{:064x}", 1, 3)),
+            (1_000_000, Err(Halt::PrecompileFailure))
+        );
+        assert_eq!(call(8, ""), ok(45000, &format!("{:064x}", 1)));
+        assert_eq!(call(8, "00"), (1_000_000, Err(Halt::PrecompileFailure)));
+    }
+
+    #[test]
+    fn blake2f_compresses() {
+        // Test vector 5 of EIP-152: the final block of BLAKE2b-512("abc").
+        let input = format!(
+            "0000000c{}616263{}{}{}01",
+            "48c9bdf267e6096a3ba7ca8485ae67bb2bf894fe72f36e3cf1361d5f3af54fa5\
+             d182e6ad7f520e511f6c3e2b8c68059b6bbd41fbabd9831f79217e1319cde05b",
+            "00".repeat(125),
+            "0300000000000000",
+            "0000000000000000"
+        );
+        let hash = "ba80a53f981c4d0d6a2797b69f12f6e94c212f14685ac4b74b12bb6fdbffa2d1\
+            7d87c5392aab792dc252d5de4533cc9518d38aa8dbf1925ab92386edd4009923";
+        assert_eq!(call(9, &input), ok(12, hash));
+        let bad_flag = format!("{}02", &input[..input.len() - 2]);
+        assert_eq!(call(9, &bad_flag), (1_000_000, Err(Halt::PrecompileFailure)));
+    }
+
+    #[test]
+    fn point_evaluation_checks_the_proof() {
+        // The polynomial of an all-zero blob is zero everywhere. Its
+        // commitment and every proof are the point at infinity.
+        let infinity = format!("c0{}", "00".repeat(47));
+        let mut versioned_hash = Sha256::digest(hex::decode(&infinity).unwrap());
+        versioned_hash[0] = VERSIONED_HASH_VERSION_KZG;
+        let input = |y: u64| {
+            format!(
+                "{}{:064x}{:064x}{}{}",
+                hex::encode(versioned_hash),
+                5,
+                y,
+                infinity,
+                infinity
+            )
+        };
+        let expected = format!("{:064x}{}", 4096, hex::encode(BLS_MODULUS));
+        assert_eq!(call(10, &input(0)), ok(50000, &expected));
+        assert_eq!(call(10, &input(1)), (1_000_000, Err(Halt::PrecompileFailure)));
+    }
+
+    #[test]
+    fn calls_reach_precompiles() {
+        let caller = H160::repeat_byte(0x10);
+        let mut evm = Evm::builder()
+            .with_state(State::new())
+            .with_tx_env(TxEnv {
+                caller,
+                kind: TxKind::Call(H160::from_low_u64_be(4)),
+                data: vec![0x01, 0x02].into(),
+                gas_limit: 100_000,
+                ..Default::default()
+            })
+            .build();
+        let result = evm.transact().unwrap();
+        assert_eq!(result.output.0, vec![0x01, 0x02]);
+        // Intrinsic gas, two bytes of call data and one word of copying.
+        assert_eq!(result.gas_used, 21_000 + 2 * 16 + 18);
+    }
+}
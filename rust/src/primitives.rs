@@ -0,0 +1,226 @@
+//! Value types shared by the transaction, block and state inputs, and the
+//! parsing rules used to load them from fixtures.
+//!
+//! Numbers are accepted as JSON integers, `0x`-prefixed hex strings or
+//! decimal strings. Byte strings, addresses and hashes are always hex, with
+//! or without the `0x` prefix.
+
+use primitive_types::{H160, H256, U256};
+use serde::de::Error;
+use serde::{Deserialize, Deserializer};
+use std::fmt;
+use std::ops::Deref;
+use std::str::FromStr;
+
+pub type Address = H160;
+
+/// An owned byte string, written as hex.
+#[derive(Clone, Default, PartialEq, Eq, Hash)]
+pub struct Bytes(pub Vec<u8>);
+
+impl Bytes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Deref for Bytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl From<Vec<u8>> for Bytes {
+    fn from(bytes: Vec<u8>) -> Self {
+        Bytes(bytes)
+    }
+}
+
+impl From<&[u8]> for Bytes {
+    fn from(bytes: &[u8]) -> Self {
+        Bytes(bytes.to_vec())
+    }
+}
+
+impl FromStr for Bytes {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let digits = strip_hex_prefix(value).unwrap_or(value);
+        hex::decode(digits)
+            .map(Bytes)
+            .map_err(|err| format!("invalid hex data {:?}: {}", value, err))
+    }
+}
+
+impl fmt::Debug for Bytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "0x{}", hex::encode(&self.0))
+    }
+}
+
+impl<'de> Deserialize<'de> for Bytes {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        value.parse().map_err(D::Error::custom)
+    }
+}
+
+fn strip_hex_prefix(value: &str) -> Option<&str> {
+    value.strip_prefix("0x").or_else(|| value.strip_prefix("0X"))
+}
+
+/// Parses a `0x`-prefixed hex or a decimal number.
+pub fn parse_u256(value: &str) -> Result<U256, String> {
+    let parsed = match strip_hex_prefix(value) {
+        Some("") => Ok(U256::zero()),
+        Some(digits) if digits.len() <= 64 => U256::from_str_radix(digits, 16).map_err(|err| format!("{:?}", err)),
+        Some(_) => Err("more than 256 bits".to_string()),
+        None => U256::from_dec_str(value).map_err(|err| format!("{:?}", err)),
+    };
+    parsed.map_err(|err| format!("invalid number {:?}: {}", value, err))
+}
+
+pub fn parse_u64(value: &str) -> Result<u64, String> {
+    let number = parse_u256(value)?;
+    if number > U256::from(u64::MAX) {
+        return Err(format!("invalid number {:?}: does not fit in 64 bits", value));
+    }
+    Ok(number.as_u64())
+}
+
+/// Parses an address. Always hex, with or without the `0x` prefix; short
+/// forms such as `0x888` are left-padded with zeros.
+pub fn parse_address(value: &str) -> Result<Address, String> {
+    parse_fixed::<20>(value)
+        .map(H160)
+        .map_err(|err| format!("invalid address {:?}: {}", value, err))
+}
+
+/// Parses a 32-byte hash or storage word. Always hex, with or without the
+/// `0x` prefix; short forms are left-padded with zeros.
+pub fn parse_h256(value: &str) -> Result<H256, String> {
+    parse_fixed::<32>(value)
+        .map(H256)
+        .map_err(|err| format!("invalid hash {:?}: {}", value, err))
+}
+
+fn parse_fixed<const N: usize>(value: &str) -> Result<[u8; N], String> {
+    let digits = strip_hex_prefix(value).unwrap_or(value);
+    if digits.len() > 2 * N {
+        return Err(format!("longer than {} bytes", N));
+    }
+    let padded = format!("{:0>width$}", digits, width = 2 * N);
+    let mut bytes = [0u8; N];
+    hex::decode_to_slice(padded, &mut bytes).map_err(|err| err.to_string())?;
+    Ok(bytes)
+}
+
+/// `deserialize_with` helpers for the typed fields of `Txn`, `Block`, `Account` and `Log`.
+pub(crate) mod de {
+    use super::*;
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Raw {
+        Number(u64),
+        Text(String),
+    }
+
+    impl Raw {
+        fn parse<T>(self, parse: fn(&str) -> Result<T, String>) -> Result<T, String> {
+            match self {
+                Raw::Number(number) => parse(&number.to_string()),
+                Raw::Text(text) => parse(&text),
+            }
+        }
+    }
+
+    fn with<'de, D, T>(deserializer: D, parse: fn(&str) -> Result<T, String>) -> Result<T, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Raw::deserialize(deserializer)?.parse(parse).map_err(D::Error::custom)
+    }
+
+    fn with_opt<'de, D, T>(deserializer: D, parse: fn(&str) -> Result<T, String>) -> Result<Option<T>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        match Option::<Raw>::deserialize(deserializer)? {
+            Some(raw) => raw.parse(parse).map(Some).map_err(D::Error::custom),
+            None => Ok(None),
+        }
+    }
+
+    pub fn u256<'de, D: Deserializer<'de>>(deserializer: D) -> Result<U256, D::Error> {
+        with(deserializer, parse_u256)
+    }
+
+    pub fn u64<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+        with(deserializer, parse_u64)
+    }
+
+    pub fn opt_u64<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u64>, D::Error> {
+        with_opt(deserializer, parse_u64)
+    }
+
+    pub fn address<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Address, D::Error> {
+        parse_address(&String::deserialize(deserializer)?).map_err(D::Error::custom)
+    }
+
+    pub fn opt_address<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Address>, D::Error> {
+        Option::<String>::deserialize(deserializer)?
+            .map(|address| parse_address(&address).map_err(D::Error::custom))
+            .transpose()
+    }
+
+    pub fn opt_h256<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<H256>, D::Error> {
+        Option::<String>::deserialize(deserializer)?
+            .map(|hash| parse_h256(&hash).map_err(D::Error::custom))
+            .transpose()
+    }
+
+    pub fn h256_list<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<H256>, D::Error> {
+        Vec::<String>::deserialize(deserializer)?
+            .iter()
+            .map(|hash| parse_h256(hash).map_err(D::Error::custom))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn addresses_are_hex_with_optional_prefix() {
+        let expected = H160::from_low_u64_be(0x1000);
+        assert_eq!(parse_address("0x1000").unwrap(), expected);
+        assert_eq!(parse_address("1000").unwrap(), expected);
+        assert_eq!(
+            parse_address("0000000000000000000000000000000000001000").unwrap(),
+            expected
+        );
+        assert!(parse_address("0x1000000000000000000000000000000000000000ff").is_err());
+        assert!(parse_address("0xzz").is_err());
+    }
+
+    #[test]
+    fn hashes_are_hex_with_optional_prefix() {
+        let expected = H256::from_low_u64_be(0x10);
+        assert_eq!(parse_h256("0x10").unwrap(), expected);
+        assert_eq!(parse_h256("10").unwrap(), expected);
+        assert_eq!(parse_h256(&format!("{:064x}", 0x10)).unwrap(), expected);
+        assert!(parse_h256(&"f".repeat(65)).is_err());
+    }
+
+    #[test]
+    fn quantities_keep_decimal() {
+        assert_eq!(parse_u256("10").unwrap(), U256::from(10));
+        assert_eq!(parse_u256("0x10").unwrap(), U256::from(16));
+        assert_eq!(parse_u64("0x").unwrap(), 0);
+    }
+}
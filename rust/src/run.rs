@@ -0,0 +1,330 @@
+//! `run`: executes a snippet of bytecode from the command line, like geth's
+//! `evm run`. The code runs as the receiver's, in a message call from the
+//! sender, so no intrinsic gas is charged and no gas is bought.
+
+use clap::Args;
+use evm::{
+    opcode_info, parse_address, parse_h256, parse_u256, parse_u64, Address, BlockEnv, Bytes, CallInputs, CallScheme,
+    CfgEnv, Eip3155Tracer, Env, ExecutionResult, ExitReason, FrameState, Host, Inspector, SpecId, State, StateHost,
+    StepControl, TxEnv, TxKind,
+};
+use primitive_types::U256;
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+#[derive(Args)]
+pub struct RunCodeArgs {
+    /// The code, in hex or as a file holding hex. Defaults to the receiver's
+    /// code in the prestate.
+    #[arg(long, value_name = "HEX|FILE")]
+    code: Option<String>,
+    /// Calldata, in hex.
+    #[arg(long, value_name = "HEX", default_value = "0x")]
+    input: Bytes,
+    /// Wei sent with the call. The sender must have it in the prestate.
+    #[arg(long, value_name = "WEI", default_value = "0", value_parser = parse_u256)]
+    value: U256,
+    #[arg(long, default_value_t = 10_000_000_000)]
+    gas: u64,
+    #[arg(long, value_name = "ADDRESS", default_value = "0x73656e646572", value_parser = parse_address)]
+    sender: Address,
+    /// The account the code runs as.
+    #[arg(long, value_name = "ADDRESS", default_value = "0x7265636569766572", value_parser = parse_address)]
+    receiver: Address,
+    /// A geth genesis file, whose `alloc` is the prestate and whose header
+    /// fields make the block.
+    #[arg(long, value_name = "FILE")]
+    prestate: Option<String>,
+    #[arg(long, value_name = "FORK", default_value_t = SpecId::default())]
+    fork: SpecId,
+    /// Prints the result, and the trace, as JSON.
+    #[arg(long)]
+    json: bool,
+    /// Traces every instruction to stderr, as EIP-3155 lines with `--json`.
+    #[arg(long)]
+    trace: bool,
+}
+
+/// The parts of a geth genesis file that matter here.
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Genesis {
+    #[serde(default)]
+    alloc: State,
+    #[serde(default)]
+    config: GenesisConfig,
+    coinbase: Option<String>,
+    number: Option<String>,
+    timestamp: Option<String>,
+    gas_limit: Option<String>,
+    difficulty: Option<String>,
+    mix_hash: Option<String>,
+    base_fee_per_gas: Option<String>,
+    excess_blob_gas: Option<String>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GenesisConfig {
+    chain_id: Option<u64>,
+}
+
+/// Runs the code and prints the outcome.
+pub fn run(args: RunCodeArgs) -> Result<(), String> {
+    let result = execute(&args)?;
+    let exit = match result.exit_reason {
+        ExitReason::Success => "success".to_string(),
+        ExitReason::Revert => "revert".to_string(),
+        ExitReason::Halt(halt) => halt.to_string(),
+    };
+    if args.json {
+        let logs: Vec<_> = result
+            .logs
+            .iter()
+            .map(|log| {
+                json!({
+                    "address": format!("{:?}", log.address),
+                    "topics": log.topics.iter().map(|topic| format!("{:?}", topic)).collect::<Vec<_>>(),
+                    "data": format!("{:?}", log.data),
+                })
+            })
+            .collect();
+        let report = json!({
+            "output": format!("{:?}", result.output),
+            "gasUsed": format!("{:#x}", result.gas_used),
+            "exit": exit,
+            "logs": logs,
+        });
+        println!("{}", report);
+        return Ok(());
+    }
+    println!("Output:   {:?}", result.output);
+    println!("Gas used: {}", result.gas_used);
+    println!("Exit:     {}", exit);
+    for (index, log) in result.logs.iter().enumerate() {
+        println!("Log {}:    {:?}", index, log.address);
+        for topic in &log.topics {
+            println!("  topic   {:?}", topic);
+        }
+        println!("  data    {:?}", log.data);
+    }
+    Ok(())
+}
+
+/// Runs the code, tracing it if asked to.
+fn execute(args: &RunCodeArgs) -> Result<ExecutionResult, String> {
+    let genesis: Genesis = match args.prestate {
+        Some(ref path) => {
+            let text = std::fs::read_to_string(path).map_err(|err| format!("cannot read {}: {}", path, err))?;
+            serde_json::from_str(&text).map_err(|err| format!("cannot parse {}: {}", path, err))?
+        }
+        None => Genesis::default(),
+    };
+    let env = genesis.env(args)?;
+    let mut state = genesis.alloc;
+    if let Some(ref code) = args.code {
+        state.account_mut(&args.receiver).code = read_code(code)?.0;
+    }
+
+    let mut json_tracer = (args.trace && args.json).then(|| Eip3155Tracer::new(io::stderr()));
+    let mut text_tracer = (args.trace && !args.json).then(TextTracer::default);
+    let mut host = StateHost::new(&mut state, &env);
+    match (&mut json_tracer, &mut text_tracer) {
+        (Some(tracer), _) => host = host.with_inspector(tracer),
+        (_, Some(tracer)) => host = host.with_inspector(tracer),
+        _ => {}
+    }
+    let outcome = host.call(CallInputs {
+        scheme: CallScheme::Call,
+        code_address: args.receiver,
+        address: args.receiver,
+        caller: args.sender,
+        value: args.value,
+        input: args.input.clone(),
+        gas_limit: args.gas,
+        is_static: false,
+        depth: 0,
+    });
+    let logs = host.finish();
+    let result = ExecutionResult {
+        exit_reason: match (outcome.success, outcome.halt) {
+            (true, _) => ExitReason::Success,
+            (false, Some(halt)) => ExitReason::Halt(halt),
+            (false, None) => ExitReason::Revert,
+        },
+        output: outcome.output,
+        logs: if outcome.success { logs } else { Vec::new() },
+        gas_used: args.gas - outcome.gas_left,
+        gas_refunded: 0,
+        created_address: None,
+        state_changes: HashMap::new(),
+    };
+    if let Some(ref mut tracer) = json_tracer {
+        tracer
+            .write_summary(&result)
+            .map_err(|err| format!("cannot write trace: {}", err))?;
+    }
+    Ok(result)
+}
+
+impl Genesis {
+    /// The call's environment, with the block from the genesis header.
+    fn env(&self, args: &RunCodeArgs) -> Result<Env, String> {
+        let defaults = BlockEnv::default();
+        let u64_or = |value: &Option<String>, default: u64| value.as_deref().map_or(Ok(default), parse_u64);
+        let block = BlockEnv {
+            number: u64_or(&self.number, defaults.number)?,
+            coinbase: self
+                .coinbase
+                .as_deref()
+                .map(parse_address)
+                .transpose()?
+                .unwrap_or_default(),
+            timestamp: u64_or(&self.timestamp, defaults.timestamp)?,
+            gas_limit: u64_or(&self.gas_limit, defaults.gas_limit)?,
+            basefee: self
+                .base_fee_per_gas
+                .as_deref()
+                .map(parse_u256)
+                .transpose()?
+                .unwrap_or_default(),
+            difficulty: self
+                .difficulty
+                .as_deref()
+                .map(parse_u256)
+                .transpose()?
+                .unwrap_or_default(),
+            prevrandao: match self.mix_hash {
+                Some(ref hash) if args.fork.is_enabled(SpecId::Paris) => Some(parse_h256(hash)?),
+                _ => None,
+            },
+            excess_blob_gas: self.excess_blob_gas.as_deref().map(parse_u64).transpose()?,
+            block_hashes: Default::default(),
+        };
+        Ok(Env {
+            cfg: CfgEnv {
+                chain_id: self.config.chain_id.unwrap_or(1),
+                spec: args.fork,
+                ..Default::default()
+            },
+            block,
+            tx: TxEnv {
+                caller: args.sender,
+                kind: TxKind::Call(args.receiver),
+                value: args.value,
+                data: args.input.clone(),
+                gas_limit: args.gas,
+                ..Default::default()
+            },
+        })
+    }
+}
+
+/// Reads `--code`: hex, or the name of a file holding hex.
+fn read_code(code: &str) -> Result<Bytes, String> {
+    if let Ok(bytes) = code.parse::<Bytes>() {
+        return Ok(bytes);
+    }
+    let text = std::fs::read_to_string(code).map_err(|err| format!("--code is neither hex nor a file: {}", err))?;
+    let hex: String = text.split_whitespace().collect();
+    hex.parse::<Bytes>()
+        .map_err(|err| format!("{} does not hold hex code: {}", code, err))
+}
+
+/// Writes a line per instruction: depth, pc, opcode, gas left and the
+/// stack, top last.
+#[derive(Default)]
+struct TextTracer;
+
+impl Inspector for TextTracer {
+    fn step(&mut self, frame: &FrameState) -> StepControl {
+        let name = opcode_info(frame.opcode).map_or("INVALID", |info| info.name);
+        let stack: Vec<String> = frame.stack.iter().map(|value| format!("{:#x}", value)).collect();
+        let _ = writeln!(
+            io::stderr(),
+            "{:>2} {:>6} {:<14} gas={:<12} stack=[{}]",
+            frame.depth + 1,
+            frame.pc,
+            name,
+            frame.gas_left,
+            stack.join(", ")
+        );
+        StepControl::Continue
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[derive(Parser)]
+    struct Cli {
+        #[command(flatten)]
+        run: RunCodeArgs,
+    }
+
+    fn execute_with(args: &[&str]) -> ExecutionResult {
+        let cli = Cli::parse_from(std::iter::once("run").chain(args.iter().copied()));
+        execute(&cli.run).unwrap()
+    }
+
+    fn word(value: u8) -> Vec<u8> {
+        let mut word = vec![0; 32];
+        word[31] = value;
+        word
+    }
+
+    #[test]
+    fn runs_code_from_the_command_line() {
+        // PUSH1 2, PUSH1 0xff, EXP: 3 + 3 + 10 + 50 for the one-byte exponent.
+        let result = execute_with(&["--code", "0x600260ff0a"]);
+        assert_eq!(result.exit_reason, ExitReason::Success);
+        assert_eq!(result.gas_used, 66);
+        assert!(result.output.0.is_empty());
+
+        // MSTORE 42 at 0 and RETURN the word: four pushes, MSTORE and a word
+        // of memory.
+        let result = execute_with(&["--code", "602a60005260206000f3"]);
+        assert_eq!(result.exit_reason, ExitReason::Success);
+        assert_eq!(result.gas_used, 18);
+        assert_eq!(result.output.0, word(42));
+
+        // REVERT with no data, and EXP out of gas.
+        let result = execute_with(&["--code", "0x60006000fd"]);
+        assert_eq!(result.exit_reason, ExitReason::Revert);
+        assert_eq!(result.gas_used, 6);
+        let result = execute_with(&["--code", "0x600260ff0a", "--gas", "65"]);
+        assert!(matches!(result.exit_reason, ExitReason::Halt(_)));
+        assert_eq!(result.gas_used, 65);
+    }
+
+    #[test]
+    fn reads_a_geth_prestate_without_hex_prefixes() {
+        // The receiver returns its slot 0x10. Balances stay decimal, as in
+        // geth.
+        let genesis = json!({
+            "config": { "chainId": 5 },
+            "alloc": {
+                "7265636569766572": {
+                    "code": "60105460005260206000f3",
+                    "storage": {
+                        "0000000000000000000000000000000000000000000000000000000000000010": "2a",
+                    },
+                    "balance": "0",
+                },
+                "73656e646572": { "balance": "10" },
+            },
+        });
+        let path = std::env::temp_dir().join(format!("run-{}-prestate.json", std::process::id()));
+        std::fs::write(&path, genesis.to_string()).unwrap();
+        let cli = Cli::parse_from(["run", "--prestate", path.to_str().unwrap(), "--value", "5"]);
+        let result = execute(&cli.run);
+        std::fs::remove_file(&path).unwrap();
+        let result = result.unwrap();
+        assert_eq!(result.exit_reason, ExitReason::Success);
+        assert_eq!(result.output.0, word(42));
+    }
+}
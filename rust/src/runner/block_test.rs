@@ -0,0 +1,508 @@
+//! Runs the BlockchainTests of ethereum/tests. Each test imports a chain of
+//! RLP blocks on top of a genesis block: headers are validated against their
+//! parents, transactions and withdrawals are executed, and the roots each
+//! header commits to are checked. Blocks marked with an expected exception
+//! must be rejected. The test then checks the head of the chain and its
+//! state.
+
+use super::{Case, Failure};
+use evm::{
+    apply_beacon_root, apply_rewards, apply_withdrawals, block_reward, keccak256, logs_bloom, ordered_trie_root,
+    parse_h256, parse_u64, state_root, Address, Bytes, CfgEnv, Evm, Header, Receipt, SealedBlock, SpecId, State,
+    MAX_BLOB_GAS_PER_BLOCK,
+};
+use primitive_types::{H256, U256};
+use serde::Deserialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::Path;
+
+/// Ommers may be at most this many generations older than their nephew.
+const MAX_OMMER_DEPTH: u64 = 6;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BlockTest {
+    network: String,
+    #[serde(rename = "genesisRLP")]
+    genesis_rlp: Bytes,
+    blocks: Vec<BlockJson>,
+    pre: State,
+    post_state: Option<State>,
+    /// Replaces `postState` in fixtures with large states.
+    post_state_hash: Option<String>,
+    lastblockhash: String,
+    #[serde(default)]
+    config: Config,
+}
+
+#[derive(Default, Deserialize)]
+struct Config {
+    chainid: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BlockJson {
+    rlp: String,
+    /// Set when the block is invalid and must not be imported.
+    expect_exception: Option<String>,
+}
+
+/// A test under the fork it is written for.
+pub struct BlockCase {
+    name: String,
+    test: BlockTest,
+    /// `None` for transition networks, such as `BerlinToLondonAt5`, and
+    /// networks the interpreter does not know.
+    spec: Option<SpecId>,
+}
+
+/// Loads every test in `path` written for one of `forks`, or every test
+/// when `forks` is empty. Tests for networks the interpreter does not
+/// model are loaded too, and skipped.
+pub fn load(path: &Path, forks: &[SpecId]) -> Result<Vec<BlockCase>, String> {
+    let text = std::fs::read_to_string(path).map_err(|err| format!("cannot read {}: {}", path.display(), err))?;
+    let tests: BTreeMap<String, BlockTest> =
+        serde_json::from_str(&text).map_err(|err| format!("cannot parse {}: {}", path.display(), err))?;
+
+    let mut cases = Vec::new();
+    for (name, test) in tests {
+        let spec = test.network.parse::<SpecId>().ok();
+        if forks.is_empty() || spec.is_some_and(|spec| forks.contains(&spec)) {
+            cases.push(BlockCase { name, test, spec });
+        }
+    }
+    Ok(cases)
+}
+
+/// An imported block, with what its children need.
+struct Imported {
+    header: Header,
+    ommers: Vec<H256>,
+    state: State,
+    total_difficulty: U256,
+}
+
+/// The blocks imported so far, keyed by hash, and the head of the chain.
+struct Chain {
+    spec: SpecId,
+    chain_id: u64,
+    blocks: HashMap<H256, Imported>,
+    head: H256,
+}
+
+impl Case for BlockCase {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn skip_reason(&self) -> Option<String> {
+        match self.spec {
+            None => Some(format!("unsupported network {}", self.test.network)),
+            Some(spec) if !spec.is_supported() => Some(format!("{} gas rules are not implemented", spec)),
+            Some(_) => None,
+        }
+    }
+
+    fn run(&self) -> Result<(), Failure> {
+        let Some(spec) = self.spec else {
+            return Err(Failure::new(format!("unsupported network {}", self.test.network)));
+        };
+        let test = &self.test;
+        let invalid_fixture = |err: String| Failure::new(format!("invalid fixture: {}", err));
+        let genesis = SealedBlock::decode(&test.genesis_rlp).map_err(invalid_fixture)?;
+        let chain_id = match test.config.chainid {
+            Some(ref chain_id) => parse_u64(chain_id).map_err(invalid_fixture)?,
+            None => 1,
+        };
+        let expected_head = parse_h256(&test.lastblockhash).map_err(invalid_fixture)?;
+
+        let mut mismatches = Vec::new();
+        let pre_root = state_root(&test.pre);
+        if genesis.header.state_root != pre_root {
+            mismatches.push(format!(
+                "genesis state root: header has {:?}, pre has {:?}",
+                genesis.header.state_root, pre_root
+            ));
+        }
+        let mut chain = Chain::new(spec, chain_id, genesis.header, test.pre.clone());
+
+        for (index, block) in test.blocks.iter().enumerate() {
+            let imported = block
+                .rlp
+                .parse::<Bytes>()
+                .and_then(|rlp| SealedBlock::decode(&rlp))
+                .and_then(|block| chain.import(&block));
+            match (imported, &block.expect_exception) {
+                (Ok(hash), Some(exception)) => mismatches.push(format!(
+                    "block {}: expected rejection with {}, but {:?} was imported",
+                    index, exception, hash
+                )),
+                (Ok(_), None) | (Err(_), Some(_)) => {}
+                (Err(err), None) => mismatches.push(format!("block {}: rejected: {}", index, err)),
+            }
+        }
+
+        if chain.head != expected_head {
+            mismatches.push(format!(
+                "last block hash: expected {:?}, got {:?}",
+                expected_head, chain.head
+            ));
+        }
+        let head = &chain.blocks[&chain.head];
+        let root = state_root(&head.state);
+        match (&test.post_state, &test.post_state_hash) {
+            (Some(expected), _) => mismatches.extend(diff_states(expected, &head.state)),
+            (None, Some(hash)) => {
+                let expected = parse_h256(hash).map_err(invalid_fixture)?;
+                if root != expected {
+                    mismatches.push(format!("state root: expected {:?}, got {:?}", expected, root));
+                }
+            }
+            (None, None) => return Err(invalid_fixture("no postState or postStateHash".to_string())),
+        }
+        match mismatches.is_empty() {
+            true => Ok(()),
+            false => Err(Failure {
+                mismatches,
+                context: format!(
+                    "Network: {}\nHead: block {} {:?}\nState root: {:?}",
+                    test.network, head.header.number, chain.head, root
+                ),
+            }),
+        }
+    }
+}
+
+impl Chain {
+    fn new(spec: SpecId, chain_id: u64, genesis: Header, state: State) -> Self {
+        let head = genesis.hash();
+        let genesis = Imported {
+            total_difficulty: genesis.difficulty,
+            header: genesis,
+            ommers: Vec::new(),
+            state,
+        };
+        Chain {
+            spec,
+            chain_id,
+            blocks: HashMap::from([(head, genesis)]),
+            head,
+        }
+    }
+
+    /// Validates and executes `block`, and makes it the head if it extends
+    /// the heaviest chain, or if it is the latest from Paris on.
+    fn import(&mut self, block: &SealedBlock) -> Result<H256, String> {
+        let header = &block.header;
+        let hash = header.hash();
+        if self.blocks.contains_key(&hash) {
+            return Err(format!("block {:?} already imported", hash));
+        }
+        let parent = self
+            .blocks
+            .get(&header.parent_hash)
+            .ok_or_else(|| format!("unknown parent {:?}", header.parent_hash))?;
+        header.validate(&parent.header, self.spec)?;
+        self.validate_ommers(block)?;
+        self.validate_body(block)?;
+
+        let state = self.execute(block, parent.state.clone())?;
+        let imported = Imported {
+            header: header.clone(),
+            ommers: block.ommers.iter().map(Header::hash).collect(),
+            state,
+            total_difficulty: parent.total_difficulty + header.difficulty,
+        };
+        if self.spec.is_enabled(SpecId::Paris) || imported.total_difficulty > self.blocks[&self.head].total_difficulty {
+            self.head = hash;
+        }
+        self.blocks.insert(hash, imported);
+        Ok(hash)
+    }
+
+    /// Checks that the ommers are recent side blocks, valid against their
+    /// own parents, that no earlier block already included.
+    fn validate_ommers(&self, block: &SealedBlock) -> Result<(), String> {
+        let ommers_hash = H256(keccak256(&rlp::encode_list(&block.ommers)));
+        if ommers_hash != block.header.ommers_hash {
+            return Err(format!(
+                "ommers hash mismatch: header has {:?}",
+                block.header.ommers_hash
+            ));
+        }
+        if block.ommers.len() > 2 {
+            return Err(format!("too many ommers: {}", block.ommers.len()));
+        }
+
+        let mut ancestors = BTreeSet::new();
+        let mut included = BTreeSet::new();
+        let mut hash = block.header.parent_hash;
+        while let Some(ancestor) = self.blocks.get(&hash) {
+            if block.header.number - ancestor.header.number > MAX_OMMER_DEPTH {
+                break;
+            }
+            ancestors.insert(hash);
+            included.extend(ancestor.ommers.iter().copied());
+            hash = ancestor.header.parent_hash;
+        }
+
+        let mut seen = BTreeSet::new();
+        for ommer in &block.ommers {
+            let hash = ommer.hash();
+            if !seen.insert(hash) {
+                return Err(format!("duplicate ommer {:?}", hash));
+            }
+            if ancestors.contains(&hash) || hash == block.header.hash() {
+                return Err(format!("ommer {:?} is an ancestor", hash));
+            }
+            if included.contains(&hash) {
+                return Err(format!("ommer {:?} already included", hash));
+            }
+            if !ancestors.contains(&ommer.parent_hash) || ommer.parent_hash == block.header.parent_hash {
+                return Err(format!("ommer {:?} does not branch off a recent ancestor", hash));
+            }
+            let parent = &self.blocks[&ommer.parent_hash].header;
+            ommer
+                .validate(parent, self.spec)
+                .map_err(|err| format!("invalid ommer {:?}: {}", hash, err))?;
+        }
+        Ok(())
+    }
+
+    /// Checks the transactions and withdrawals against the header.
+    fn validate_body(&self, block: &SealedBlock) -> Result<(), String> {
+        let header = &block.header;
+        let transactions_root = ordered_trie_root(block.transactions.iter().map(|tx| tx.encode()));
+        if transactions_root != header.transactions_root {
+            return Err(format!(
+                "transactions root: header has {:?}, body has {:?}",
+                header.transactions_root, transactions_root
+            ));
+        }
+        match (&block.withdrawals, header.withdrawals_root) {
+            (Some(withdrawals), Some(root)) => {
+                let withdrawals_root = ordered_trie_root(withdrawals.iter().map(rlp::encode));
+                if withdrawals_root != root {
+                    return Err(format!(
+                        "withdrawals root: header has {:?}, body has {:?}",
+                        root, withdrawals_root
+                    ));
+                }
+            }
+            (None, None) => {}
+            _ => return Err("withdrawals presence does not match the header".to_string()),
+        }
+        Ok(())
+    }
+
+    /// Runs the block on `state`, the state of its parent, and checks the
+    /// commitments of the header to the outcome.
+    fn execute(&self, block: &SealedBlock, mut state: State) -> Result<State, String> {
+        let header = &block.header;
+        let spec = self.spec;
+        let cfg = CfgEnv {
+            chain_id: self.chain_id,
+            spec,
+            ..Default::default()
+        };
+        let block_env = header.block_env(spec, self.block_hashes(header));
+        if let Some(root) = header.parent_beacon_block_root {
+            apply_beacon_root(&mut state, &cfg, &block_env, root);
+        }
+
+        let mut evm = Evm::builder()
+            .with_state(state)
+            .with_cfg_env(cfg)
+            .with_block_env(block_env)
+            .build();
+        let mut receipts = Vec::new();
+        let mut gas_used = 0u64;
+        let mut blob_gas_used = 0u64;
+        for (index, tx) in block.transactions.iter().enumerate() {
+            let invalid = |err: String| format!("transaction {}: {}", index, err);
+            tx.validate(spec, self.chain_id).map_err(invalid)?;
+            if tx.gas_limit > header.gas_limit - gas_used {
+                return Err(invalid(format!(
+                    "gas limit {} above the {} left in the block",
+                    tx.gas_limit,
+                    header.gas_limit - gas_used
+                )));
+            }
+            blob_gas_used += tx.blob_gas();
+            if blob_gas_used > MAX_BLOB_GAS_PER_BLOCK {
+                return Err(invalid("too much blob gas in the block".to_string()));
+            }
+
+            let sender = tx.recover_sender().map_err(invalid)?;
+            evm.env_mut().tx = tx.tx_env(sender);
+            let result = evm.transact().map_err(|err| invalid(err.to_string()))?;
+            gas_used += result.gas_used;
+            receipts.push(Receipt {
+                tx_type: tx.tx_type,
+                success: result.is_success(),
+                // Receipts held the intermediate state root before Byzantium.
+                state_root: (!spec.is_enabled(SpecId::Byzantium)).then(|| state_root(evm.state())),
+                cumulative_gas_used: gas_used,
+                logs: result.logs,
+            });
+        }
+        let mut state = evm.into_state();
+
+        if gas_used != header.gas_used {
+            return Err(format!(
+                "gas used: header has {}, block used {}",
+                header.gas_used, gas_used
+            ));
+        }
+        if header.blob_gas_used.is_some_and(|gas| gas != blob_gas_used) {
+            return Err(format!(
+                "blob gas used: header has {:?}, block used {}",
+                header.blob_gas_used, blob_gas_used
+            ));
+        }
+        let reward = block_reward(spec);
+        if !reward.is_zero() {
+            let ommers: Vec<_> = block
+                .ommers
+                .iter()
+                .map(|ommer| (header.number - ommer.number, ommer.coinbase))
+                .collect();
+            apply_rewards(&mut state, reward, &header.coinbase, &ommers);
+        }
+        apply_withdrawals(&mut state, block.withdrawals.as_deref().unwrap_or_default());
+
+        let receipts_root = ordered_trie_root(receipts.iter().map(Receipt::encode));
+        if receipts_root != header.receipts_root {
+            return Err(format!(
+                "receipts root: header has {:?}, block has {:?}",
+                header.receipts_root, receipts_root
+            ));
+        }
+        let bloom = logs_bloom(receipts.iter().flat_map(|receipt| &receipt.logs));
+        if bloom != header.logs_bloom {
+            return Err("logs bloom mismatch".to_string());
+        }
+        let root = state_root(&state);
+        if root != header.state_root {
+            return Err(format!(
+                "state root: header has {:?}, block has {:?}",
+                header.state_root, root
+            ));
+        }
+        Ok(state)
+    }
+
+    /// The hashes of the 256 blocks before `header` on its branch.
+    fn block_hashes(&self, header: &Header) -> BTreeMap<u64, H256> {
+        let mut hashes = BTreeMap::new();
+        let mut hash = header.parent_hash;
+        while let Some(block) = self.blocks.get(&hash) {
+            if header.number - block.header.number > 256 {
+                break;
+            }
+            hashes.insert(block.header.number, hash);
+            hash = block.header.parent_hash;
+        }
+        hashes
+    }
+}
+
+/// Describes every account whose expected and actual values differ.
+fn diff_states(expected: &State, actual: &State) -> Vec<String> {
+    let addresses: BTreeSet<Address> = expected
+        .iter()
+        .chain(actual.iter())
+        .map(|(address, _)| *address)
+        .collect();
+    let mut mismatches = Vec::new();
+    for address in addresses {
+        let (expected, actual) = (expected.account(&address), actual.account(&address));
+        match (expected, actual) {
+            (Some(expected), Some(actual)) => {
+                let nonzero = |account: &evm::Account| {
+                    let slots = account.storage.iter().filter(|(_, value)| !value.is_zero());
+                    slots.map(|(key, value)| (*key, *value)).collect::<BTreeMap<_, _>>()
+                };
+                if expected.balance != actual.balance {
+                    mismatches.push(format!(
+                        "{:?} balance: expected {}, got {}",
+                        address, expected.balance, actual.balance
+                    ));
+                }
+                if expected.nonce != actual.nonce {
+                    mismatches.push(format!(
+                        "{:?} nonce: expected {}, got {}",
+                        address, expected.nonce, actual.nonce
+                    ));
+                }
+                if expected.code != actual.code {
+                    mismatches.push(format!("{:?} code differs", address));
+                }
+                if nonzero(expected) != nonzero(actual) {
+                    mismatches.push(format!(
+                        "{:?} storage: expected {:?}, got {:?}",
+                        address,
+                        nonzero(expected),
+                        nonzero(actual)
+                    ));
+                }
+            }
+            (Some(_), None) => mismatches.push(format!("{:?}: expected to exist, but does not", address)),
+            (None, Some(_)) => mismatches.push(format!("{:?}: not expected to exist, but does", address)),
+            (None, None) => {}
+        }
+    }
+    mismatches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture() -> std::path::PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("testdata/block_test.json")
+    }
+
+    #[test]
+    fn passes_the_fixture_and_skips_unsupported_networks() {
+        let mut skipped = Vec::new();
+        for case in load(&fixture(), &[]).unwrap() {
+            match case.skip_reason() {
+                Some(reason) => skipped.push(format!("{} ({})", case.name(), reason)),
+                None => {
+                    if let Err(failure) = case.run() {
+                        panic!("{}: {:?}", case.name(), failure.mismatches);
+                    }
+                }
+            }
+        }
+        assert_eq!(
+            skipped,
+            [
+                "refundsPrecompilesAndWithdrawals_BerlinToLondonAt5 (unsupported network BerlinToLondonAt5)",
+                "refundsPrecompilesAndWithdrawals_Frontier (Frontier gas rules are not implemented)",
+            ]
+        );
+        assert_eq!(load(&fixture(), &[SpecId::Shanghai]).unwrap().len(), 1);
+        assert!(load(&fixture(), &[SpecId::Cancun]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn reports_an_unexpectedly_rejected_block() {
+        let text = std::fs::read_to_string(fixture()).unwrap();
+        let mut tests: BTreeMap<String, BlockTest> = serde_json::from_str(&text).unwrap();
+        let mut test = tests.remove("refundsPrecompilesAndWithdrawals_Shanghai").unwrap();
+        test.blocks[1].expect_exception = None;
+        let case = BlockCase {
+            name: "rejected".to_string(),
+            test,
+            spec: Some(SpecId::Shanghai),
+        };
+        let failure = case.run().unwrap_err();
+        assert_eq!(
+            failure.mismatches,
+            ["block 1: rejected: gas used: header has 21000, block used 0"]
+        );
+    }
+}
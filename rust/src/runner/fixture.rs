@@ -0,0 +1,223 @@
+//! The `evm.json` test schema, and how a test is run and checked.
+
+use super::{Case, Failure};
+use evm::{assemble, disassemble, evm_with_state, render_asm, Block, Bytes, EvmResult, Log, SpecId, State, Txn};
+use primitive_types::U256;
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+pub struct Evmtest {
+    pub name: String,
+    pub hint: String,
+    pub code: Code,
+    pub expect: Expect,
+    pub tx: Option<Txn>,
+    pub block: Option<Block>,
+    pub state: Option<State>,
+    /// Fork to run under. Tests without one get the runner's `--fork`.
+    pub fork: Option<SpecId>,
+    /// Why the test is skipped: it relies on a simplification of the course
+    /// that a real EVM does not make.
+    pub skip: Option<String>,
+    /// Like `skip`, for a test that still runs, to show the difference.
+    #[serde(rename = "expectedFailure")]
+    pub expected_failure: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Code {
+    /// Missing for tests written directly as bytecode.
+    pub asm: Option<String>,
+    pub bin: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Expect {
+    pub stack: Option<Vec<String>>,
+    pub success: bool,
+    /// Logs and return data are only checked when given.
+    pub logs: Option<Vec<Log>>,
+    #[serde(rename = "return")]
+    pub ret: Option<Bytes>,
+}
+
+impl Case for Evmtest {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn skip_reason(&self) -> Option<String> {
+        self.skip.clone()
+    }
+
+    fn expected_failure(&self) -> Option<String> {
+        self.expected_failure.clone()
+    }
+
+    /// Runs the test, checking every expected field.
+    fn run(&self) -> Result<(), Failure> {
+        let code = hex::decode(&self.code.bin).map_err(|err| self.failure(format!("invalid bin: {}", err), &[]))?;
+        if let Some(ref asm) = self.code.asm {
+            match assemble(asm) {
+                Ok(assembled) if assembled == code => {}
+                Ok(assembled) => {
+                    let mismatch = format!(
+                        "asm assembles to {} but bin is {}",
+                        hex::encode(assembled),
+                        self.code.bin
+                    );
+                    return Err(self.failure(mismatch, &code));
+                }
+                Err(err) => return Err(self.failure(format!("asm does not assemble: {}", err), &code)),
+            }
+        }
+
+        let mut expected_stack: Vec<U256> = Vec::new();
+        for value in self.expect.stack.iter().flatten() {
+            let value = U256::from_str_radix(value, 16)
+                .map_err(|err| self.failure(format!("invalid expected stack value {:?}: {}", value, err), &code))?;
+            expected_stack.push(value);
+        }
+
+        let mut state = self.state.clone().unwrap_or_default();
+        let spec = self.fork.unwrap_or_default();
+        let result = evm_with_state(&code, &self.tx, &self.block, &mut state, spec);
+        let mismatches = diff(&self.expect, &expected_stack, &result);
+        match mismatches.is_empty() {
+            true => Ok(()),
+            false => Err(Failure {
+                mismatches,
+                context: self.context(&code),
+            }),
+        }
+    }
+}
+
+impl Evmtest {
+    fn failure(&self, mismatch: String, code: &[u8]) -> Failure {
+        Failure {
+            mismatches: vec![mismatch],
+            context: self.context(code),
+        }
+    }
+
+    fn context(&self, code: &[u8]) -> String {
+        let asm = self.code.asm.clone().unwrap_or_else(|| render_asm(&disassemble(code)));
+        let mut context = format!("Instructions: \n{}\n", asm);
+        if let Some(ref state) = self.state {
+            for (address, account) in state.iter().filter(|(_, account)| !account.code.is_empty()) {
+                context += &format!(
+                    "\nCode of {:?}: \n{}\n",
+                    address,
+                    render_asm(&disassemble(&account.code))
+                );
+            }
+        }
+        context += &format!("\nHint: {}", self.hint);
+        context
+    }
+}
+
+/// Describes every field of `result` that differs from `expect`, each with
+/// its expected and actual value.
+fn diff(expect: &Expect, expected_stack: &[U256], result: &EvmResult) -> Vec<String> {
+    let mut mismatches = Vec::new();
+    if result.success != expect.success {
+        mismatches.push(format!(
+            "success: expected {:?}, got {:?}",
+            expect.success, result.success
+        ));
+    }
+    if result.stack != expected_stack {
+        let render = |stack: &[U256]| {
+            let lines: Vec<String> = stack.iter().map(|v| format!("  {:#X},", v)).collect();
+            format!("[\n{}\n]", lines.join("\n"))
+        };
+        mismatches.push(format!(
+            "stack:\nexpected {}\ngot {}",
+            render(expected_stack),
+            render(&result.stack)
+        ));
+    }
+    if let Some(ref ret) = expect.ret {
+        if ret[..] != result.output[..] {
+            mismatches.push(format!(
+                "return: expected {:?}, got {:?}",
+                ret,
+                Bytes::from(result.output.clone())
+            ));
+        }
+    }
+    if let Some(ref logs) = expect.logs {
+        if logs.len() != result.logs.len() {
+            mismatches.push(format!("logs: expected {}, got {}", logs.len(), result.logs.len()));
+        }
+        for (index, (expected, actual)) in logs.iter().zip(&result.logs).enumerate() {
+            if expected.address != actual.address {
+                mismatches.push(format!(
+                    "logs[{}].address: expected {:?}, got {:?}",
+                    index, expected.address, actual.address
+                ));
+            }
+            if expected.topics != actual.topics {
+                mismatches.push(format!(
+                    "logs[{}].topics: expected {:?}, got {:?}",
+                    index, expected.topics, actual.topics
+                ));
+            }
+            if expected.data != actual.data {
+                mismatches.push(format!(
+                    "logs[{}].data: expected {:?}, got {:?}",
+                    index, expected.data, actual.data
+                ));
+            }
+        }
+    }
+    mismatches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// DIFFICULTY in a block that has both a difficulty and a prevrandao.
+    fn difficulty_test(fork: Option<&str>) -> Evmtest {
+        let mut test = serde_json::json!({
+            "name": "DIFFICULTY",
+            "hint": "",
+            "code": {"asm": "DIFFICULTY", "bin": "44"},
+            "block": {"difficulty": "0x20000", "prevrandao": "0x01"},
+            "expect": {"stack": ["0x20000"], "success": true},
+        });
+        if let Some(fork) = fork {
+            test["fork"] = fork.into();
+        }
+        serde_json::from_value(test).unwrap()
+    }
+
+    #[test]
+    fn runs_under_the_fixture_fork() {
+        assert!(difficulty_test(Some("London")).run().is_ok());
+        assert!(difficulty_test(Some("Cancun")).run().is_err());
+        assert!(difficulty_test(None).run().is_err());
+
+        let mut test = difficulty_test(None);
+        test.fork = Some(SpecId::Berlin);
+        assert!(test.run().is_ok());
+    }
+
+    #[test]
+    fn reads_skips_and_expected_failures_from_the_test() {
+        let test: Evmtest = serde_json::from_value(serde_json::json!({
+            "name": "SELFDESTRUCT",
+            "hint": "",
+            "expectedFailure": "deletes at once",
+            "code": {"asm": null, "bin": "00"},
+            "expect": {"success": true},
+        }))
+        .unwrap();
+        assert_eq!(test.skip_reason(), None);
+        assert_eq!(test.expected_failure().as_deref(), Some("deletes at once"));
+        assert_eq!(difficulty_test(None).expected_failure(), None);
+    }
+}
@@ -0,0 +1,403 @@
+//! Runs every test in a set of fixture files without stopping at failures,
+//! and sums up the outcome.
+
+pub mod block_test;
+mod fixture;
+pub mod report;
+pub mod state_test;
+mod yaml;
+
+use evm::SpecId;
+use fixture::Evmtest;
+use glob::Pattern;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Which tests to run, and how much to print while running them.
+#[derive(Default)]
+pub struct Options {
+    /// Only tests whose name matches one of these run. All run when empty.
+    pub filter: Vec<Pattern>,
+    /// Tests whose name matches one of these are skipped.
+    pub skip: Vec<Pattern>,
+    /// Leaves out passed and skipped tests from the output.
+    pub quiet: bool,
+}
+
+impl Options {
+    fn selects(&self, name: &str) -> bool {
+        (self.filter.is_empty() || self.filter.iter().any(|pattern| pattern.matches(name)))
+            && !self.skip.iter().any(|pattern| pattern.matches(name))
+    }
+}
+
+/// One test of a fixture file.
+pub trait Case {
+    fn name(&self) -> &str;
+    fn run(&self) -> Result<(), Failure>;
+
+    /// Why the test is not run at all, if it is not.
+    fn skip_reason(&self) -> Option<String> {
+        None
+    }
+
+    /// Why the test is known to fail, if it is. It then counts as failed
+    /// only if it passes.
+    fn expected_failure(&self) -> Option<String> {
+        None
+    }
+}
+
+/// What went wrong in a failed test.
+pub struct Failure {
+    /// One entry per mismatching field.
+    pub mismatches: Vec<String>,
+    /// Details that help find the cause, such as the code involved.
+    pub context: String,
+}
+
+impl Failure {
+    pub fn new(mismatch: impl Into<String>) -> Self {
+        Failure {
+            mismatches: vec![mismatch.into()],
+            context: String::new(),
+        }
+    }
+}
+
+pub enum Status {
+    Passed,
+    Failed(Failure),
+    /// Failed, as the test is known to, for the given reason.
+    ExpectedFailure(String),
+    /// Not run, for the given reason. `None` when the options left it out.
+    Skipped(Option<String>),
+}
+
+/// The outcome of one test.
+pub struct Outcome {
+    pub file: String,
+    pub name: String,
+    pub status: Status,
+    pub duration: Duration,
+}
+
+/// Expands `paths` into fixture files. Each is a file, a directory searched
+/// for fixtures recursively, or a glob pattern, which must match something.
+pub fn expand(paths: &[String]) -> Result<Vec<PathBuf>, String> {
+    let mut files = Vec::new();
+    for path in paths {
+        let before = files.len();
+        if !path.contains(['*', '?', '[']) {
+            add(PathBuf::from(path), &mut files)?;
+            continue;
+        }
+        let matches = glob::glob(path).map_err(|err| format!("invalid pattern {:?}: {}", path, err))?;
+        for entry in matches {
+            add(entry.map_err(|err| err.to_string())?, &mut files)?;
+        }
+        if files.len() == before {
+            return Err(format!("no fixture matches {:?}", path));
+        }
+    }
+    Ok(files)
+}
+
+/// Adds `path`, or the fixtures under it if it is a directory, in name order.
+fn add(path: PathBuf, files: &mut Vec<PathBuf>) -> Result<(), String> {
+    if !path.is_dir() {
+        files.push(path);
+        return Ok(());
+    }
+    let mut entries: Vec<PathBuf> = std::fs::read_dir(&path)
+        .map_err(|err| format!("cannot read {}: {}", path.display(), err))?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<_, _>>()
+        .map_err(|err| format!("cannot read {}: {}", path.display(), err))?;
+    entries.sort();
+    for entry in entries {
+        let is_fixture = matches!(
+            entry.extension().and_then(|e| e.to_str()),
+            Some("json" | "yaml" | "yml")
+        );
+        if entry.is_dir() || is_fixture {
+            add(entry, files)?;
+        }
+    }
+    Ok(())
+}
+
+/// Runs every `evm.json`-style test in `files`, under `fork` unless a test
+/// names its own. See [`run_cases`].
+pub fn run(files: &[PathBuf], options: &Options, fork: SpecId) -> Vec<Outcome> {
+    run_cases(files, options, |path| {
+        let mut tests = load(path)?;
+        for test in &mut tests {
+            test.fork.get_or_insert(fork);
+        }
+        Ok(tests)
+    })
+}
+
+/// Runs every case `load` finds in `files`, printing each outcome as it
+/// comes. A file that cannot be loaded counts as one failed test named
+/// after it.
+pub fn run_cases<C: Case>(
+    files: &[PathBuf],
+    options: &Options,
+    load: impl Fn(&Path) -> Result<Vec<C>, String>,
+) -> Vec<Outcome> {
+    // Panics are reported as failures, so the default hook's message on
+    // stderr would only repeat them.
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+
+    let mut outcomes = Vec::new();
+    for path in files {
+        let file = path.display().to_string();
+        let tests = match load(path) {
+            Ok(tests) => tests,
+            Err(err) => {
+                let outcome = Outcome {
+                    name: file.clone(),
+                    file,
+                    status: Status::Failed(Failure::new(err)),
+                    duration: Duration::ZERO,
+                };
+                print(&outcome, options);
+                outcomes.push(outcome);
+                continue;
+            }
+        };
+        for test in tests {
+            let start = Instant::now();
+            let status = match (options.selects(test.name()), test.skip_reason()) {
+                (false, _) => Status::Skipped(None),
+                (true, Some(reason)) => Status::Skipped(Some(reason)),
+                (true, None) => {
+                    let status = match panic::catch_unwind(AssertUnwindSafe(|| test.run())) {
+                        Ok(Ok(())) => Status::Passed,
+                        Ok(Err(failure)) => Status::Failed(failure),
+                        Err(payload) => Status::Failed(Failure::new(format!("panicked: {}", panic_message(&*payload)))),
+                    };
+                    match (status, test.expected_failure()) {
+                        (Status::Failed(_), Some(reason)) => Status::ExpectedFailure(reason),
+                        (Status::Passed, Some(reason)) => Status::Failed(Failure::new(format!(
+                            "passed, but is marked as an expected failure: {}",
+                            reason
+                        ))),
+                        (status, _) => status,
+                    }
+                }
+            };
+            let outcome = Outcome {
+                file: file.clone(),
+                name: test.name().to_string(),
+                status,
+                duration: start.elapsed(),
+            };
+            print(&outcome, options);
+            outcomes.push(outcome);
+        }
+    }
+
+    panic::set_hook(hook);
+    outcomes
+}
+
+/// Prints how many tests passed, failed, failed as expected and were
+/// skipped, and lists the failed ones by file.
+pub fn print_summary(outcomes: &[Outcome]) {
+    let count = |wanted: fn(&Status) -> bool| outcomes.iter().filter(|outcome| wanted(&outcome.status)).count();
+    let failed: Vec<&Outcome> = outcomes
+        .iter()
+        .filter(|outcome| matches!(outcome.status, Status::Failed(_)))
+        .collect();
+    println!(
+        "{} tests: {} passed, {} failed, {} failed as expected, {} skipped",
+        outcomes.len(),
+        count(|status| matches!(status, Status::Passed)),
+        failed.len(),
+        count(|status| matches!(status, Status::ExpectedFailure(_))),
+        count(|status| matches!(status, Status::Skipped(_))),
+    );
+    if !failed.is_empty() {
+        println!("\nFailed:");
+        let mut file = None;
+        for outcome in failed {
+            if file != Some(&outcome.file) {
+                file = Some(&outcome.file);
+                println!("  {}", outcome.file);
+            }
+            println!("    {}", outcome.name);
+        }
+    }
+}
+
+/// Reads `evm.json`-style JSON, or the YAML it is generated from when the
+/// file ends in `.yaml` or `.yml`.
+fn load(path: &Path) -> Result<Vec<Evmtest>, String> {
+    let text = std::fs::read_to_string(path).map_err(|err| format!("cannot read {}: {}", path.display(), err))?;
+    let tests = match path.extension().and_then(|extension| extension.to_str()) {
+        Some("yaml" | "yml") => yaml::load(&text),
+        _ => serde_json::from_str(&text).map_err(|err| err.to_string()),
+    };
+    tests.map_err(|err| format!("cannot parse {}: {}", path.display(), err))
+}
+
+fn print(outcome: &Outcome, options: &Options) {
+    match outcome.status {
+        Status::Passed if !options.quiet => println!("PASS {}", outcome.name),
+        Status::Skipped(None) if !options.quiet => println!("SKIP {}", outcome.name),
+        Status::Skipped(Some(ref reason)) if !options.quiet => println!("SKIP {} ({})", outcome.name, reason),
+        Status::ExpectedFailure(ref reason) if !options.quiet => println!("XFAIL {} ({})", outcome.name, reason),
+        Status::Failed(ref failure) => {
+            println!("FAIL {}\n", outcome.name);
+            for mismatch in &failure.mismatches {
+                println!("{}\n", mismatch);
+            }
+            if !failure.context.is_empty() {
+                println!("{}\n", failure.context);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+    match (payload.downcast_ref::<&str>(), payload.downcast_ref::<String>()) {
+        (Some(message), _) => message.to_string(),
+        (_, Some(message)) => message.clone(),
+        _ => "unknown panic".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Stub {
+        name: &'static str,
+        passes: bool,
+        skip: bool,
+        expected_failure: bool,
+    }
+
+    impl Case for Stub {
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        fn run(&self) -> Result<(), Failure> {
+            match self.passes {
+                true => Ok(()),
+                false => Err(Failure::new("mismatch")),
+            }
+        }
+
+        fn skip_reason(&self) -> Option<String> {
+            self.skip.then(|| "unsupported".to_string())
+        }
+
+        fn expected_failure(&self) -> Option<String> {
+            self.expected_failure.then(|| "known".to_string())
+        }
+    }
+
+    #[test]
+    fn skips_and_expected_failures() {
+        let stub = |name, passes, skip, expected_failure| Stub {
+            name,
+            passes,
+            skip,
+            expected_failure,
+        };
+        let options = Options {
+            quiet: true,
+            skip: vec![Pattern::new("filtered").unwrap()],
+            ..Default::default()
+        };
+        let outcomes = run_cases(&[PathBuf::from("stub.json")], &options, |_| {
+            Ok(vec![
+                stub("passes", true, false, false),
+                stub("fails", false, false, false),
+                stub("filtered", true, false, false),
+                stub("unsupported", false, true, false),
+                stub("known", false, false, true),
+                stub("fixed", true, false, true),
+            ])
+        });
+        let statuses: Vec<String> = outcomes
+            .iter()
+            .map(|outcome| match &outcome.status {
+                Status::Passed => "passed".to_string(),
+                Status::Failed(failure) => format!("failed: {}", failure.mismatches[0]),
+                Status::ExpectedFailure(reason) => format!("expected failure: {}", reason),
+                Status::Skipped(reason) => format!("skipped: {:?}", reason),
+            })
+            .collect();
+        assert_eq!(
+            statuses,
+            [
+                "passed",
+                "failed: mismatch",
+                "skipped: None",
+                "skipped: Some(\"unsupported\")",
+                "expected failure: known",
+                "failed: passed, but is marked as an expected failure: known",
+            ]
+        );
+    }
+
+    #[test]
+    fn expands_files_directories_and_globs() {
+        let dir = std::env::temp_dir().join(format!("expand-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        for file in [
+            "b.json",
+            "a.yaml",
+            "notes.txt",
+            "sub/c.yml",
+            "sub/deeper/d.json",
+            "sub/e.txt",
+        ] {
+            let path = dir.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "").unwrap();
+        }
+        let at = |path: &str| dir.join(path).to_string_lossy().into_owned();
+        let expanded = |paths: &[String]| -> Result<Vec<String>, String> {
+            let files = expand(paths)?;
+            Ok(files
+                .iter()
+                .map(|file| file.strip_prefix(&dir).unwrap().to_string_lossy().into_owned())
+                .collect())
+        };
+
+        // A directory yields its fixtures, recursively and in name order.
+        assert_eq!(
+            expanded(&[at("")]).unwrap(),
+            ["a.yaml", "b.json", "sub/c.yml", "sub/deeper/d.json"]
+        );
+        // A file is taken as given, fixture or not.
+        assert_eq!(
+            expanded(&[at("notes.txt"), at("b.json")]).unwrap(),
+            ["notes.txt", "b.json"]
+        );
+        // A glob may match files and directories alike.
+        assert_eq!(
+            expanded(&[at("*.json"), at("s?b")]).unwrap(),
+            ["b.json", "sub/c.yml", "sub/deeper/d.json"]
+        );
+        assert_eq!(expanded(&[at("sub/[a-d].*")]).unwrap(), ["sub/c.yml"]);
+        // A glob must match something, and must be well formed.
+        assert_eq!(
+            expanded(&[at("*.yaml"), at("*.toml")]).unwrap_err(),
+            format!("no fixture matches {:?}", at("*.toml"))
+        );
+        assert!(expanded(&[at("[")]).unwrap_err().starts_with("invalid pattern"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
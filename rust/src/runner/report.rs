@@ -0,0 +1,251 @@
+//! Machine-readable reports of a run, for CI to track over time.
+
+use super::{Outcome, Status};
+use serde_json::{json, Value};
+use std::fmt::Write;
+
+/// A JSON object with the totals and one entry per test.
+pub fn json(outcomes: &[Outcome]) -> Value {
+    let tests: Vec<Value> = outcomes
+        .iter()
+        .map(|outcome| {
+            let (status, mismatches, reason) = match &outcome.status {
+                Status::Passed => ("passed", &[][..], None),
+                Status::Failed(failure) => ("failed", &failure.mismatches[..], None),
+                Status::ExpectedFailure(reason) => ("expected_failure", &[][..], Some(reason)),
+                Status::Skipped(reason) => ("skipped", &[][..], reason.as_ref()),
+            };
+            json!({
+                "file": outcome.file,
+                "name": outcome.name,
+                "status": status,
+                "reason": reason,
+                "time": outcome.duration.as_secs_f64(),
+                "failures": mismatches,
+            })
+        })
+        .collect();
+    let count = |wanted: &str| tests.iter().filter(|test| test["status"] == wanted).count();
+    json!({
+        "passed": count("passed"),
+        "failed": count("failed"),
+        "expected_failures": count("expected_failure"),
+        "skipped": count("skipped"),
+        "tests": tests,
+    })
+}
+
+/// A JUnit XML document with one test suite per fixture file.
+pub fn junit(outcomes: &[Outcome]) -> String {
+    let mut files: Vec<&str> = Vec::new();
+    for outcome in outcomes {
+        if !files.contains(&outcome.file.as_str()) {
+            files.push(&outcome.file);
+        }
+    }
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let (failed, skipped) = totals(outcomes.iter());
+    let _ = writeln!(
+        xml,
+        "<testsuites tests=\"{}\" failures=\"{}\" skipped=\"{}\">",
+        outcomes.len(),
+        failed,
+        skipped
+    );
+    for file in files {
+        let suite: Vec<&Outcome> = outcomes.iter().filter(|outcome| outcome.file == file).collect();
+        let (failed, skipped) = totals(suite.iter().copied());
+        let time: f64 = suite.iter().map(|outcome| outcome.duration.as_secs_f64()).sum();
+        let _ = writeln!(
+            xml,
+            "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" skipped=\"{}\" time=\"{:.6}\">",
+            escape(file),
+            suite.len(),
+            failed,
+            skipped,
+            time
+        );
+        for outcome in suite {
+            let _ = write!(
+                xml,
+                "    <testcase name=\"{}\" classname=\"{}\" time=\"{:.6}\"",
+                escape(&outcome.name),
+                escape(file),
+                outcome.duration.as_secs_f64()
+            );
+            match &outcome.status {
+                Status::Passed => xml.push_str("/>\n"),
+                // JUnit has no expected failures, so they are reported as
+                // skipped, like pytest does.
+                Status::Skipped(None) => xml.push_str(">\n      <skipped/>\n    </testcase>\n"),
+                Status::Skipped(Some(reason)) => {
+                    let _ = write!(
+                        xml,
+                        ">\n      <skipped message=\"{}\"/>\n    </testcase>\n",
+                        escape(reason)
+                    );
+                }
+                Status::ExpectedFailure(reason) => {
+                    let _ = write!(
+                        xml,
+                        ">\n      <skipped message=\"expected failure: {}\"/>\n    </testcase>\n",
+                        escape(reason)
+                    );
+                }
+                Status::Failed(failure) => {
+                    let message = failure.mismatches.first().map(String::as_str).unwrap_or_default();
+                    let mut body = failure.mismatches.join("\n\n");
+                    if !failure.context.is_empty() {
+                        body = format!("{}\n\n{}", body, failure.context);
+                    }
+                    let _ = write!(
+                        xml,
+                        ">\n      <failure message=\"{}\">{}</failure>\n    </testcase>\n",
+                        escape(message.lines().next().unwrap_or_default()),
+                        escape(&body)
+                    );
+                }
+            }
+        }
+        xml.push_str("  </testsuite>\n");
+    }
+    xml.push_str("</testsuites>\n");
+    xml
+}
+
+/// Numbers of failed and skipped tests. Expected failures count as skipped.
+fn totals<'a>(outcomes: impl Iterator<Item = &'a Outcome>) -> (usize, usize) {
+    outcomes.fold((0, 0), |(failed, skipped), outcome| match outcome.status {
+        Status::Failed(_) => (failed + 1, skipped),
+        Status::Skipped(_) | Status::ExpectedFailure(_) => (failed, skipped + 1),
+        Status::Passed => (failed, skipped),
+    })
+}
+
+/// Escapes `text` for XML, replacing the characters XML 1.0 does not allow
+/// at all, such as most control characters, with U+FFFD.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\t' | '\n' | '\r' => escaped.push(c),
+            '\0'..='\x1f' | '\u{fffe}' | '\u{ffff}' => escaped.push(char::REPLACEMENT_CHARACTER),
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::Failure;
+    use std::time::Duration;
+
+    fn outcomes() -> Vec<Outcome> {
+        let outcome = |file: &str, name: &str, status, millis| Outcome {
+            file: file.to_string(),
+            name: name.to_string(),
+            status,
+            duration: Duration::from_millis(millis),
+        };
+        vec![
+            outcome("a.json", "ADD", Status::Passed, 1),
+            outcome(
+                "a.json",
+                "MUL <8>",
+                Status::Failed(Failure {
+                    mismatches: vec!["stack: expected [1]\ngot [2]".to_string(), "gas".to_string()],
+                    context: "code \"6001\"\u{1b}[0m".to_string(),
+                }),
+                2,
+            ),
+            outcome(
+                "b.json",
+                "SELFDESTRUCT",
+                Status::ExpectedFailure("deleted at once".to_string()),
+                3,
+            ),
+            outcome("b.json", "CREATE", Status::Skipped(Some("unfunded".to_string())), 0),
+            outcome("b.json", "filtered", Status::Skipped(None), 0),
+        ]
+    }
+
+    #[test]
+    fn json_report() {
+        let expected = json!({
+            "passed": 1,
+            "failed": 1,
+            "expected_failures": 1,
+            "skipped": 2,
+            "tests": [
+                {"file": "a.json", "name": "ADD", "status": "passed", "reason": null, "time": 0.001, "failures": []},
+                {
+                    "file": "a.json",
+                    "name": "MUL <8>",
+                    "status": "failed",
+                    "reason": null,
+                    "time": 0.002,
+                    "failures": ["stack: expected [1]\ngot [2]", "gas"],
+                },
+                {
+                    "file": "b.json",
+                    "name": "SELFDESTRUCT",
+                    "status": "expected_failure",
+                    "reason": "deleted at once",
+                    "time": 0.003,
+                    "failures": [],
+                },
+                {"file": "b.json", "name": "CREATE", "status": "skipped", "reason": "unfunded", "time": 0.0, "failures": []},
+                {"file": "b.json", "name": "filtered", "status": "skipped", "reason": null, "time": 0.0, "failures": []},
+            ],
+        });
+        assert_eq!(json(&outcomes()), expected);
+    }
+
+    #[test]
+    fn junit_report() {
+        let expected = r#"<?xml version="1.0" encoding="UTF-8"?>
+<testsuites tests="5" failures="1" skipped="3">
+  <testsuite name="a.json" tests="2" failures="1" skipped="0" time="0.003000">
+    <testcase name="ADD" classname="a.json" time="0.001000"/>
+    <testcase name="MUL &lt;8&gt;" classname="a.json" time="0.002000">
+      <failure message="stack: expected [1]">stack: expected [1]
+got [2]
+
+gas
+
+code &quot;6001&quot;�[0m</failure>
+    </testcase>
+  </testsuite>
+  <testsuite name="b.json" tests="3" failures="0" skipped="3" time="0.003000">
+    <testcase name="SELFDESTRUCT" classname="b.json" time="0.003000">
+      <skipped message="expected failure: deleted at once"/>
+    </testcase>
+    <testcase name="CREATE" classname="b.json" time="0.000000">
+      <skipped message="unfunded"/>
+    </testcase>
+    <testcase name="filtered" classname="b.json" time="0.000000">
+      <skipped/>
+    </testcase>
+  </testsuite>
+</testsuites>
+"#;
+        assert_eq!(junit(&outcomes()), expected);
+    }
+
+    #[test]
+    fn escapes_markup_and_replaces_characters_xml_forbids() {
+        assert_eq!(escape("a<b & 'c'\t\r\n"), "a&lt;b &amp; &apos;c&apos;\t\r\n");
+        assert_eq!(
+            escape("\0\u{8}\u{1b}\u{7f}\u{fffe}"),
+            "\u{fffd}\u{fffd}\u{fffd}\u{7f}\u{fffd}"
+        );
+    }
+}
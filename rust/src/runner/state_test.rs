@@ -0,0 +1,370 @@
+//! Runs the GeneralStateTests of ethereum/tests. Each test applies one
+//! transaction to a prestate, in every combination of its data, gas and
+//! value variants that a fork lists an expected post-state for. The
+//! post-state is checked by its root hash and the logs by their hash.
+
+use super::{Case, Failure};
+use evm::{
+    keccak256, logs_hash, parse_address, parse_h256, parse_u256, parse_u64, state_root, AccessListItem, Address, Block,
+    BlockEnv, Bytes, CfgEnv, Evm, ExitReason, SpecId, State, TxEnv, TxKind,
+};
+use primitive_types::H256;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::rc::Rc;
+
+#[derive(Deserialize)]
+struct StateTest {
+    env: Block,
+    pre: State,
+    transaction: Transaction,
+    post: BTreeMap<String, Vec<PostState>>,
+    #[serde(default)]
+    config: Config,
+}
+
+#[derive(Default, Deserialize)]
+struct Config {
+    chainid: Option<String>,
+}
+
+/// The transaction, with a list of variants for data, gas limit and value.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Transaction {
+    data: Vec<Bytes>,
+    gas_limit: Vec<String>,
+    value: Vec<String>,
+    gas_price: Option<String>,
+    max_fee_per_gas: Option<String>,
+    max_priority_fee_per_gas: Option<String>,
+    nonce: String,
+    secret_key: Option<String>,
+    /// Newer fixtures name the sender instead of leaving it to the key.
+    sender: Option<String>,
+    /// Empty for a creation.
+    to: String,
+    /// One per data variant. Present only for EIP-2930 and later types.
+    access_lists: Option<Vec<Option<Vec<AccessListJson>>>>,
+    blob_versioned_hashes: Option<Vec<String>>,
+    max_fee_per_blob_gas: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AccessListJson {
+    address: String,
+    storage_keys: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PostState {
+    hash: String,
+    logs: String,
+    indexes: Indexes,
+    /// Set when the transaction is invalid and leaves the state unchanged.
+    expect_exception: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Indexes {
+    data: usize,
+    gas: usize,
+    value: usize,
+}
+
+/// One variant of a test under one fork.
+pub struct StateCase {
+    name: String,
+    test: Rc<StateTest>,
+    fork: String,
+    /// `None` for forks the interpreter does not know.
+    spec: Option<SpecId>,
+    index: usize,
+}
+
+/// Loads the cases of every test in `path` for `forks`, or for every fork
+/// when `forks` is empty. Cases for forks the interpreter does not model
+/// are loaded too, and skipped.
+pub fn load(path: &Path, forks: &[SpecId]) -> Result<Vec<StateCase>, String> {
+    let text = std::fs::read_to_string(path).map_err(|err| format!("cannot read {}: {}", path.display(), err))?;
+    let tests: BTreeMap<String, StateTest> =
+        serde_json::from_str(&text).map_err(|err| format!("cannot parse {}: {}", path.display(), err))?;
+
+    let mut cases = Vec::new();
+    for (name, test) in tests {
+        let test = Rc::new(test);
+        let mut selected: Vec<(Option<SpecId>, &String)> = test
+            .post
+            .keys()
+            .map(|fork| (fork.parse::<SpecId>().ok(), fork))
+            .filter(|(spec, _)| forks.is_empty() || spec.is_some_and(|spec| forks.contains(&spec)))
+            .collect();
+        selected.sort();
+        for (spec, fork) in selected {
+            for (index, post) in test.post[fork].iter().enumerate() {
+                let Indexes { data, gas, value } = post.indexes;
+                cases.push(StateCase {
+                    name: format!("{}/{}/d{}g{}v{}", name, fork, data, gas, value),
+                    test: test.clone(),
+                    fork: fork.clone(),
+                    spec,
+                    index,
+                });
+            }
+        }
+    }
+    Ok(cases)
+}
+
+impl Case for StateCase {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn skip_reason(&self) -> Option<String> {
+        match self.spec {
+            None => Some(format!("unknown fork {}", self.fork)),
+            Some(spec) if !spec.is_supported() => Some(format!("{} gas rules are not implemented", spec)),
+            Some(_) => None,
+        }
+    }
+
+    fn run(&self) -> Result<(), Failure> {
+        let Some(spec) = self.spec else {
+            return Err(Failure::new(format!("unknown fork {}", self.fork)));
+        };
+        let test = &*self.test;
+        let post = &test.post[&self.fork][self.index];
+        let invalid_fixture = |err: String| Failure::new(format!("invalid fixture: {}", err));
+        let expected_root = parse_h256(&post.hash).map_err(invalid_fixture)?;
+        let expected_logs = parse_h256(&post.logs).map_err(invalid_fixture)?;
+        let chain_id = match test.config.chainid {
+            Some(ref chain_id) => parse_u64(chain_id).map_err(invalid_fixture)?,
+            None => 1,
+        };
+        let tx = test.transaction.tx_env(&post.indexes).map_err(invalid_fixture)?;
+
+        let mut block = BlockEnv::from(&test.env);
+        // Test blocks have no real ancestors. Their hashes are defined as the
+        // hash of the block number written in decimal.
+        for number in block.number.saturating_sub(256)..block.number {
+            block
+                .block_hashes
+                .insert(number, H256(keccak256(number.to_string().as_bytes())));
+        }
+        let mut evm = Evm::builder()
+            .with_state(test.pre.clone())
+            .with_cfg_env(CfgEnv {
+                chain_id,
+                spec,
+                ..Default::default()
+            })
+            .with_block_env(block)
+            .with_tx_env(tx)
+            .build();
+
+        let mut mismatches = Vec::new();
+        let mut context = String::new();
+        let result = match test.transaction.required_fork() {
+            fork if !spec.is_enabled(fork) => Err(format!("transaction type not supported before {}", fork)),
+            _ => evm.transact().map_err(|err| err.to_string()),
+        };
+        let logs = match (result, &post.expect_exception) {
+            (Ok(result), expected) => {
+                if let Some(exception) = expected {
+                    mismatches.push(format!(
+                        "transaction: expected rejection with {}, but it ran",
+                        exception
+                    ));
+                }
+                let exit = match result.exit_reason {
+                    ExitReason::Success => "success".to_string(),
+                    ExitReason::Revert => "revert".to_string(),
+                    ExitReason::Halt(halt) => halt.to_string(),
+                };
+                context = format!("Exit: {}\nGas used: {}", exit, result.gas_used);
+                result.logs
+            }
+            (Err(_), Some(_)) => Vec::new(),
+            (Err(err), None) => {
+                mismatches.push(format!("transaction: rejected with {}", err));
+                Vec::new()
+            }
+        };
+
+        let root = state_root(evm.state());
+        if root != expected_root {
+            mismatches.push(format!("state root: expected {:?}, got {:?}", expected_root, root));
+        }
+        let logs_hash = logs_hash(&logs);
+        if logs_hash != expected_logs {
+            mismatches.push(format!("logs hash: expected {:?}, got {:?}", expected_logs, logs_hash));
+        }
+        match mismatches.is_empty() {
+            true => Ok(()),
+            false => Err(Failure { mismatches, context }),
+        }
+    }
+}
+
+impl Transaction {
+    /// The variant of the transaction picked by `indexes`.
+    fn tx_env(&self, indexes: &Indexes) -> Result<TxEnv, String> {
+        let pick = |name: &str, len: usize, index: usize| match index < len {
+            true => Ok(index),
+            false => Err(format!("{} index {} out of range", name, index)),
+        };
+        let data = pick("data", self.data.len(), indexes.data)?;
+        let gas = pick("gas", self.gas_limit.len(), indexes.gas)?;
+        let value = pick("value", self.value.len(), indexes.value)?;
+
+        let caller = match (&self.sender, &self.secret_key) {
+            (Some(sender), _) => parse_address(sender)?,
+            (None, Some(key)) => secret_key_address(key)?,
+            (None, None) => return Err("transaction has neither sender nor secretKey".to_string()),
+        };
+        let kind = match self.to.as_str() {
+            "" => TxKind::Create,
+            to => TxKind::Call(parse_address(to)?),
+        };
+        let gas_price = match (&self.gas_price, &self.max_fee_per_gas) {
+            (Some(price), _) | (None, Some(price)) => parse_u256(price)?,
+            (None, None) => return Err("transaction has no gas price".to_string()),
+        };
+        let mut access_list = Vec::new();
+        let entries = self
+            .access_lists
+            .as_ref()
+            .and_then(|lists| lists.get(data))
+            .and_then(Option::as_ref);
+        for entry in entries.into_iter().flatten() {
+            access_list.push(AccessListItem {
+                address: parse_address(&entry.address)?,
+                storage_keys: entry
+                    .storage_keys
+                    .iter()
+                    .map(|key| parse_u256(key))
+                    .collect::<Result<_, _>>()?,
+            });
+        }
+
+        Ok(TxEnv {
+            caller,
+            kind,
+            value: parse_u256(&self.value[value])?,
+            data: self.data[data].clone(),
+            gas_limit: parse_u64(&self.gas_limit[gas])?,
+            gas_price,
+            gas_priority_fee: self.max_priority_fee_per_gas.as_deref().map(parse_u256).transpose()?,
+            nonce: Some(parse_u64(&self.nonce)?),
+            access_list,
+            blob_hashes: self
+                .blob_versioned_hashes
+                .iter()
+                .flatten()
+                .map(|hash| parse_h256(hash))
+                .collect::<Result<_, _>>()?,
+            max_fee_per_blob_gas: self.max_fee_per_blob_gas.as_deref().map(parse_u256).transpose()?,
+            origin: None,
+        })
+    }
+
+    /// The fork that introduced the transaction's type.
+    fn required_fork(&self) -> SpecId {
+        if self.blob_versioned_hashes.is_some() {
+            SpecId::Cancun
+        } else if self.max_fee_per_gas.is_some() {
+            SpecId::London
+        } else if self.access_lists.is_some() {
+            SpecId::Berlin
+        } else {
+            SpecId::Frontier
+        }
+    }
+}
+
+/// The address of the account a secp256k1 secret key controls.
+fn secret_key_address(key: &str) -> Result<Address, String> {
+    let key = key.parse::<Bytes>()?;
+    let key = k256::ecdsa::SigningKey::from_slice(&key).map_err(|err| format!("invalid secret key: {}", err))?;
+    let point = key.verifying_key().to_encoded_point(false);
+    let hash = keccak256(&point.as_bytes()[1..]);
+    Ok(Address::from_slice(&hash[12..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture() -> std::path::PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("testdata/state_test.json")
+    }
+
+    #[test]
+    fn passes_the_fixture_and_skips_unsupported_forks() {
+        let mut skipped = Vec::new();
+        for case in load(&fixture(), &[]).unwrap() {
+            match case.skip_reason() {
+                Some(reason) => skipped.push(format!("{} ({})", case.name(), reason)),
+                None => {
+                    if let Err(failure) = case.run() {
+                        panic!("{}: {:?}", case.name(), failure.mismatches);
+                    }
+                }
+            }
+        }
+        assert_eq!(
+            skipped,
+            [
+                "refundsAndPrecompiles/Frontier/d0g0v0 (Frontier gas rules are not implemented)",
+                "refundsAndPrecompiles/Frontier/d1g0v1 (Frontier gas rules are not implemented)",
+                "selfdestructRefund/Frontier/d0g0v0 (Frontier gas rules are not implemented)",
+            ]
+        );
+
+        let names: Vec<_> = load(&fixture(), &[SpecId::Berlin])
+            .unwrap()
+            .iter()
+            .map(|case| case.name().to_string())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "refundsAndPrecompiles/Berlin/d0g0v0",
+                "refundsAndPrecompiles/Berlin/d1g0v1",
+                "selfdestructRefund/Berlin/d0g0v0",
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_a_wrong_root_and_unknown_forks() {
+        let text = std::fs::read_to_string(fixture()).unwrap();
+        let mut tests: BTreeMap<String, StateTest> = serde_json::from_str(&text).unwrap();
+        let mut test = tests.remove("selfdestructRefund").unwrap();
+        let mut post = test.post.remove("London").unwrap();
+        post[0].hash = format!("{:?}", H256::zero());
+        test.post.insert("Prague".to_string(), Vec::new());
+        test.post.insert("London".to_string(), post);
+        let test = Rc::new(test);
+        let case = |fork: &str, spec| StateCase {
+            name: fork.to_string(),
+            test: test.clone(),
+            fork: fork.to_string(),
+            spec,
+            index: 0,
+        };
+
+        let failure = case("London", Some(SpecId::London)).run().unwrap_err();
+        assert_eq!(failure.mismatches.len(), 1);
+        assert!(failure.mismatches[0].starts_with("state root: expected 0x0000"));
+        assert!(failure.context.contains("Gas used: 53602"));
+        assert_eq!(
+            case("Prague", None).skip_reason().as_deref(),
+            Some("unknown fork Prague")
+        );
+    }
+}
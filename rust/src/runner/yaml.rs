@@ -0,0 +1,170 @@
+//! Loads `scripts/evm.yaml`, the source `evm.json` is generated from,
+//! applying the same rewrites as `scripts/index.js`:
+//!
+//! - `code` given as a list of lines is assembled, keeping the lines as `asm`.
+//! - BigInt-style literals such as `0x1122n`, `15000001n` or `0x60_01n`
+//!   become `0x` hex strings.
+//! - Tests marked `todo` are left out.
+//! - `hint` defaults to empty and `expect.success` to true.
+
+use super::fixture::Evmtest;
+use evm::assemble;
+use serde_json::{json, Map, Value};
+use serde_yaml::Value as Yaml;
+
+pub fn load(text: &str) -> Result<Vec<Evmtest>, String> {
+    let document: Yaml = serde_yaml::from_str(text).map_err(|err| err.to_string())?;
+    let Yaml::Mapping(tests) = document else {
+        return Err("expected a mapping of test names to tests".to_string());
+    };
+
+    let mut fixtures = Vec::new();
+    for (name, test) in &tests {
+        let name = name
+            .as_str()
+            .ok_or_else(|| format!("test name {:?} is not a string", name))?;
+        if test.get("todo").and_then(Yaml::as_bool) == Some(true) {
+            continue;
+        }
+        let mut test = convert(None, test).map_err(|err| format!("{}: {}", name, err))?;
+        let Some(fields) = test.as_object_mut() else {
+            return Err(format!("{}: expected a mapping", name));
+        };
+        fields.insert("name".into(), name.into());
+        fields.entry("hint").or_insert_with(|| "".into());
+        if let Some(expect) = fields.get_mut("expect").and_then(Value::as_object_mut) {
+            expect.entry("success").or_insert(true.into());
+        }
+        fixtures.push(serde_json::from_value(test).map_err(|err| format!("{}: {}", name, err))?);
+    }
+    Ok(fixtures)
+}
+
+/// Turns `value`, found under `key`, into its `evm.json` form.
+fn convert(key: Option<&str>, value: &Yaml) -> Result<Value, String> {
+    Ok(match value {
+        Yaml::Sequence(lines) if key == Some("code") => {
+            let lines: Vec<&str> = lines
+                .iter()
+                .map(|line| {
+                    line.as_str()
+                        .ok_or_else(|| format!("code line {:?} is not a string", line))
+                })
+                .collect::<Result<_, _>>()?;
+            let asm = lines.join("\n");
+            let bin = assemble(&asm).map_err(|err| format!("code {}", err))?;
+            json!({ "asm": asm, "bin": hex::encode(bin) })
+        }
+        Yaml::String(bin) if key == Some("code") => json!({ "asm": null, "bin": bin }),
+        Yaml::String(text) => big_int(text).unwrap_or_else(|| text.clone()).into(),
+        Yaml::Sequence(items) => items
+            .iter()
+            .map(|item| convert(None, item))
+            .collect::<Result<Vec<_>, _>>()?
+            .into(),
+        Yaml::Mapping(entries) => {
+            let mut object = Map::new();
+            for (entry_key, entry_value) in entries {
+                let entry_key = match entry_key {
+                    Yaml::String(text) => text.clone(),
+                    Yaml::Number(number) => number.to_string(),
+                    other => return Err(format!("unsupported key {:?}", other)),
+                };
+                let value = convert(Some(&entry_key), entry_value)?;
+                // Addresses in `state` are BigInt literals too.
+                let entry_key = match key {
+                    Some("state") => big_int(&entry_key).unwrap_or(entry_key),
+                    _ => entry_key,
+                };
+                object.insert(entry_key, value);
+            }
+            object.into()
+        }
+        Yaml::Number(number) => serde_json::to_value(number).map_err(|err| err.to_string())?,
+        Yaml::Bool(value) => (*value).into(),
+        Yaml::Null => Value::Null,
+        Yaml::Tagged(tagged) => return Err(format!("unsupported tag {}", tagged.tag)),
+    })
+}
+
+/// Rewrites a BigInt-style literal, hex or decimal with an `n` suffix and
+/// optional `_` separators, as a `0x` hex string.
+fn big_int(text: &str) -> Option<String> {
+    let digits = text.strip_suffix('n')?.replace('_', "");
+    let value = match digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")) {
+        Some(hex) => evm::parse_u256(&format!("0x{}", hex)).ok()?,
+        None if !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit()) => evm::parse_u256(&digits).ok()?,
+        None => return None,
+    };
+    Some(format!("{:#x}", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use evm::Address;
+    use primitive_types::U256;
+
+    #[test]
+    fn applies_the_index_js_rewrites() {
+        let text = r#"
+ADD:
+  code:
+    - PUSH1 0x01
+    - PUSH1 2
+    - ADD
+  tx:
+    value: 0x1_0n
+    to: 0x1000n
+  state:
+    0xaa_00n:
+      balance: 15000001n
+  expect:
+    stack: [3n]
+
+RAW:
+  hint: bytecode
+  code: '6001'
+  expect:
+    success: false
+
+LATER:
+  todo: true
+  code: [STOP]
+  expect: {}
+"#;
+        let tests = load(text).unwrap();
+        assert_eq!(tests.len(), 2);
+
+        let add = &tests[0];
+        assert_eq!((add.name.as_str(), add.hint.as_str()), ("ADD", ""));
+        assert_eq!(add.code.asm.as_deref(), Some("PUSH1 0x01\nPUSH1 2\nADD"));
+        assert_eq!(add.code.bin, "6001600201");
+        assert!(add.expect.success);
+        assert_eq!(add.expect.stack, Some(vec!["0x3".to_string()]));
+        let tx = add.tx.as_ref().unwrap();
+        assert_eq!(tx.value, U256::from(16));
+        assert_eq!(tx.to, Some(Address::from_low_u64_be(0x1000)));
+        let state = add.state.as_ref().unwrap();
+        let account = state.account(&Address::from_low_u64_be(0xaa00)).unwrap();
+        assert_eq!(account.balance, U256::from(15_000_001));
+
+        let raw = &tests[1];
+        assert_eq!((raw.code.asm.as_deref(), raw.code.bin.as_str()), (None, "6001"));
+        assert!(!raw.expect.success);
+    }
+
+    #[test]
+    fn agrees_with_evm_json() {
+        let manifest = std::path::Path::new(env!("CARGO_MANIFEST_DIR"));
+        let yaml = load(&std::fs::read_to_string(manifest.join("../scripts/evm.yaml")).unwrap()).unwrap();
+        let json: Vec<Evmtest> =
+            serde_json::from_str(&std::fs::read_to_string(manifest.join("../evm.json")).unwrap()).unwrap();
+        // evm.json may lag behind the YAML, but what it has must agree.
+        for from_json in &json {
+            let from_yaml = yaml.iter().find(|test| test.name == from_json.name).unwrap();
+            assert_eq!(from_yaml.code.bin, from_json.code.bin, "{}", from_json.name);
+            assert_eq!(from_yaml.expect.stack, from_json.expect.stack, "{}", from_json.name);
+        }
+    }
+}
@@ -0,0 +1,76 @@
+use serde::de::Error;
+use serde::{Deserialize, Deserializer};
+use std::fmt;
+use std::str::FromStr;
+
+/// Ethereum hardforks, in activation order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum SpecId {
+    Frontier,
+    Homestead,
+    TangerineWhistle,
+    SpuriousDragon,
+    Byzantium,
+    Constantinople,
+    Petersburg,
+    Istanbul,
+    Berlin,
+    London,
+    /// The Merge.
+    Paris,
+    Shanghai,
+    /// The latest fork the interpreter implements.
+    #[default]
+    Cancun,
+}
+
+impl SpecId {
+    /// Whether `fork` is active under this spec.
+    pub fn is_enabled(self, fork: SpecId) -> bool {
+        self >= fork
+    }
+
+    /// Whether the interpreter has this fork's gas schedule. Account and
+    /// storage access are always priced by EIP-2929, so only Berlin and
+    /// later are modelled.
+    pub fn is_supported(self) -> bool {
+        self.is_enabled(SpecId::Berlin)
+    }
+}
+
+impl FromStr for SpecId {
+    type Err = String;
+
+    /// Accepts the fork names used by geth and ethereum/tests, case-insensitively.
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        let spec = match name.to_ascii_lowercase().as_str() {
+            "frontier" => SpecId::Frontier,
+            "homestead" => SpecId::Homestead,
+            "tangerinewhistle" | "eip150" => SpecId::TangerineWhistle,
+            "spuriousdragon" | "eip158" => SpecId::SpuriousDragon,
+            "byzantium" => SpecId::Byzantium,
+            "constantinople" => SpecId::Constantinople,
+            "petersburg" | "constantinoplefix" => SpecId::Petersburg,
+            "istanbul" => SpecId::Istanbul,
+            "berlin" => SpecId::Berlin,
+            "london" => SpecId::London,
+            "paris" | "merge" => SpecId::Paris,
+            "shanghai" => SpecId::Shanghai,
+            "cancun" => SpecId::Cancun,
+            _ => return Err(format!("unknown fork {:?}", name)),
+        };
+        Ok(spec)
+    }
+}
+
+impl<'de> Deserialize<'de> for SpecId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?.parse().map_err(D::Error::custom)
+    }
+}
+
+impl fmt::Display for SpecId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
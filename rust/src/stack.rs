@@ -0,0 +1,64 @@
+use primitive_types::U256;
+
+/// The operand stack of a call frame, top at the end.
+///
+/// The interpreter checks each instruction's stack effect against
+/// `opcode::info` before running it, so the accessors below never see an
+/// underflow. They still return zero instead of panicking if one slips through.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Stack {
+    data: Vec<U256>,
+}
+
+impl Stack {
+    /// Maximum number of items on the stack.
+    pub const LIMIT: usize = 1024;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// The items on the stack, bottom first.
+    pub fn data(&self) -> &[U256] {
+        &self.data
+    }
+
+    pub fn into_vec(self) -> Vec<U256> {
+        self.data
+    }
+
+    pub fn push(&mut self, value: U256) {
+        self.data.push(value);
+    }
+
+    pub fn pop(&mut self) -> U256 {
+        self.data.pop().unwrap_or_default()
+    }
+
+    /// Pushes a copy of the item `depth` places from the top (DUP1 is depth 1).
+    pub fn dup(&mut self, depth: usize) {
+        let value = self.peek(depth - 1);
+        self.data.push(value);
+    }
+
+    /// Swaps the top with the item `depth` places below it (SWAP1 is depth 1).
+    pub fn swap(&mut self, depth: usize) {
+        let len = self.data.len();
+        if depth < len {
+            self.data.swap(len - 1, len - 1 - depth);
+        }
+    }
+
+    /// The item `depth` places from the top, where 0 is the top.
+    pub fn peek(&self, depth: usize) -> U256 {
+        self.data.iter().rev().nth(depth).copied().unwrap_or_default()
+    }
+}
@@ -0,0 +1,182 @@
+use crate::primitives::{parse_address, parse_h256, parse_u256, parse_u64, Bytes};
+use primitive_types::{H160, U256};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+
+/// A single account in the world state.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "AccountJson")]
+pub struct Account {
+    pub balance: U256,
+    pub nonce: u64,
+    pub code: Vec<u8>,
+    pub storage: HashMap<U256, U256>,
+}
+
+impl Account {
+    /// An account is empty when it has no code, zero nonce and zero balance (EIP-161).
+    pub fn is_empty(&self) -> bool {
+        self.code.is_empty() && self.nonce == 0 && self.balance.is_zero()
+    }
+}
+
+/// The world state: every account that exists, keyed by address.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "HashMap<String, Account>")]
+pub struct State {
+    accounts: HashMap<H160, Account>,
+}
+
+impl State {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn account(&self, address: &H160) -> Option<&Account> {
+        self.accounts.get(address)
+    }
+
+    pub fn account_mut(&mut self, address: &H160) -> &mut Account {
+        self.accounts.entry(*address).or_default()
+    }
+
+    pub fn insert(&mut self, address: H160, account: Account) {
+        self.accounts.insert(address, account);
+    }
+
+    /// All accounts, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (&H160, &Account)> {
+        self.accounts.iter()
+    }
+
+    pub fn remove(&mut self, address: &H160) -> Option<Account> {
+        self.accounts.remove(address)
+    }
+
+    /// Moves `value` wei from `from` to `to`. Returns false, leaving the state
+    /// untouched, when `from` cannot afford it or `to`'s balance would overflow.
+    #[must_use]
+    pub fn transfer(&mut self, from: &H160, to: &H160, value: U256) -> bool {
+        let balance = self.account(from).map(|account| account.balance).unwrap_or_default();
+        if balance < value {
+            return false;
+        }
+        if from == to {
+            self.account_mut(from);
+            return true;
+        }
+        let credited = self.account(to).map(|account| account.balance).unwrap_or_default();
+        let Some(credited) = credited.checked_add(value) else {
+            return false;
+        };
+        self.account_mut(from).balance = balance - value;
+        self.account_mut(to).balance = credited;
+        true
+    }
+
+    /// Code of the account at `address`, empty if the account does not exist.
+    pub fn code(&self, address: &H160) -> &[u8] {
+        self.accounts
+            .get(address)
+            .map(|account| account.code.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// Value of storage slot `key` at `address`, zero if never written.
+    pub fn storage(&self, address: &H160, key: &U256) -> U256 {
+        self.accounts
+            .get(address)
+            .and_then(|account| account.storage.get(key))
+            .copied()
+            .unwrap_or_default()
+    }
+}
+
+impl TryFrom<HashMap<String, Account>> for State {
+    type Error = String;
+
+    fn try_from(raw: HashMap<String, Account>) -> Result<Self, Self::Error> {
+        let mut accounts = HashMap::new();
+        for (address, account) in raw {
+            accounts.insert(parse_address(&address)?, account);
+        }
+        Ok(State { accounts })
+    }
+}
+
+#[derive(Deserialize)]
+struct AccountJson {
+    balance: Option<String>,
+    nonce: Option<String>,
+    code: Option<CodeJson>,
+    storage: Option<HashMap<String, String>>,
+}
+
+/// Code as `evm.json` gives it, or as plain hex like ethereum/tests.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum CodeJson {
+    Fixture { bin: String },
+    Hex(String),
+}
+
+impl TryFrom<AccountJson> for Account {
+    type Error = String;
+
+    fn try_from(raw: AccountJson) -> Result<Self, Self::Error> {
+        let mut account = Account::default();
+        if let Some(balance) = raw.balance {
+            account.balance = parse_u256(&balance)?;
+        }
+        if let Some(nonce) = raw.nonce {
+            account.nonce = parse_u64(&nonce)?;
+        }
+        if let Some(code) = raw.code {
+            let (CodeJson::Fixture { bin: hex } | CodeJson::Hex(hex)) = code;
+            account.code = hex.parse::<Bytes>()?.0;
+        }
+        // Storage words are hex, as in geth's allocs, even without the prefix.
+        let word = |value: &str| parse_h256(value).map(|word| U256::from_big_endian(word.as_bytes()));
+        for (key, value) in raw.storage.unwrap_or_default() {
+            account.storage.insert(word(&key)?, word(&value)?);
+        }
+        Ok(account)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn funded(balance: U256) -> Account {
+        Account {
+            balance,
+            ..Account::default()
+        }
+    }
+
+    #[test]
+    fn transfer_moves_value() {
+        let (from, to) = (H160::repeat_byte(1), H160::repeat_byte(2));
+        let mut state = State::new();
+        state.insert(from, funded(U256::from(10)));
+        assert!(state.transfer(&from, &to, U256::from(4)));
+        assert_eq!(state.account(&from).unwrap().balance, U256::from(6));
+        assert_eq!(state.account(&to).unwrap().balance, U256::from(4));
+        assert!(!state.transfer(&from, &to, U256::from(7)));
+        assert!(state.transfer(&from, &from, U256::from(6)));
+        assert_eq!(state.account(&from).unwrap().balance, U256::from(6));
+    }
+
+    #[test]
+    fn transfer_refuses_overflow() {
+        let (from, to) = (H160::repeat_byte(1), H160::repeat_byte(2));
+        let mut state = State::new();
+        state.insert(from, funded(U256::from(10)));
+        state.insert(to, funded(U256::MAX));
+        let before = state.clone();
+        assert!(!state.transfer(&from, &to, U256::one()));
+        assert_eq!(state, before);
+    }
+}
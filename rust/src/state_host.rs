@@ -0,0 +1,818 @@
+use crate::host::{
+    CallInputs, CallOutcome, CallScheme, CreateInputs, CreateOutcome, CreateScheme, Host, Log, SStoreResult,
+    SelfDestructResult,
+};
+use crate::interpreter::{execute, CallContext};
+use crate::{gas, precompile};
+use crate::{keccak256, u256_to_address, Account, Address, Bytes, Env, Halt, Inspector, SpecId, State, TxKind};
+use primitive_types::{H256, U256};
+use std::collections::{HashMap, HashSet};
+
+/// Upper bound on the depth of nested calls.
+const CALL_DEPTH_LIMIT: usize = 1024;
+
+/// A [`Host`] backed by an in-memory [`State`]. Sub-calls run against the
+/// same state and are rolled back when they fail.
+pub struct StateHost<'a> {
+    state: &'a mut State,
+    env: &'a Env,
+    spec: SpecId,
+    accessed_addresses: HashSet<Address>,
+    accessed_storage: HashSet<(Address, U256)>,
+    /// Storage values from before the transaction first wrote each slot.
+    original_storage: HashMap<(Address, U256), U256>,
+    transient_storage: HashMap<(Address, U256), U256>,
+    logs: Vec<Log>,
+    selfdestructs: HashSet<Address>,
+    /// Contracts created by this transaction (EIP-6780).
+    created: HashSet<Address>,
+    /// Accounts to delete at the end of the transaction if empty (EIP-161).
+    touched: HashSet<Address>,
+    /// Changes made so far, undone from the end when a call frame fails.
+    journal: Vec<JournalEntry>,
+    inspector: Option<&'a mut dyn Inspector>,
+}
+
+/// A change to the state or the host's bookkeeping, with what it takes to
+/// undo it.
+enum JournalEntry {
+    /// The account did not exist before.
+    AccountCreated(Address),
+    BalanceChanged {
+        address: Address,
+        previous: U256,
+    },
+    NonceChanged {
+        address: Address,
+        previous: u64,
+    },
+    CodeChanged {
+        address: Address,
+        previous: Vec<u8>,
+    },
+    StorageChanged {
+        address: Address,
+        key: U256,
+        previous: Option<U256>,
+    },
+    TransientStorageChanged {
+        address: Address,
+        key: U256,
+        previous: Option<U256>,
+    },
+    AddressWarmed(Address),
+    StorageWarmed(Address, U256),
+    SelfDestructed(Address),
+    Created(Address),
+    Touched(Address),
+}
+
+/// An account before the transaction, as far as the journal tells: the
+/// fields it changed, and whether the account existed.
+#[derive(Default)]
+struct Original {
+    existed: Option<bool>,
+    balance: Option<U256>,
+    nonce: Option<u64>,
+    code: Option<Vec<u8>>,
+    storage: HashMap<U256, Option<U256>>,
+}
+
+impl Original {
+    /// Whether `account`, the account now, differs from the original.
+    fn differs(&self, account: Option<&Account>) -> bool {
+        let Some(account) = account else {
+            return self.existed == Some(true);
+        };
+        self.existed == Some(false)
+            || self.balance.is_some_and(|balance| balance != account.balance)
+            || self.nonce.is_some_and(|nonce| nonce != account.nonce)
+            || self.code.as_ref().is_some_and(|code| *code != account.code)
+            || self
+                .storage
+                .iter()
+                .any(|(key, value)| account.storage.get(key) != value.as_ref())
+    }
+}
+
+/// Where a call frame started, to revert to if it fails.
+struct Checkpoint {
+    journal: usize,
+    logs: usize,
+}
+
+impl<'a> StateHost<'a> {
+    pub fn new(state: &'a mut State, env: &'a Env) -> Self {
+        // The sender, the recipient, the precompiles and the access list start
+        // out warm (EIP-2929, EIP-2930), and so does the coinbase (EIP-3651).
+        let mut accessed_addresses: HashSet<Address> = precompile::addresses(env.cfg.spec).collect();
+        accessed_addresses.insert(env.tx.caller);
+        if let TxKind::Call(to) = env.tx.kind {
+            accessed_addresses.insert(to);
+        }
+        if env.cfg.spec.is_enabled(SpecId::Shanghai) {
+            accessed_addresses.insert(env.block.coinbase);
+        }
+        let mut accessed_storage = HashSet::new();
+        for item in &env.tx.access_list {
+            accessed_addresses.insert(item.address);
+            for key in &item.storage_keys {
+                accessed_storage.insert((item.address, *key));
+            }
+        }
+        StateHost {
+            state,
+            env,
+            spec: env.cfg.spec,
+            accessed_addresses,
+            accessed_storage,
+            original_storage: HashMap::new(),
+            transient_storage: HashMap::new(),
+            logs: Vec::new(),
+            selfdestructs: HashSet::new(),
+            created: HashSet::new(),
+            touched: HashSet::new(),
+            journal: Vec::new(),
+            inspector: None,
+        }
+    }
+
+    /// Reports execution to `inspector`.
+    pub fn with_inspector(mut self, inspector: &'a mut dyn Inspector) -> Self {
+        self.inspector = Some(inspector);
+        self
+    }
+
+    pub fn state(&self) -> &State {
+        self.state
+    }
+
+    pub fn state_mut(&mut self) -> &mut State {
+        self.state
+    }
+
+    /// Logs emitted so far.
+    pub fn logs(&self) -> &[Log] {
+        &self.logs
+    }
+
+    /// Marks `address` as touched, so it is deleted at the end of the
+    /// transaction if it is empty (EIP-161).
+    pub fn touch(&mut self, address: Address) {
+        if self.touched.insert(address) {
+            self.journal.push(JournalEntry::Touched(address));
+        }
+    }
+
+    /// Ends the transaction: deletes the accounts that self-destructed and the
+    /// touched accounts left empty, and returns the logs.
+    pub fn finish(self) -> Vec<Log> {
+        self.finish_with_changes().0
+    }
+
+    /// Ends the transaction like [`finish`](Self::finish), and also returns
+    /// the new value of every account it changed, `None` for deleted ones.
+    /// Only the accounts in the journal are compared with what they were.
+    pub fn finish_with_changes(self) -> (Vec<Log>, HashMap<Address, Option<Account>>) {
+        let originals = self.originals();
+        for address in &self.selfdestructs {
+            self.state.remove(address);
+        }
+        if self.spec.is_enabled(SpecId::SpuriousDragon) {
+            for address in &self.touched {
+                if self.state.account(address).is_some_and(|account| account.is_empty()) {
+                    self.state.remove(address);
+                }
+            }
+        }
+        let changes = originals
+            .into_iter()
+            .filter(|(address, original)| original.differs(self.state.account(address)))
+            .map(|(address, _)| (address, self.state.account(&address).cloned()))
+            .collect();
+        (self.logs, changes)
+    }
+
+    /// What the journal says each account it mentions was like before the
+    /// transaction. The earliest entry for a field holds its original value.
+    fn originals(&self) -> HashMap<Address, Original> {
+        let mut originals: HashMap<Address, Original> = HashMap::new();
+        for entry in &self.journal {
+            let (address, existed) = match entry {
+                JournalEntry::AccountCreated(address) => (address, false),
+                JournalEntry::BalanceChanged { address, .. }
+                | JournalEntry::NonceChanged { address, .. }
+                | JournalEntry::CodeChanged { address, .. }
+                | JournalEntry::StorageChanged { address, .. } => (address, true),
+                JournalEntry::SelfDestructed(address)
+                | JournalEntry::Created(address)
+                | JournalEntry::Touched(address) => {
+                    originals.entry(*address).or_default();
+                    continue;
+                }
+                JournalEntry::TransientStorageChanged { .. }
+                | JournalEntry::AddressWarmed(_)
+                | JournalEntry::StorageWarmed(..) => continue,
+            };
+            let original = originals.entry(*address).or_default();
+            original.existed.get_or_insert(existed);
+            match entry {
+                JournalEntry::BalanceChanged { previous, .. } => {
+                    original.balance.get_or_insert(*previous);
+                }
+                JournalEntry::NonceChanged { previous, .. } => {
+                    original.nonce.get_or_insert(*previous);
+                }
+                JournalEntry::CodeChanged { previous, .. } => {
+                    original.code.get_or_insert_with(|| previous.clone());
+                }
+                JournalEntry::StorageChanged { key, previous, .. } => {
+                    original.storage.entry(*key).or_insert(*previous);
+                }
+                _ => {}
+            }
+        }
+        // Accounts only touched were left as they were, existing or not.
+        for (address, original) in &mut originals {
+            original.existed.get_or_insert(self.state.account(address).is_some());
+        }
+        originals
+    }
+
+    fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            journal: self.journal.len(),
+            logs: self.logs.len(),
+        }
+    }
+
+    fn revert(&mut self, checkpoint: Checkpoint) {
+        for entry in self.journal.split_off(checkpoint.journal).into_iter().rev() {
+            match entry {
+                JournalEntry::AccountCreated(address) => {
+                    self.state.remove(&address);
+                }
+                JournalEntry::BalanceChanged { address, previous } => {
+                    self.state.account_mut(&address).balance = previous;
+                }
+                JournalEntry::NonceChanged { address, previous } => {
+                    self.state.account_mut(&address).nonce = previous;
+                }
+                JournalEntry::CodeChanged { address, previous } => {
+                    self.state.account_mut(&address).code = previous;
+                }
+                JournalEntry::StorageChanged { address, key, previous } => {
+                    let storage = &mut self.state.account_mut(&address).storage;
+                    match previous {
+                        Some(value) => storage.insert(key, value),
+                        None => storage.remove(&key),
+                    };
+                }
+                JournalEntry::TransientStorageChanged { address, key, previous } => {
+                    match previous {
+                        Some(value) => self.transient_storage.insert((address, key), value),
+                        None => self.transient_storage.remove(&(address, key)),
+                    };
+                }
+                JournalEntry::AddressWarmed(address) => {
+                    self.accessed_addresses.remove(&address);
+                }
+                JournalEntry::StorageWarmed(address, key) => {
+                    self.accessed_storage.remove(&(address, key));
+                }
+                JournalEntry::SelfDestructed(address) => {
+                    self.selfdestructs.remove(&address);
+                }
+                JournalEntry::Created(address) => {
+                    self.created.remove(&address);
+                }
+                JournalEntry::Touched(address) => {
+                    self.touched.remove(&address);
+                }
+            }
+        }
+        self.logs.truncate(checkpoint.logs);
+    }
+
+    /// The account at `address`, created empty if it does not exist yet.
+    fn account_mut(&mut self, address: Address) -> &mut Account {
+        if self.state.account(&address).is_none() {
+            self.journal.push(JournalEntry::AccountCreated(address));
+        }
+        self.state.account_mut(&address)
+    }
+
+    pub(crate) fn set_balance(&mut self, address: Address, balance: U256) {
+        let previous = std::mem::replace(&mut self.account_mut(address).balance, balance);
+        self.journal.push(JournalEntry::BalanceChanged { address, previous });
+    }
+
+    pub(crate) fn set_nonce(&mut self, address: Address, nonce: u64) {
+        let previous = std::mem::replace(&mut self.account_mut(address).nonce, nonce);
+        self.journal.push(JournalEntry::NonceChanged { address, previous });
+    }
+
+    fn set_code(&mut self, address: Address, code: Vec<u8>) {
+        let previous = std::mem::replace(&mut self.account_mut(address).code, code);
+        self.journal.push(JournalEntry::CodeChanged { address, previous });
+    }
+
+    /// Moves `value` wei as [`State::transfer`] does, journaling the balances
+    /// it changes. Returns false, journaling nothing, when it refuses.
+    #[must_use]
+    fn transfer(&mut self, from: Address, to: Address, value: U256) -> bool {
+        let entries = [from, to].map(|address| match self.state.account(&address) {
+            Some(account) => JournalEntry::BalanceChanged {
+                address,
+                previous: account.balance,
+            },
+            None => JournalEntry::AccountCreated(address),
+        });
+        if !self.state.transfer(&from, &to, value) {
+            return false;
+        }
+        self.journal.extend(entries);
+        true
+    }
+
+    /// Runs init code for a new contract at `address` and deploys what it
+    /// returns.
+    fn deploy(&mut self, inputs: &CreateInputs, address: Address) -> CallOutcome {
+        self.account_mut(address);
+        if self.spec.is_enabled(SpecId::SpuriousDragon) {
+            self.set_nonce(address, 1);
+        }
+        if self.created.insert(address) {
+            self.journal.push(JournalEntry::Created(address));
+        }
+        self.touch(address);
+        if !self.transfer(inputs.caller, address, inputs.value) {
+            return CallOutcome::refused(inputs.gas_limit, Halt::OverflowPayment);
+        }
+
+        let context = CallContext {
+            code: &inputs.init_code,
+            input: &[],
+            address,
+            caller: inputs.caller,
+            value: inputs.value,
+            gas: inputs.gas_limit,
+            is_static: false,
+            depth: inputs.depth,
+        };
+        let result = execute(&context, self);
+        if !result.success {
+            return CallOutcome {
+                output: result.output.into(),
+                gas_left: result.gas_left,
+                halt: result.halt,
+                ..Default::default()
+            };
+        }
+
+        let code = result.output;
+        let code_size_limit = self.env.cfg.limit_contract_code_size.unwrap_or(gas::MAX_CODE_SIZE);
+        let halt = if self.spec.is_enabled(SpecId::SpuriousDragon) && code.len() > code_size_limit {
+            Some(Halt::CreateContractSizeLimit)
+        } else if self.spec.is_enabled(SpecId::London) && code.first() == Some(&0xef) {
+            Some(Halt::CreateContractStartingWithEF)
+        } else if result.gas_left < gas::CODE_DEPOSIT * code.len() as u64 {
+            Some(Halt::OutOfGas)
+        } else {
+            None
+        };
+        if let Some(halt) = halt {
+            return CallOutcome {
+                halt: Some(halt),
+                ..Default::default()
+            };
+        }
+
+        let gas_left = result.gas_left - gas::CODE_DEPOSIT * code.len() as u64;
+        self.set_code(address, code.clone());
+        CallOutcome {
+            success: true,
+            output: code.into(),
+            gas_left,
+            refund: result.refund,
+            halt: None,
+        }
+    }
+
+    /// Runs a message call, reverting its changes if it fails.
+    fn run_call(&mut self, inputs: &CallInputs) -> CallOutcome {
+        let moves_value = matches!(inputs.scheme, CallScheme::Call | CallScheme::CallCode);
+        if inputs.depth > CALL_DEPTH_LIMIT {
+            return CallOutcome::refused(inputs.gas_limit, Halt::CallTooDeep);
+        }
+        if moves_value && self.balance(inputs.caller) < inputs.value {
+            return CallOutcome::refused(inputs.gas_limit, Halt::OutOfFunds);
+        }
+
+        let code = self.state.code(&inputs.code_address).to_vec();
+        let checkpoint = self.checkpoint();
+        if inputs.scheme == CallScheme::Call {
+            self.touch(inputs.address);
+            if !self.transfer(inputs.caller, inputs.address, inputs.value) {
+                self.revert(checkpoint);
+                return CallOutcome::refused(inputs.gas_limit, Halt::OverflowPayment);
+            }
+        }
+        let context = CallContext {
+            code: &code,
+            input: &inputs.input,
+            address: inputs.address,
+            caller: inputs.caller,
+            value: inputs.value,
+            gas: inputs.gas_limit,
+            is_static: inputs.is_static,
+            depth: inputs.depth,
+        };
+        let result = match precompile::run(self.spec, &inputs.code_address, &inputs.input, inputs.gas_limit) {
+            Some(result) => result,
+            None => execute(&context, self),
+        };
+        if !result.success {
+            self.revert(checkpoint);
+        }
+        CallOutcome {
+            success: result.success,
+            output: result.output.into(),
+            gas_left: result.gas_left,
+            refund: result.refund,
+            halt: result.halt,
+        }
+    }
+
+    /// Creates a contract, reverting its changes if it fails.
+    fn run_create(&mut self, inputs: &CreateInputs) -> CreateOutcome {
+        let nonce = self
+            .state
+            .account(&inputs.caller)
+            .map(|account| account.nonce)
+            .unwrap_or_default();
+        let refusal = if inputs.depth > CALL_DEPTH_LIMIT {
+            Some(Halt::CallTooDeep)
+        } else if self.balance(inputs.caller) < inputs.value {
+            Some(Halt::OutOfFunds)
+        } else if nonce == u64::MAX {
+            Some(Halt::NonceOverflow)
+        } else {
+            None
+        };
+        if let Some(reason) = refusal {
+            return CreateOutcome {
+                address: None,
+                result: CallOutcome::refused(inputs.gas_limit, reason),
+            };
+        }
+        self.set_nonce(inputs.caller, nonce + 1);
+
+        let address = match inputs.scheme {
+            CreateScheme::Create => create_address(&inputs.caller, nonce),
+            CreateScheme::Create2 { salt } => create2_address(&inputs.caller, salt, &inputs.init_code),
+        };
+        self.access_account(address);
+        if self
+            .state
+            .account(&address)
+            .is_some_and(|account| !account.code.is_empty() || account.nonce != 0)
+        {
+            return CreateOutcome {
+                address: None,
+                result: CallOutcome {
+                    halt: Some(Halt::CreateCollision),
+                    ..Default::default()
+                },
+            };
+        }
+
+        let checkpoint = self.checkpoint();
+        let result = self.deploy(inputs, address);
+        if !result.success {
+            self.revert(checkpoint);
+        }
+        CreateOutcome {
+            address: result.success.then_some(address),
+            result,
+        }
+    }
+}
+
+impl Host for StateHost<'_> {
+    fn spec(&self) -> SpecId {
+        self.spec
+    }
+
+    fn metered(&self) -> bool {
+        !self.env.cfg.disable_gas
+    }
+
+    fn origin(&self) -> Address {
+        self.env.tx.origin.unwrap_or(self.env.tx.caller)
+    }
+
+    fn gas_price(&self) -> U256 {
+        self.env.tx.effective_gas_price(self.env.block.basefee)
+    }
+
+    fn blob_hash(&self, index: usize) -> Option<H256> {
+        self.env.tx.blob_hashes.get(index).copied()
+    }
+
+    fn coinbase(&self) -> Address {
+        self.env.block.coinbase
+    }
+
+    fn timestamp(&self) -> U256 {
+        U256::from(self.env.block.timestamp)
+    }
+
+    fn number(&self) -> U256 {
+        U256::from(self.env.block.number)
+    }
+
+    fn difficulty(&self) -> U256 {
+        self.env.block.difficulty
+    }
+
+    fn prevrandao(&self) -> Option<H256> {
+        self.env.block.prevrandao
+    }
+
+    fn gas_limit(&self) -> U256 {
+        U256::from(self.env.block.gas_limit)
+    }
+
+    fn chain_id(&self) -> U256 {
+        U256::from(self.env.cfg.chain_id)
+    }
+
+    fn base_fee(&self) -> U256 {
+        self.env.block.basefee
+    }
+
+    fn blob_base_fee(&self) -> U256 {
+        gas::blob_base_fee(U256::from(self.env.block.excess_blob_gas.unwrap_or_default()))
+    }
+
+    fn block_hash(&mut self, number: u64) -> H256 {
+        self.env.block.block_hashes.get(&number).copied().unwrap_or_default()
+    }
+
+    fn access_account(&mut self, address: Address) -> bool {
+        let cold = self.accessed_addresses.insert(address);
+        if cold {
+            self.journal.push(JournalEntry::AddressWarmed(address));
+        }
+        cold
+    }
+
+    fn access_storage(&mut self, address: Address, key: U256) -> bool {
+        let cold = self.accessed_storage.insert((address, key));
+        if cold {
+            self.journal.push(JournalEntry::StorageWarmed(address, key));
+        }
+        cold
+    }
+
+    fn is_empty(&mut self, address: Address) -> bool {
+        self.state.account(&address).is_none_or(|account| account.is_empty())
+    }
+
+    fn balance(&mut self, address: Address) -> U256 {
+        self.state
+            .account(&address)
+            .map(|account| account.balance)
+            .unwrap_or_default()
+    }
+
+    fn code(&mut self, address: Address) -> Bytes {
+        self.state.code(&address).into()
+    }
+
+    fn code_hash(&mut self, address: Address) -> H256 {
+        match self.state.account(&address) {
+            Some(account) if !account.is_empty() => H256(keccak256(&account.code)),
+            _ => H256::zero(),
+        }
+    }
+
+    fn sload(&mut self, address: Address, key: U256) -> U256 {
+        self.state.storage(&address, &key)
+    }
+
+    fn sstore(&mut self, address: Address, key: U256, value: U256) -> SStoreResult {
+        let current = self.state.storage(&address, &key);
+        let original = *self.original_storage.entry((address, key)).or_insert(current);
+        let previous = self.account_mut(address).storage.insert(key, value);
+        self.journal
+            .push(JournalEntry::StorageChanged { address, key, previous });
+        SStoreResult {
+            original,
+            current,
+            new: value,
+        }
+    }
+
+    fn tload(&mut self, address: Address, key: U256) -> U256 {
+        self.transient_storage.get(&(address, key)).copied().unwrap_or_default()
+    }
+
+    fn tstore(&mut self, address: Address, key: U256, value: U256) {
+        let previous = self.transient_storage.insert((address, key), value);
+        self.journal
+            .push(JournalEntry::TransientStorageChanged { address, key, previous });
+    }
+
+    fn log(&mut self, log: Log) {
+        self.logs.push(log);
+    }
+
+    fn selfdestruct(&mut self, address: Address, target: Address) -> SelfDestructResult {
+        let balance = self.balance(address);
+        let result = SelfDestructResult {
+            had_value: !balance.is_zero(),
+            target_exists: !self.is_empty(target),
+            previously_destroyed: self.selfdestructs.contains(&address),
+        };
+
+        // From Cancun only contracts created in the same transaction are
+        // deleted. Others just hand over their balance (EIP-6780).
+        let deleted = !self.spec.is_enabled(SpecId::Cancun) || self.created.contains(&address);
+        self.touch(target);
+        if target != address {
+            // Only a target holding more than the total supply could refuse
+            // the balance, which then stays with the contract.
+            let _ = self.transfer(address, target, balance);
+        } else if deleted {
+            self.set_balance(address, U256::zero());
+        }
+        if deleted && self.selfdestructs.insert(address) {
+            self.journal.push(JournalEntry::SelfDestructed(address));
+        }
+        result
+    }
+
+    fn call(&mut self, mut inputs: CallInputs) -> CallOutcome {
+        let outcome = match self
+            .inspector
+            .as_deref_mut()
+            .and_then(|inspector| inspector.call(&mut inputs))
+        {
+            Some(outcome) => outcome,
+            None => self.run_call(&inputs),
+        };
+        match self.inspector.as_deref_mut() {
+            Some(inspector) => inspector.call_end(&inputs, outcome),
+            None => outcome,
+        }
+    }
+
+    fn create(&mut self, mut inputs: CreateInputs) -> CreateOutcome {
+        let outcome = match self
+            .inspector
+            .as_deref_mut()
+            .and_then(|inspector| inspector.create(&mut inputs))
+        {
+            Some(outcome) => outcome,
+            None => self.run_create(&inputs),
+        };
+        match self.inspector.as_deref_mut() {
+            Some(inspector) => inspector.create_end(&inputs, outcome),
+            None => outcome,
+        }
+    }
+
+    fn inspector(&mut self) -> Option<&mut dyn Inspector> {
+        // Rebuilt by hand so the trait object takes the shorter lifetime.
+        match &mut self.inspector {
+            Some(inspector) => Some(&mut **inspector),
+            None => None,
+        }
+    }
+}
+
+/// Address of a contract created by `sender` with CREATE: the low 20 bytes of
+/// `keccak256(rlp([sender, nonce]))`.
+pub fn create_address(sender: &Address, nonce: u64) -> Address {
+    let nonce_bytes = nonce.to_be_bytes();
+    let nonce_bytes = &nonce_bytes[nonce.leading_zeros() as usize / 8..];
+    let mut rlp = Vec::with_capacity(32);
+    let nonce_len = match nonce_bytes {
+        [byte] if *byte < 0x80 => 1,
+        _ => 1 + nonce_bytes.len(),
+    };
+    rlp.push(0xc0 + 21 + nonce_len as u8);
+    rlp.push(0x80 + 20);
+    rlp.extend_from_slice(sender.as_bytes());
+    match nonce_bytes {
+        [byte] if *byte < 0x80 => rlp.push(*byte),
+        _ => {
+            rlp.push(0x80 + nonce_bytes.len() as u8);
+            rlp.extend_from_slice(nonce_bytes);
+        }
+    }
+    u256_to_address(U256::from_big_endian(&keccak256(&rlp)))
+}
+
+/// Address of a contract created by `sender` with CREATE2 (EIP-1014).
+pub fn create2_address(sender: &Address, salt: U256, init_code: &[u8]) -> Address {
+    let mut preimage = Vec::with_capacity(85);
+    preimage.push(0xff);
+    preimage.extend_from_slice(sender.as_bytes());
+    let mut salt_bytes = [0u8; 32];
+    salt.to_big_endian(&mut salt_bytes);
+    preimage.extend_from_slice(&salt_bytes);
+    preimage.extend_from_slice(&keccak256(init_code));
+    u256_to_address(U256::from_big_endian(&keccak256(&preimage)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn revert_undoes_everything_since_the_checkpoint() {
+        let (sender, contract, fresh) = (
+            Address::repeat_byte(1),
+            Address::repeat_byte(2),
+            Address::repeat_byte(3),
+        );
+        let mut state = State::new();
+        state.account_mut(&sender).balance = U256::from(10);
+        state.account_mut(&contract).storage.insert(U256::one(), U256::from(7));
+        let before = state.clone();
+        let mut env = Env::default();
+        // Before Cancun, so SELFDESTRUCT marks the contract for deletion.
+        env.cfg.spec = SpecId::Shanghai;
+        let mut host = StateHost::new(&mut state, &env);
+        host.sstore(contract, U256::one(), U256::from(8));
+
+        let checkpoint = host.checkpoint();
+        assert!(host.transfer(sender, fresh, U256::from(4)));
+        host.set_nonce(sender, 5);
+        host.sstore(contract, U256::one(), U256::from(9));
+        host.sstore(contract, U256::from(2), U256::one());
+        host.tstore(contract, U256::one(), U256::one());
+        assert!(host.access_account(fresh));
+        assert!(host.access_storage(contract, U256::one()));
+        host.selfdestruct(contract, sender);
+        host.revert(checkpoint);
+
+        let mut expected = before;
+        expected
+            .account_mut(&contract)
+            .storage
+            .insert(U256::one(), U256::from(8));
+        assert_eq!(*host.state(), expected);
+        assert!(host.tload(contract, U256::one()).is_zero());
+        assert!(host.access_account(fresh));
+        assert!(host.access_storage(contract, U256::one()));
+        assert!(!host.selfdestruct(contract, sender).previously_destroyed);
+    }
+
+    #[test]
+    fn refuses_value_that_would_overflow_the_recipient() {
+        let caller = Address::repeat_byte(1);
+        let (callee, created) = (Address::repeat_byte(2), create_address(&caller, 0));
+        let mut state = State::new();
+        state.account_mut(&caller).balance = U256::from(10);
+        for address in [callee, created] {
+            state.account_mut(&address).balance = U256::MAX;
+        }
+        // SSTORE 1 at slot 0, which must not run.
+        state.account_mut(&callee).code = vec![0x60, 0x01, 0x60, 0x00, 0x55];
+        let env = Env::default();
+        let mut host = StateHost::new(&mut state, &env);
+
+        let outcome = host.call(CallInputs {
+            scheme: CallScheme::Call,
+            code_address: callee,
+            address: callee,
+            caller,
+            value: U256::one(),
+            input: Bytes::default(),
+            gas_limit: 50_000,
+            is_static: false,
+            depth: 0,
+        });
+        assert_eq!(outcome, CallOutcome::refused(50_000, Halt::OverflowPayment));
+        let outcome = host.create(CreateInputs {
+            scheme: CreateScheme::Create,
+            caller,
+            value: U256::one(),
+            init_code: Bytes::default(),
+            gas_limit: 50_000,
+            depth: 0,
+        });
+        assert_eq!(outcome.address, None);
+        assert_eq!(outcome.result, CallOutcome::refused(50_000, Halt::OverflowPayment));
+
+        let state = host.state();
+        assert_eq!(state.account(&caller).unwrap().balance, U256::from(10));
+        assert!(state.storage(&callee, &U256::zero()).is_zero());
+        let created = state.account(&created).unwrap();
+        assert_eq!((created.balance, created.nonce), (U256::MAX, 0));
+    }
+}
@@ -0,0 +1,836 @@
+//! The state transition tool (`t8n`) that execution-spec-tests fills tests
+//! with, following the interface of geth's `evm t8n`: it applies a list of
+//! transactions to a prestate in a block environment, and writes the
+//! poststate, the block's roots and receipts, and the encoded transactions.
+//!
+//! Any input may be `stdin`, which then holds one JSON object with `alloc`,
+//! `env` and `txs` (or `txsRlp`). Any output may be `stdout` or `stderr`,
+//! which then get one JSON object with `result`, `alloc` and `body`.
+
+use clap::Args;
+use evm::{
+    apply_beacon_root, apply_rewards, apply_withdrawals, calc_base_fee, calc_difficulty, calc_excess_blob_gas,
+    logs_bloom, logs_hash, ordered_trie_root, parse_address, parse_h256, parse_u256, parse_u64, state_root,
+    AccessListItem, Address, BlockEnv, Bytes, CfgEnv, Evm, Header, Receipt, Signature, SpecId, State, Transaction,
+    TxKind, TxType, Withdrawal, EMPTY_OMMERS_HASH, MAX_BLOB_GAS_PER_BLOCK,
+};
+use primitive_types::{H256, U256};
+use rlp::{Rlp, RlpStream};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+#[derive(Args)]
+pub struct T8nArgs {
+    /// The prestate.
+    #[arg(long = "input.alloc", value_name = "FILE", default_value = "alloc.json")]
+    alloc: String,
+    /// The block environment.
+    #[arg(long = "input.env", value_name = "FILE", default_value = "env.json")]
+    env: String,
+    /// The transactions, as JSON or, for files ending in .rlp, as the hex
+    /// string of their RLP list.
+    #[arg(long = "input.txs", value_name = "FILE", default_value = "txs.json")]
+    txs: String,
+    /// The directory the outputs are written to. Defaults to the current one.
+    #[arg(long = "output.basedir", value_name = "DIR")]
+    basedir: Option<PathBuf>,
+    #[arg(long = "output.result", value_name = "FILE", default_value = "result.json")]
+    result: String,
+    #[arg(long = "output.alloc", value_name = "FILE", default_value = "alloc.json")]
+    output_alloc: String,
+    /// Where to write the RLP of the transactions. Not written by default.
+    #[arg(long = "output.body", value_name = "FILE")]
+    body: Option<String>,
+    /// A fork, or a transition such as `BerlinToLondonAt5` or
+    /// `ShanghaiToCancunAtTime15k`.
+    #[arg(long = "state.fork", value_name = "FORK", default_value = "Cancun")]
+    fork: Fork,
+    #[arg(long = "state.chainid", value_name = "ID", default_value_t = 1)]
+    chain_id: u64,
+    /// The block reward in wei. Negative for none.
+    #[arg(
+        long = "state.reward",
+        value_name = "WEI",
+        default_value_t = 0,
+        allow_negative_numbers = true
+    )]
+    reward: i128,
+}
+
+/// The fork of the block, which a transition picks by block number or
+/// timestamp.
+#[derive(Debug, Clone, Copy)]
+pub enum Fork {
+    Fixed(SpecId),
+    AtBlock { from: SpecId, to: SpecId, number: u64 },
+    AtTime { from: SpecId, to: SpecId, timestamp: u64 },
+}
+
+impl Fork {
+    fn spec(self, number: u64, timestamp: u64) -> SpecId {
+        match self {
+            Fork::Fixed(spec) => spec,
+            Fork::AtBlock { from, to, number: at } => {
+                if number >= at {
+                    to
+                } else {
+                    from
+                }
+            }
+            Fork::AtTime {
+                from,
+                to,
+                timestamp: at,
+            } => {
+                if timestamp >= at {
+                    to
+                } else {
+                    from
+                }
+            }
+        }
+    }
+}
+
+impl FromStr for Fork {
+    type Err = String;
+
+    /// Accepts forks from Berlin on, the first whose gas rules the
+    /// interpreter implements.
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        let supported = |name: &str| match name.parse::<SpecId>()? {
+            spec if spec.is_supported() => Ok(spec),
+            spec => Err(format!("{} gas rules are not implemented", spec)),
+        };
+        let Some((from, rest)) = name.split_once("To") else {
+            return Ok(Fork::Fixed(supported(name)?));
+        };
+        let (to, at) = rest
+            .split_once("At")
+            .ok_or_else(|| format!("unknown fork {:?}", name))?;
+        let (from, to) = (supported(from)?, supported(to)?);
+        let (at, by_time) = match at.strip_prefix("Time") {
+            Some(at) => (at, true),
+            None => (at, false),
+        };
+        let parsed = match at.strip_suffix('k') {
+            Some(thousands) => thousands.parse::<u64>().map(|at| at * 1000),
+            None => at.parse(),
+        };
+        let at = parsed.map_err(|_| format!("unknown fork {:?}", name))?;
+        Ok(match by_time {
+            true => Fork::AtTime {
+                from,
+                to,
+                timestamp: at,
+            },
+            false => Fork::AtBlock { from, to, number: at },
+        })
+    }
+}
+
+/// The inputs when they all come from stdin.
+#[derive(Default, Deserialize)]
+struct Input {
+    alloc: Option<State>,
+    env: Option<Env>,
+    txs: Option<Vec<TxJson>>,
+    #[serde(rename = "txsRlp")]
+    txs_rlp: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Env {
+    current_coinbase: String,
+    current_gas_limit: String,
+    current_number: String,
+    current_timestamp: String,
+    current_difficulty: Option<String>,
+    /// PREVRANDAO, from Paris on.
+    current_random: Option<String>,
+    current_base_fee: Option<String>,
+    current_excess_blob_gas: Option<String>,
+    parent_difficulty: Option<String>,
+    parent_timestamp: Option<String>,
+    parent_base_fee: Option<String>,
+    parent_gas_used: Option<String>,
+    parent_gas_limit: Option<String>,
+    parent_uncle_hash: Option<String>,
+    parent_excess_blob_gas: Option<String>,
+    parent_blob_gas_used: Option<String>,
+    parent_beacon_block_root: Option<String>,
+    /// Keyed by block number in decimal.
+    #[serde(default)]
+    block_hashes: BTreeMap<String, String>,
+    #[serde(default)]
+    ommers: Vec<Ommer>,
+    withdrawals: Option<Vec<WithdrawalJson>>,
+}
+
+#[derive(Deserialize)]
+struct Ommer {
+    /// How many blocks older than the current block the ommer is.
+    delta: u64,
+    address: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct WithdrawalJson {
+    index: String,
+    validator_index: String,
+    address: String,
+    amount: String,
+}
+
+/// A transaction, signed or with the key to sign it with.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TxJson {
+    #[serde(rename = "type")]
+    tx_type: Option<String>,
+    chain_id: Option<String>,
+    nonce: String,
+    gas_price: Option<String>,
+    max_fee_per_gas: Option<String>,
+    max_priority_fee_per_gas: Option<String>,
+    #[serde(alias = "gasLimit")]
+    gas: String,
+    /// Missing, null or empty for a creation.
+    to: Option<String>,
+    value: String,
+    #[serde(alias = "data")]
+    input: Bytes,
+    access_list: Option<Vec<AccessListJson>>,
+    max_fee_per_blob_gas: Option<String>,
+    blob_versioned_hashes: Option<Vec<String>>,
+    v: Option<String>,
+    r: Option<String>,
+    s: Option<String>,
+    secret_key: Option<String>,
+    /// Whether a legacy transaction signed here gets replay protection.
+    protected: Option<bool>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AccessListJson {
+    address: String,
+    storage_keys: Vec<String>,
+}
+
+/// Runs the transition and writes its outputs.
+pub fn run(args: T8nArgs) -> Result<(), String> {
+    let mut stdin = Input::default();
+    if [&args.alloc, &args.env, &args.txs]
+        .iter()
+        .any(|input| *input == "stdin")
+    {
+        let mut text = String::new();
+        std::io::stdin()
+            .read_to_string(&mut text)
+            .map_err(|err| format!("cannot read stdin: {}", err))?;
+        stdin = serde_json::from_str(&text).map_err(|err| format!("cannot parse stdin: {}", err))?;
+    }
+    let mut state: State = match args.alloc.as_str() {
+        "stdin" => stdin.alloc.take().ok_or("no alloc on stdin")?,
+        path => read_json(path)?,
+    };
+    let env: Env = match args.env.as_str() {
+        "stdin" => stdin.env.take().ok_or("no env on stdin")?,
+        path => read_json(path)?,
+    };
+    let txs = match args.txs.as_str() {
+        "stdin" => match (stdin.txs.take(), stdin.txs_rlp.take()) {
+            (Some(txs), _) => sign_all(txs, args.chain_id)?,
+            (None, Some(rlp)) => decode_all(&rlp)?,
+            (None, None) => Vec::new(),
+        },
+        path if path.ends_with(".rlp") => decode_all(&read_json::<String>(path)?)?,
+        path => sign_all(read_json(path)?, args.chain_id)?,
+    };
+
+    let result = apply(&mut state, &env, &txs, &args)?;
+
+    let mut body = RlpStream::new_list(txs.len());
+    for tx in &txs {
+        tx.rlp_append(&mut body);
+    }
+    let outputs = [
+        ("result", Some(&args.result), result),
+        ("alloc", Some(&args.output_alloc), alloc_json(&state)),
+        (
+            "body",
+            args.body.as_ref(),
+            json!(format!("0x{}", hex::encode(body.out()))),
+        ),
+    ];
+    let mut stdout = Map::new();
+    let mut stderr = Map::new();
+    for (name, target, value) in outputs {
+        match target.map(String::as_str) {
+            None => {}
+            Some("stdout") => drop(stdout.insert(name.to_string(), value)),
+            Some("stderr") => drop(stderr.insert(name.to_string(), value)),
+            Some(file) => {
+                let mut path = PathBuf::from(file);
+                if let Some(ref basedir) = args.basedir {
+                    std::fs::create_dir_all(basedir)
+                        .map_err(|err| format!("cannot create {}: {}", basedir.display(), err))?;
+                    path = basedir.join(file);
+                }
+                let text = serde_json::to_string_pretty(&value).expect("JSON values serialize");
+                std::fs::write(&path, text).map_err(|err| format!("cannot write {}: {}", path.display(), err))?;
+            }
+        }
+    }
+    if !stdout.is_empty() {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&stdout).expect("JSON values serialize")
+        );
+    }
+    if !stderr.is_empty() {
+        eprintln!(
+            "{}",
+            serde_json::to_string_pretty(&stderr).expect("JSON values serialize")
+        );
+    }
+    Ok(())
+}
+
+/// Applies `txs` to `state`, skipping invalid ones, then the rewards and
+/// withdrawals, and describes the outcome like geth's `result.json`.
+fn apply(state: &mut State, env: &Env, txs: &[Transaction], args: &T8nArgs) -> Result<Value, String> {
+    let number = parse_u64(&env.current_number)?;
+    let timestamp = parse_u64(&env.current_timestamp)?;
+    let spec = args.fork.spec(number, timestamp);
+    let parent = env.parent_header(number)?;
+    let cfg = CfgEnv {
+        chain_id: args.chain_id,
+        spec,
+        ..Default::default()
+    };
+
+    let difficulty = match (&env.current_difficulty, env.parent_difficulty.is_some()) {
+        _ if spec.is_enabled(SpecId::Paris) => None,
+        (Some(difficulty), _) => Some(parse_u256(difficulty)?),
+        (None, true) => Some(calc_difficulty(&parent, timestamp, spec)),
+        (None, false) => return Err("currentDifficulty or parentDifficulty is required before Paris".to_string()),
+    };
+    let prevrandao = match env.current_random {
+        _ if !spec.is_enabled(SpecId::Paris) => None,
+        Some(ref random) => Some(parse_h256(random)?),
+        None => return Err("currentRandom is required from Paris on".to_string()),
+    };
+    let base_fee = match (&env.current_base_fee, parent.base_fee) {
+        _ if !spec.is_enabled(SpecId::London) => None,
+        (Some(base_fee), _) => Some(parse_u256(base_fee)?),
+        (None, Some(_)) => Some(calc_base_fee(&parent)),
+        (None, None) => return Err("currentBaseFee or parentBaseFee is required from London on".to_string()),
+    };
+    let excess_blob_gas = match env.current_excess_blob_gas {
+        _ if !spec.is_enabled(SpecId::Cancun) => None,
+        Some(ref excess) => Some(parse_u64(excess)?),
+        None => Some(calc_excess_blob_gas(&parent)),
+    };
+    let withdrawals = match env.withdrawals {
+        _ if !spec.is_enabled(SpecId::Shanghai) => None,
+        Some(ref withdrawals) => Some(
+            withdrawals
+                .iter()
+                .map(WithdrawalJson::parse)
+                .collect::<Result<Vec<_>, _>>()?,
+        ),
+        None => return Err("withdrawals are required from Shanghai on".to_string()),
+    };
+    let mut block_hashes = BTreeMap::new();
+    for (number, hash) in &env.block_hashes {
+        let number = number
+            .parse()
+            .map_err(|_| format!("invalid block number {:?}", number))?;
+        block_hashes.insert(number, parse_h256(hash)?);
+    }
+    let coinbase = parse_address(&env.current_coinbase)?;
+    let gas_limit = parse_u64(&env.current_gas_limit)?;
+    let block = BlockEnv {
+        number,
+        coinbase,
+        timestamp,
+        gas_limit,
+        basefee: base_fee.unwrap_or_default(),
+        difficulty: difficulty.unwrap_or_default(),
+        prevrandao,
+        excess_blob_gas,
+        block_hashes,
+    };
+
+    if spec.is_enabled(SpecId::Cancun) {
+        let root = env
+            .parent_beacon_block_root
+            .as_deref()
+            .ok_or("parentBeaconBlockRoot is required from Cancun on")?;
+        apply_beacon_root(state, &cfg, &block, parse_h256(root)?);
+    }
+
+    let mut evm = Evm::builder()
+        .with_state(std::mem::take(state))
+        .with_cfg_env(cfg)
+        .with_block_env(block)
+        .build();
+    let mut included = Vec::new();
+    let mut receipts = Vec::new();
+    let mut receipts_json = Vec::new();
+    let mut rejected = Vec::new();
+    let mut all_logs = Vec::new();
+    let mut gas_used = 0u64;
+    let mut blob_gas_used = 0u64;
+    for (index, tx) in txs.iter().enumerate() {
+        let checked = tx.validate(spec, args.chain_id).and_then(|()| {
+            if tx.gas_limit > gas_limit - gas_used {
+                return Err(format!(
+                    "gas limit reached: tx {}, left in block {}",
+                    tx.gas_limit,
+                    gas_limit - gas_used
+                ));
+            }
+            if blob_gas_used + tx.blob_gas() > MAX_BLOB_GAS_PER_BLOCK {
+                return Err(format!(
+                    "blob gas limit reached: tx {}, used {}",
+                    tx.blob_gas(),
+                    blob_gas_used
+                ));
+            }
+            tx.recover_sender()
+        });
+        let sender = match checked {
+            Ok(sender) => sender,
+            Err(err) => {
+                rejected.push(json!({ "index": index, "error": err }));
+                continue;
+            }
+        };
+        evm.env_mut().tx = tx.tx_env(sender);
+        let result = match evm.transact() {
+            Ok(result) => result,
+            Err(err) => {
+                rejected.push(json!({ "index": index, "error": err.to_string() }));
+                continue;
+            }
+        };
+        gas_used += result.gas_used;
+        blob_gas_used += tx.blob_gas();
+        let receipt = Receipt {
+            tx_type: tx.tx_type,
+            success: result.is_success(),
+            state_root: (!spec.is_enabled(SpecId::Byzantium)).then(|| state_root(evm.state())),
+            cumulative_gas_used: gas_used,
+            logs: result.logs,
+        };
+        let logs: Vec<Value> = receipt
+            .logs
+            .iter()
+            .map(|log| {
+                all_logs.push(log.clone());
+                json!({
+                    "address": format!("{:?}", log.address),
+                    "topics": log.topics.iter().map(|topic| format!("{:?}", topic)).collect::<Vec<_>>(),
+                    "data": format!("{:?}", log.data),
+                    "blockNumber": format!("{:#x}", number),
+                    "transactionHash": format!("{:?}", tx.hash()),
+                    "transactionIndex": format!("{:#x}", included.len()),
+                    "blockHash": format!("{:?}", H256::zero()),
+                    "logIndex": format!("{:#x}", all_logs.len() - 1),
+                    "removed": false,
+                })
+            })
+            .collect();
+        receipts_json.push(json!({
+            "type": format!("{:#x}", tx.tx_type as u8),
+            "root": receipt.state_root.map_or("0x".to_string(), |root| format!("{:?}", root)),
+            "status": format!("{:#x}", receipt.success as u8),
+            "cumulativeGasUsed": format!("{:#x}", gas_used),
+            "logsBloom": format!("0x{}", hex::encode(receipt.bloom())),
+            "logs": logs,
+            "transactionHash": format!("{:?}", tx.hash()),
+            "contractAddress": format!("{:?}", result.created_address.unwrap_or_default()),
+            "gasUsed": format!("{:#x}", result.gas_used),
+            "effectiveGasPrice": format!("{:#x}", evm.env().tx.effective_gas_price(evm.env().block.basefee)),
+            "blockHash": format!("{:?}", H256::zero()),
+            "transactionIndex": format!("{:#x}", included.len()),
+        }));
+        receipts.push(receipt);
+        included.push(tx);
+    }
+    *state = evm.into_state();
+
+    if let Ok(reward) = u128::try_from(args.reward) {
+        let mut ommers = Vec::new();
+        for ommer in &env.ommers {
+            ommers.push((ommer.delta, parse_address(&ommer.address)?));
+        }
+        apply_rewards(state, U256::from(reward), &coinbase, &ommers);
+        // Touching empty accounts deletes them (EIP-161).
+        if spec.is_enabled(SpecId::SpuriousDragon) {
+            for address in std::iter::once(coinbase).chain(ommers.iter().map(|(_, address)| *address)) {
+                if state.account(&address).is_some_and(|account| account.is_empty()) {
+                    state.remove(&address);
+                }
+            }
+        }
+    }
+    if let Some(ref withdrawals) = withdrawals {
+        apply_withdrawals(state, withdrawals);
+    }
+
+    let mut result = json!({
+        "stateRoot": format!("{:?}", state_root(state)),
+        "txRoot": format!("{:?}", ordered_trie_root(included.iter().map(|tx| tx.encode()))),
+        "receiptsRoot": format!("{:?}", ordered_trie_root(receipts.iter().map(Receipt::encode))),
+        "logsHash": format!("{:?}", logs_hash(&all_logs)),
+        "logsBloom": format!("0x{}", hex::encode(logs_bloom(&all_logs))),
+        "receipts": receipts_json,
+        "currentDifficulty": difficulty.map(|difficulty| format!("{:#x}", difficulty)),
+        "gasUsed": format!("{:#x}", gas_used),
+    });
+    let fields = result.as_object_mut().expect("result is an object");
+    if !rejected.is_empty() {
+        fields.insert("rejected".to_string(), json!(rejected));
+    }
+    if let Some(base_fee) = base_fee {
+        fields.insert("currentBaseFee".to_string(), json!(format!("{:#x}", base_fee)));
+    }
+    if let Some(ref withdrawals) = withdrawals {
+        let root = ordered_trie_root(withdrawals.iter().map(rlp::encode));
+        fields.insert("withdrawalsRoot".to_string(), json!(format!("{:?}", root)));
+    }
+    if let Some(excess_blob_gas) = excess_blob_gas {
+        fields.insert(
+            "currentExcessBlobGas".to_string(),
+            json!(format!("{:#x}", excess_blob_gas)),
+        );
+        fields.insert("blobGasUsed".to_string(), json!(format!("{:#x}", blob_gas_used)));
+    }
+    Ok(result)
+}
+
+impl Env {
+    /// The parent block, as far as the environment describes it.
+    fn parent_header(&self, number: u64) -> Result<Header, String> {
+        let u64_or_zero = |value: &Option<String>| {
+            value
+                .as_deref()
+                .map(parse_u64)
+                .transpose()
+                .map(Option::unwrap_or_default)
+        };
+        Ok(Header {
+            parent_hash: H256::zero(),
+            ommers_hash: match self.parent_uncle_hash {
+                Some(ref hash) => parse_h256(hash)?,
+                None => EMPTY_OMMERS_HASH,
+            },
+            coinbase: Address::zero(),
+            state_root: H256::zero(),
+            transactions_root: H256::zero(),
+            receipts_root: H256::zero(),
+            logs_bloom: [0; 256],
+            difficulty: self
+                .parent_difficulty
+                .as_deref()
+                .map(parse_u256)
+                .transpose()?
+                .unwrap_or_default(),
+            number: number.saturating_sub(1),
+            gas_limit: u64_or_zero(&self.parent_gas_limit)?,
+            gas_used: u64_or_zero(&self.parent_gas_used)?,
+            timestamp: u64_or_zero(&self.parent_timestamp)?,
+            extra_data: Bytes::default(),
+            mix_hash: H256::zero(),
+            nonce: [0; 8],
+            base_fee: self.parent_base_fee.as_deref().map(parse_u256).transpose()?,
+            withdrawals_root: None,
+            blob_gas_used: self.parent_blob_gas_used.as_deref().map(parse_u64).transpose()?,
+            excess_blob_gas: self.parent_excess_blob_gas.as_deref().map(parse_u64).transpose()?,
+            parent_beacon_block_root: None,
+        })
+    }
+}
+
+impl WithdrawalJson {
+    fn parse(&self) -> Result<Withdrawal, String> {
+        Ok(Withdrawal {
+            index: parse_u64(&self.index)?,
+            validator_index: parse_u64(&self.validator_index)?,
+            address: parse_address(&self.address)?,
+            amount: parse_u64(&self.amount)?,
+        })
+    }
+}
+
+impl TxJson {
+    /// The transaction, signed with `secretKey` when it has one.
+    fn into_transaction(self, chain_id: u64) -> Result<Transaction, String> {
+        let tx_type = match self.tx_type.as_deref().map(parse_u64).transpose()? {
+            Some(0) => TxType::Legacy,
+            Some(1) => TxType::AccessList,
+            Some(2) => TxType::DynamicFee,
+            Some(3) => TxType::Blob,
+            Some(other) => return Err(format!("unsupported transaction type {}", other)),
+            None if self.blob_versioned_hashes.is_some() => TxType::Blob,
+            None if self.max_fee_per_gas.is_some() => TxType::DynamicFee,
+            None if self.access_list.is_some() => TxType::AccessList,
+            None => TxType::Legacy,
+        };
+        let v = self.v.as_deref().map(parse_u64).transpose()?;
+        let chain_id = match (tx_type, &self.secret_key) {
+            (TxType::Legacy, Some(_)) => self.protected.unwrap_or(true).then_some(chain_id),
+            (TxType::Legacy, None) => v.filter(|v| *v >= 35).map(|v| (v - 35) / 2),
+            _ => Some(self.chain_id.as_deref().map(parse_u64).transpose()?.unwrap_or(chain_id)),
+        };
+        let gas_price = match (&self.gas_price, &self.max_fee_per_gas) {
+            (Some(price), _) | (None, Some(price)) => parse_u256(price)?,
+            (None, None) => return Err("transaction has no gas price".to_string()),
+        };
+        let mut access_list = Vec::new();
+        for entry in self.access_list.iter().flatten() {
+            access_list.push(AccessListItem {
+                address: parse_address(&entry.address)?,
+                storage_keys: entry
+                    .storage_keys
+                    .iter()
+                    .map(|key| parse_u256(key))
+                    .collect::<Result<_, _>>()?,
+            });
+        }
+        let mut tx = Transaction {
+            tx_type,
+            chain_id,
+            nonce: parse_u64(&self.nonce)?,
+            gas_price,
+            max_priority_fee_per_gas: self.max_priority_fee_per_gas.as_deref().map(parse_u256).transpose()?,
+            gas_limit: parse_u64(&self.gas)?,
+            kind: match self.to.as_deref() {
+                None | Some("") => TxKind::Create,
+                Some(to) => TxKind::Call(parse_address(to)?),
+            },
+            value: parse_u256(&self.value)?,
+            data: self.input,
+            access_list,
+            max_fee_per_blob_gas: self.max_fee_per_blob_gas.as_deref().map(parse_u256).transpose()?,
+            blob_hashes: self
+                .blob_versioned_hashes
+                .iter()
+                .flatten()
+                .map(|hash| parse_h256(hash))
+                .collect::<Result<_, _>>()?,
+            signature: Signature::default(),
+        };
+        match (self.secret_key, v, self.r, self.s) {
+            (Some(key), ..) => tx.sign(&key.parse::<Bytes>()?)?,
+            (None, Some(v), Some(r), Some(s)) => {
+                tx.signature = Signature {
+                    v,
+                    r: parse_u256(&r)?,
+                    s: parse_u256(&s)?,
+                }
+            }
+            _ => return Err("transaction has neither secretKey nor signature".to_string()),
+        }
+        Ok(tx)
+    }
+}
+
+fn sign_all(txs: Vec<TxJson>, chain_id: u64) -> Result<Vec<Transaction>, String> {
+    let signed = txs.into_iter().enumerate().map(|(index, tx)| {
+        tx.into_transaction(chain_id)
+            .map_err(|err| format!("transaction {}: {}", index, err))
+    });
+    signed.collect()
+}
+
+/// Decodes the hex string of an RLP list of transactions.
+fn decode_all(hex: &str) -> Result<Vec<Transaction>, String> {
+    let bytes = hex.parse::<Bytes>()?;
+    let rlp = Rlp::new(&bytes);
+    if !rlp.is_list() {
+        return Err("transactions RLP is not a list".to_string());
+    }
+    rlp.iter().map(|tx| Transaction::decode_rlp(&tx)).collect()
+}
+
+fn read_json<T: DeserializeOwned>(path: &str) -> Result<T, String> {
+    let text = std::fs::read_to_string(path).map_err(|err| format!("cannot read {}: {}", path, err))?;
+    serde_json::from_str(&text).map_err(|err| format!("cannot parse {}: {}", path, err))
+}
+
+/// The state in the format of `alloc.json`, leaving out empty fields.
+fn alloc_json(state: &State) -> Value {
+    let accounts: BTreeMap<_, _> = state.iter().collect();
+    let mut alloc = Map::new();
+    for (address, account) in accounts {
+        let mut fields = Map::new();
+        if !account.code.is_empty() {
+            fields.insert("code".to_string(), json!(format!("0x{}", hex::encode(&account.code))));
+        }
+        let storage: BTreeMap<_, _> = account.storage.iter().filter(|(_, value)| !value.is_zero()).collect();
+        if !storage.is_empty() {
+            let slot = |value: &U256| {
+                let mut bytes = [0u8; 32];
+                value.to_big_endian(&mut bytes);
+                format!("{:?}", H256(bytes))
+            };
+            let storage: Map<_, _> = storage
+                .into_iter()
+                .map(|(key, value)| (slot(key), json!(slot(value))))
+                .collect();
+            fields.insert("storage".to_string(), Value::Object(storage));
+        }
+        fields.insert("balance".to_string(), json!(format!("{:#x}", account.balance)));
+        if account.nonce != 0 {
+            fields.insert("nonce".to_string(), json!(format!("{:#x}", account.nonce)));
+        }
+        alloc.insert(format!("{:?}", address), Value::Object(fields));
+    }
+    Value::Object(alloc)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use std::path::Path;
+
+    #[derive(Parser)]
+    struct Cli {
+        #[command(flatten)]
+        t8n: T8nArgs,
+    }
+
+    /// The `refundsAndPrecompiles` state test, whose post-state roots were
+    /// computed with another implementation.
+    fn fixture() -> Value {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("testdata/state_test.json");
+        let text = std::fs::read_to_string(path).unwrap();
+        serde_json::from_str::<Value>(&text).unwrap()["refundsAndPrecompiles"].clone()
+    }
+
+    /// Runs the transition in a fresh directory and returns `result.json`
+    /// and `alloc.json`.
+    fn transition(name: &str, fork: &str, env: Value, txs: Value) -> (Value, Value) {
+        let dir = std::env::temp_dir().join(format!("t8n-{}-{}", std::process::id(), name));
+        std::fs::create_dir_all(&dir).unwrap();
+        let input = |file: &str, value: &Value| {
+            let path = dir.join(file);
+            std::fs::write(&path, value.to_string()).unwrap();
+            path.to_str().unwrap().to_string()
+        };
+        let (alloc, env, txs) = (
+            input("in-alloc.json", &fixture()["pre"]),
+            input("env.json", &env),
+            input("txs.json", &txs),
+        );
+        let basedir = dir.to_str().unwrap();
+        let cli = Cli::parse_from([
+            "t8n",
+            "--input.alloc",
+            &alloc,
+            "--input.env",
+            &env,
+            "--input.txs",
+            &txs,
+            "--output.basedir",
+            basedir,
+            "--state.fork",
+            fork,
+        ]);
+        run(cli.t8n).unwrap();
+        let output = |file: &str| serde_json::from_str(&std::fs::read_to_string(dir.join(file)).unwrap()).unwrap();
+        let outputs = (output("result.json"), output("alloc.json"));
+        std::fs::remove_dir_all(&dir).unwrap();
+        outputs
+    }
+
+    /// The fixture's transaction with data and value `d1v1`.
+    fn tx(nonce: &str) -> Value {
+        let tx = &fixture()["transaction"];
+        json!({
+            "nonce": nonce,
+            "gasPrice": tx["gasPrice"],
+            "gas": tx["gasLimit"][0],
+            "to": tx["to"],
+            "value": tx["value"][1],
+            "input": tx["data"][1],
+            "secretKey": tx["secretKey"],
+        })
+    }
+
+    fn post(fork: &str) -> Value {
+        fixture()["post"][fork][1].clone()
+    }
+
+    #[test]
+    fn parses_forks_and_transitions() {
+        assert!(matches!("London".parse(), Ok(Fork::Fixed(SpecId::London))));
+        let Ok(Fork::AtBlock { from, to, number }) = "BerlinToLondonAt5".parse() else {
+            panic!("not a transition by block number");
+        };
+        assert_eq!((from, to, number), (SpecId::Berlin, SpecId::London, 5));
+        let Ok(Fork::AtTime { from, to, timestamp }) = "ShanghaiToCancunAtTime15k".parse() else {
+            panic!("not a transition by timestamp");
+        };
+        assert_eq!((from, to, timestamp), (SpecId::Shanghai, SpecId::Cancun, 15_000));
+    }
+
+    #[test]
+    fn rejects_forks_before_berlin() {
+        let error = |name: &str| Fork::from_str(name).unwrap_err();
+        assert_eq!(error("Frontier"), "Frontier gas rules are not implemented");
+        assert_eq!(error("IstanbulToBerlinAt5"), "Istanbul gas rules are not implemented");
+        assert!(Cli::try_parse_from(["t8n", "--state.fork", "Homestead"]).is_err());
+    }
+
+    #[test]
+    fn matches_the_state_test_roots() {
+        let env = fixture()["env"].clone();
+        let (result, alloc) = transition("roots", "London", env, json!([tx("0x00")]));
+        let expected = post("London");
+        assert_eq!(result["stateRoot"], expected["hash"]);
+        assert_eq!(result["logsHash"], expected["logs"]);
+        assert!(result.get("rejected").is_none(), "{}", result);
+        assert_eq!(result["receipts"].as_array().unwrap().len(), 1);
+        assert_eq!(result["receipts"][0]["status"], "0x1");
+
+        let alloc: State = serde_json::from_value(alloc).unwrap();
+        assert_eq!(format!("{:?}", state_root(&alloc)), expected["hash"].as_str().unwrap());
+        let sender = parse_address("0xa94f5374fce5edbc8e2a8697c15331677e6ebf0b").unwrap();
+        assert_eq!(alloc.account(&sender).unwrap().nonce, 1);
+    }
+
+    #[test]
+    fn picks_the_fork_by_block_and_rejects_invalid_transactions() {
+        // Block 1 is still Berlin, whose refunds differ from London's.
+        let env = fixture()["env"].clone();
+        let txs = json!([tx("0x00"), tx("0x05")]);
+        let (result, _) = transition("transition", "BerlinToLondonAt5", env, txs);
+        let expected = post("Berlin");
+        assert_eq!(result["stateRoot"], expected["hash"]);
+        assert_eq!(result["logsHash"], expected["logs"]);
+        assert_eq!(result["receipts"].as_array().unwrap().len(), 1);
+        assert_eq!(result["gasUsed"], result["receipts"][0]["cumulativeGasUsed"]);
+        let rejected = result["rejected"].as_array().unwrap();
+        assert_eq!(rejected.len(), 1);
+        assert_eq!(rejected[0]["index"], 1);
+        assert!(
+            rejected[0]["error"].as_str().unwrap().contains("nonce"),
+            "{}",
+            rejected[0]
+        );
+    }
+}
@@ -0,0 +1,358 @@
+use crate::host::{CallInputs, CallOutcome, CallScheme, CreateInputs, CreateOutcome, CreateScheme, Log};
+use crate::{Address, Bytes, ExecutionResult, FrameState, Inspector, TxEnv};
+use primitive_types::U256;
+use serde_json::{json, Map, Value};
+
+/// Options of geth's `callTracer`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CallTracerConfig {
+    /// Leaves out every call below the transaction's own.
+    pub only_top_call: bool,
+    /// Adds the logs each call emitted. Logs of failed calls are dropped.
+    pub with_log: bool,
+}
+
+struct CallFrame {
+    kind: &'static str,
+    from: Address,
+    to: Option<Address>,
+    value: Option<U256>,
+    gas: u64,
+    gas_used: u64,
+    input: Bytes,
+    output: Bytes,
+    error: Option<String>,
+    revert_reason: Option<String>,
+    /// Logs with the number of sub-calls made before each.
+    logs: Vec<(Log, usize)>,
+    calls: Vec<CallFrame>,
+}
+
+impl CallFrame {
+    fn end(&mut self, outcome: &CallOutcome) {
+        self.gas_used = self.gas.saturating_sub(outcome.gas_left);
+        match outcome.halt {
+            _ if outcome.success => self.output = outcome.output.clone(),
+            Some(halt) => self.fail(halt.to_string()),
+            None => {
+                self.fail("execution reverted".to_string());
+                self.output = outcome.output.clone();
+                self.revert_reason = revert_reason(&outcome.output);
+            }
+        }
+    }
+
+    /// Marks the call failed, dropping the logs of it and its sub-calls.
+    fn fail(&mut self, error: String) {
+        self.error = Some(error);
+        self.clear_logs();
+    }
+
+    fn clear_logs(&mut self) {
+        self.logs.clear();
+        for call in &mut self.calls {
+            call.clear_logs();
+        }
+    }
+
+    fn into_json(self) -> Value {
+        let mut frame = Map::new();
+        frame.insert("type".into(), self.kind.into());
+        frame.insert("from".into(), format!("{:?}", self.from).into());
+        if let Some(to) = self.to {
+            frame.insert("to".into(), format!("{:?}", to).into());
+        }
+        if let Some(value) = self.value {
+            frame.insert("value".into(), format!("{:#x}", value).into());
+        }
+        frame.insert("gas".into(), format!("{:#x}", self.gas).into());
+        frame.insert("gasUsed".into(), format!("{:#x}", self.gas_used).into());
+        frame.insert("input".into(), format!("{:?}", self.input).into());
+        if !self.output.is_empty() {
+            frame.insert("output".into(), format!("{:?}", self.output).into());
+        }
+        if let Some(error) = self.error {
+            frame.insert("error".into(), error.into());
+        }
+        if let Some(reason) = self.revert_reason {
+            frame.insert("revertReason".into(), reason.into());
+        }
+        if !self.logs.is_empty() {
+            let logs: Vec<Value> = self
+                .logs
+                .into_iter()
+                .map(|(log, position)| {
+                    json!({
+                        "address": format!("{:?}", log.address),
+                        "topics": log.topics.iter().map(|topic| format!("{:?}", topic)).collect::<Vec<_>>(),
+                        "data": format!("{:?}", log.data),
+                        "position": format!("{:#x}", position),
+                    })
+                })
+                .collect();
+            frame.insert("logs".into(), logs.into());
+        }
+        if !self.calls.is_empty() {
+            let calls: Vec<Value> = self.calls.into_iter().map(CallFrame::into_json).collect();
+            frame.insert("calls".into(), calls.into());
+        }
+        frame.into()
+    }
+}
+
+/// Records the call tree that geth's `callTracer` returns.
+#[derive(Default)]
+pub struct CallTracer {
+    config: CallTracerConfig,
+    /// Calls in progress, outermost first.
+    stack: Vec<CallFrame>,
+    root: Option<CallFrame>,
+}
+
+impl CallTracer {
+    pub fn new(config: CallTracerConfig) -> Self {
+        CallTracer {
+            config,
+            ..Default::default()
+        }
+    }
+
+    /// The call tree of `tx`, which ended with `result`. The top call reports
+    /// the transaction's gas, including intrinsic gas and refunds.
+    pub fn into_json(self, tx: &TxEnv, result: &ExecutionResult) -> Value {
+        match self.root {
+            Some(mut root) => {
+                root.gas = tx.gas_limit;
+                root.gas_used = result.gas_used;
+                root.into_json()
+            }
+            None => Value::Null,
+        }
+    }
+
+    fn traces(&self, depth: usize) -> bool {
+        depth == 0 || !self.config.only_top_call
+    }
+
+    fn enter(&mut self, frame: CallFrame) {
+        self.stack.push(frame);
+    }
+
+    /// Closes the innermost call. A creation passes the new contract's
+    /// address, which is only known once it succeeded.
+    fn exit(&mut self, outcome: &CallOutcome, created: Option<Address>) {
+        let Some(mut frame) = self.stack.pop() else {
+            return;
+        };
+        frame.end(outcome);
+        if created.is_some() {
+            frame.to = created;
+        }
+        match self.stack.last_mut() {
+            Some(parent) => parent.calls.push(frame),
+            None => self.root = Some(frame),
+        }
+    }
+}
+
+impl Inspector for CallTracer {
+    fn call(&mut self, inputs: &mut CallInputs) -> Option<CallOutcome> {
+        if !self.traces(inputs.depth) {
+            return None;
+        }
+        let (kind, from, value) = match inputs.scheme {
+            CallScheme::Call => ("CALL", inputs.caller, Some(inputs.value)),
+            CallScheme::CallCode => ("CALLCODE", inputs.caller, Some(inputs.value)),
+            // A delegate call keeps its caller's caller, so the account making
+            // it is the one it runs as.
+            CallScheme::DelegateCall => ("DELEGATECALL", inputs.address, Some(inputs.value)),
+            CallScheme::StaticCall => ("STATICCALL", inputs.caller, None),
+        };
+        self.enter(CallFrame {
+            kind,
+            from,
+            to: Some(inputs.code_address),
+            value,
+            gas: inputs.gas_limit,
+            gas_used: 0,
+            input: inputs.input.clone(),
+            output: Bytes::new(),
+            error: None,
+            revert_reason: None,
+            logs: Vec::new(),
+            calls: Vec::new(),
+        });
+        None
+    }
+
+    fn call_end(&mut self, inputs: &CallInputs, outcome: CallOutcome) -> CallOutcome {
+        if self.traces(inputs.depth) {
+            self.exit(&outcome, None);
+        }
+        outcome
+    }
+
+    fn create(&mut self, inputs: &mut CreateInputs) -> Option<CreateOutcome> {
+        if !self.traces(inputs.depth) {
+            return None;
+        }
+        let kind = match inputs.scheme {
+            CreateScheme::Create => "CREATE",
+            CreateScheme::Create2 { .. } => "CREATE2",
+        };
+        self.enter(CallFrame {
+            kind,
+            from: inputs.caller,
+            to: None,
+            value: Some(inputs.value),
+            gas: inputs.gas_limit,
+            gas_used: 0,
+            input: inputs.init_code.clone(),
+            output: Bytes::new(),
+            error: None,
+            revert_reason: None,
+            logs: Vec::new(),
+            calls: Vec::new(),
+        });
+        None
+    }
+
+    fn create_end(&mut self, inputs: &CreateInputs, outcome: CreateOutcome) -> CreateOutcome {
+        if self.traces(inputs.depth) {
+            self.exit(&outcome.result, outcome.address);
+        }
+        outcome
+    }
+
+    fn log(&mut self, _frame: &FrameState, log: &Log) {
+        if !self.config.with_log {
+            return;
+        }
+        if let Some(frame) = self.stack.last_mut() {
+            let position = frame.calls.len();
+            frame.logs.push((log.clone(), position));
+        }
+    }
+}
+
+/// Decodes the message of a Solidity `Error(string)` or `Panic(uint256)`
+/// revert, as geth does.
+fn revert_reason(output: &[u8]) -> Option<String> {
+    let (selector, data) = (output.get(..4)?, &output[4..]);
+    match selector {
+        [0x08, 0xc3, 0x79, 0xa0] => {
+            let offset = abi_usize(data.get(..32)?)?;
+            let length = abi_usize(data.get(offset..offset.checked_add(32)?)?)?;
+            let start = offset + 32;
+            let message = data.get(start..start.checked_add(length)?)?;
+            String::from_utf8(message.to_vec()).ok()
+        }
+        [0x4e, 0x48, 0x7b, 0x71] => {
+            let code = U256::from_big_endian(data.get(..32)?);
+            let reason = match code.low_u64() {
+                _ if code > U256::from(u64::MAX) => None,
+                0x00 => Some("generic panic"),
+                0x01 => Some("assert(false)"),
+                0x11 => Some("arithmetic underflow or overflow"),
+                0x12 => Some("division or modulo by zero"),
+                0x21 => Some("enum overflow"),
+                0x22 => Some("invalid encoded storage byte array accessed"),
+                0x31 => Some("out-of-bounds array access; popping on an empty array"),
+                0x32 => Some("out-of-bounds access of an array or bytesN"),
+                0x41 => Some("out of memory"),
+                0x51 => Some("uninitialized function"),
+                _ => None,
+            };
+            Some(match reason {
+                Some(reason) => reason.to_string(),
+                None => format!("unknown panic code: {:#x}", code),
+            })
+        }
+        _ => None,
+    }
+}
+
+/// Reads a 32-byte ABI word that must fit in a `usize`.
+fn abi_usize(word: &[u8]) -> Option<usize> {
+    let value = U256::from_big_endian(word);
+    (value <= U256::from(u32::MAX)).then(|| value.as_usize())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tracer::nested_calls;
+
+    fn trace(config: CallTracerConfig) -> Value {
+        let mut evm = nested_calls();
+        let mut tracer = CallTracer::new(config);
+        let result = evm.inspect(&mut tracer).unwrap();
+        tracer.into_json(&evm.env().tx, &result)
+    }
+
+    #[test]
+    fn nested_call_create_and_revert() {
+        let address = |low: u64| format!("{:?}", Address::from_low_u64_be(low));
+        let revert_data = concat!(
+            "0x08c379a0",
+            "0000000000000000000000000000000000000000000000000000000000000020",
+            "0000000000000000000000000000000000000000000000000000000000000004",
+            "6e6f706500000000000000000000000000000000000000000000000000000000",
+        );
+        // Successful frames have no `error` or `revertReason`, and leaves no `calls`.
+        let expected = json!({
+            "type": "CALL",
+            "from": address(0x10),
+            "to": address(0x20),
+            "value": "0x0",
+            "gas": "0x30d40",
+            "gasUsed": "0xe4c1",
+            "input": "0x",
+            "calls": [
+                {
+                    "type": "CALL",
+                    "from": address(0x20),
+                    "to": address(0x30),
+                    "value": "0x0",
+                    "gas": "0x1000",
+                    "gasUsed": "0x12",
+                    "input": "0x",
+                    "output": format!("0x{:064x}", 0x2a),
+                },
+                {
+                    "type": "CREATE",
+                    "from": address(0x20),
+                    "to": "0x46eba08f4182ee6a5660880b6bad94769f427355",
+                    "value": "0x0",
+                    "gas": "0x22b01",
+                    "gasUsed": "0xda",
+                    "input": "0x60ff60005360016000f3",
+                    "output": "0xff",
+                },
+                {
+                    "type": "CALL",
+                    "from": address(0x20),
+                    "to": address(0x40),
+                    "value": "0x0",
+                    "gas": "0x1000",
+                    "gasUsed": "0x36",
+                    "input": "0x",
+                    "output": revert_data,
+                    "error": "execution reverted",
+                    "revertReason": "nope",
+                },
+            ],
+        });
+        assert_eq!(trace(CallTracerConfig::default()), expected);
+    }
+
+    #[test]
+    fn only_top_call_drops_sub_calls() {
+        let trace = trace(CallTracerConfig {
+            only_top_call: true,
+            ..Default::default()
+        });
+        assert_eq!(trace["type"], "CALL");
+        assert!(trace.get("calls").is_none());
+    }
+}
@@ -0,0 +1,333 @@
+use crate::host::{CallInputs, CallOutcome, CreateInputs, CreateOutcome};
+use crate::{opcode, ExecutionResult, ExitReason, FrameState, Inspector, StepControl};
+use serde::Serialize;
+use std::io::{self, Write};
+
+/// One line per instruction, in the field order of the EIP-3155 examples.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct StepLine {
+    pc: usize,
+    op: u8,
+    gas: String,
+    gas_cost: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    memory: Option<String>,
+    mem_size: usize,
+    stack: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    return_data: Option<String>,
+    depth: usize,
+    refund: i64,
+    op_name: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SummaryLine {
+    output: String,
+    gas_used: String,
+    pass: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// An instruction that has not finished yet.
+struct PendingStep {
+    /// Its line, until written. That happens once its gas cost is known:
+    /// when it has run, or when its callee starts if it makes a call or
+    /// creation, so that the callee's lines follow it.
+    line: Option<StepLine>,
+    gas: u64,
+    refund: i64,
+    /// Gas charged by a call or creation instruction when it is suspended.
+    charged: Option<u64>,
+}
+
+/// Writes an EIP-3155 trace: one JSON object per instruction and a summary
+/// line, each on its own line. Lines are written as execution goes.
+///
+/// The output follows geth's conventions. Depth starts at 1, the refund is
+/// the transaction's refund counter, and the gas cost of a call includes the
+/// gas given to the callee. Memory and return data are left out unless
+/// enabled, as they make traces large, and when empty.
+///
+/// Write errors do not stop execution. The first one is returned by
+/// [`write_summary`](Self::write_summary).
+pub struct Eip3155Tracer<W: Write> {
+    out: W,
+    include_memory: bool,
+    include_return_data: bool,
+    /// The current instruction of every frame on the call stack, outermost first.
+    pending: Vec<PendingStep>,
+    error: Option<io::Error>,
+}
+
+impl<W: Write> Eip3155Tracer<W> {
+    pub fn new(out: W) -> Self {
+        Eip3155Tracer {
+            out,
+            include_memory: false,
+            include_return_data: false,
+            pending: Vec::new(),
+            error: None,
+        }
+    }
+
+    /// Adds each step's memory as a `memory` field.
+    pub fn with_memory(mut self, include: bool) -> Self {
+        self.include_memory = include;
+        self
+    }
+
+    /// Adds the last sub-call's output as a `returnData` field.
+    pub fn with_return_data(mut self, include: bool) -> Self {
+        self.include_return_data = include;
+        self
+    }
+
+    /// Ends the trace with the summary line for `result`, and reports the
+    /// first write error, if any.
+    pub fn write_summary(&mut self, result: &ExecutionResult) -> io::Result<()> {
+        let summary = SummaryLine {
+            output: hex::encode(&result.output[..]),
+            gas_used: format!("{:#x}", result.gas_used),
+            pass: result.is_success(),
+            error: match result.exit_reason {
+                ExitReason::Success => None,
+                ExitReason::Revert => Some("execution reverted".to_string()),
+                ExitReason::Halt(halt) => Some(halt.to_string()),
+            },
+        };
+        self.write_line(&summary);
+        self.out.flush()?;
+        match self.error.take() {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+
+    fn write_line<T: Serialize>(&mut self, line: &T) {
+        if self.error.is_some() {
+            return;
+        }
+        let mut bytes = serde_json::to_vec(line).expect("trace lines serialize");
+        bytes.push(b'\n');
+        if let Err(err) = self.out.write_all(&bytes) {
+            self.error = Some(err);
+        }
+    }
+
+    /// Writes the line of the suspended instruction whose callee is about to
+    /// start. `forwarded` is the gas given to a creation, which geth leaves
+    /// out of the instruction's cost, unlike that given to a call.
+    fn write_suspended(&mut self, forwarded: u64) {
+        let Some(step) = self.pending.last_mut() else {
+            return;
+        };
+        let (Some(charged), Some(mut line)) = (step.charged.take(), step.line.take()) else {
+            return;
+        };
+        line.gas_cost = format!("{:#x}", charged.saturating_sub(forwarded));
+        self.write_line(&line);
+    }
+}
+
+impl<W: Write> Inspector for Eip3155Tracer<W> {
+    fn step(&mut self, frame: &FrameState) -> StepControl {
+        // Frames only hold their own refund. Callers' refunds are frozen
+        // while they wait, so the running total is their sum.
+        let refund = frame.refund + self.pending.iter().map(|step| step.refund).sum::<i64>();
+        let op_name = match opcode::info(frame.opcode) {
+            Some(info) => info.name.to_string(),
+            None => format!("opcode {:#04x} not defined", frame.opcode),
+        };
+        let line = StepLine {
+            pc: frame.pc,
+            op: frame.opcode,
+            gas: format!("{:#x}", frame.gas_left),
+            gas_cost: String::new(),
+            memory: (self.include_memory && !frame.memory.is_empty())
+                .then(|| format!("0x{}", hex::encode(frame.memory))),
+            mem_size: frame.memory.len(),
+            stack: frame.stack.iter().map(|value| format!("{:#x}", value)).collect(),
+            return_data: (self.include_return_data && !frame.return_data.is_empty())
+                .then(|| format!("0x{}", hex::encode(frame.return_data))),
+            depth: frame.depth + 1,
+            refund,
+            op_name,
+        };
+        self.pending.push(PendingStep {
+            line: Some(line),
+            gas: frame.gas_left,
+            refund,
+            charged: None,
+        });
+        StepControl::Continue
+    }
+
+    fn step_end(&mut self, frame: &FrameState) {
+        let Some(step) = self.pending.pop() else {
+            return;
+        };
+        if let Some(mut line) = step.line {
+            line.gas_cost = format!("{:#x}", step.gas.saturating_sub(frame.gas_left));
+            self.write_line(&line);
+        }
+    }
+
+    fn step_suspend(&mut self, frame: &FrameState) {
+        if let Some(step) = self.pending.last_mut() {
+            step.charged = Some(step.gas.saturating_sub(frame.gas_left));
+        }
+    }
+
+    fn call(&mut self, _inputs: &mut CallInputs) -> Option<CallOutcome> {
+        self.write_suspended(0);
+        None
+    }
+
+    fn create(&mut self, inputs: &mut CreateInputs) -> Option<CreateOutcome> {
+        self.write_suspended(inputs.gas_limit);
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tracer::{clear_and_return, nested_calls};
+    use crate::StructLogger;
+    use serde_json::Value;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn matches_geth_json_trace() {
+        let mut tracer = Eip3155Tracer::new(Vec::new()).with_memory(true);
+        let result = clear_and_return().inspect(&mut tracer).unwrap();
+        tracer.write_summary(&result).unwrap();
+        let word = format!("{:064x}", 0x2a);
+        let expected = [
+            r#"{"pc":0,"op":96,"gas":"0x13498","gasCost":"0x3","memSize":0,"stack":[],"depth":1,"refund":0,"opName":"PUSH1"}"#.to_string(),
+            r#"{"pc":2,"op":96,"gas":"0x13495","gasCost":"0x3","memSize":0,"stack":["0x2a"],"depth":1,"refund":0,"opName":"PUSH1"}"#.to_string(),
+            r#"{"pc":4,"op":82,"gas":"0x13492","gasCost":"0x6","memSize":0,"stack":["0x2a","0x0"],"depth":1,"refund":0,"opName":"MSTORE"}"#.to_string(),
+            format!(r#"{{"pc":5,"op":96,"gas":"0x1348c","gasCost":"0x3","memory":"0x{word}","memSize":32,"stack":[],"depth":1,"refund":0,"opName":"PUSH1"}}"#),
+            format!(r#"{{"pc":7,"op":96,"gas":"0x13489","gasCost":"0x3","memory":"0x{word}","memSize":32,"stack":["0x0"],"depth":1,"refund":0,"opName":"PUSH1"}}"#),
+            format!(r#"{{"pc":9,"op":85,"gas":"0x13486","gasCost":"0x1388","memory":"0x{word}","memSize":32,"stack":["0x0","0x0"],"depth":1,"refund":0,"opName":"SSTORE"}}"#),
+            format!(r#"{{"pc":10,"op":96,"gas":"0x120fe","gasCost":"0x3","memory":"0x{word}","memSize":32,"stack":[],"depth":1,"refund":4800,"opName":"PUSH1"}}"#),
+            format!(r#"{{"pc":12,"op":96,"gas":"0x120fb","gasCost":"0x3","memory":"0x{word}","memSize":32,"stack":["0x20"],"depth":1,"refund":4800,"opName":"PUSH1"}}"#),
+            format!(r#"{{"pc":14,"op":243,"gas":"0x120f8","gasCost":"0x0","memory":"0x{word}","memSize":32,"stack":["0x20","0x0"],"depth":1,"refund":4800,"opName":"RETURN"}}"#),
+            format!(r#"{{"output":"{word}","gasUsed":"0x52e8","pass":true}}"#),
+        ];
+        let trace = String::from_utf8(tracer.into_inner()).unwrap();
+        assert_eq!(trace.lines().collect::<Vec<_>>(), expected);
+    }
+
+    /// A sink that can be read while the tracer writes to it.
+    #[derive(Clone, Default)]
+    struct Shared(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Shared {
+        fn lines(&self) -> Vec<Value> {
+            let bytes = self.0.borrow();
+            bytes
+                .split(|&b| b == b'\n')
+                .filter(|line| !line.is_empty())
+                .map(|line| serde_json::from_slice(line).unwrap())
+                .collect()
+        }
+    }
+
+    /// Checks, as each frame starts, that the instruction that made it has
+    /// already been written.
+    struct Streaming {
+        tracer: Eip3155Tracer<Shared>,
+        sink: Shared,
+        depth: usize,
+        /// Opcode of the last line written when each callee started.
+        callers: Vec<u64>,
+    }
+
+    impl Inspector for Streaming {
+        fn step(&mut self, frame: &FrameState) -> StepControl {
+            if frame.depth > self.depth {
+                let last = self.sink.lines().pop().unwrap();
+                self.callers.push(last["op"].as_u64().unwrap());
+            }
+            self.depth = frame.depth;
+            self.tracer.step(frame)
+        }
+
+        fn step_end(&mut self, frame: &FrameState) {
+            self.depth = frame.depth;
+            self.tracer.step_end(frame);
+        }
+
+        fn step_suspend(&mut self, frame: &FrameState) {
+            self.tracer.step_suspend(frame);
+        }
+
+        fn call(&mut self, inputs: &mut CallInputs) -> Option<CallOutcome> {
+            self.tracer.call(inputs)
+        }
+
+        fn create(&mut self, inputs: &mut CreateInputs) -> Option<CreateOutcome> {
+            self.tracer.create(inputs)
+        }
+    }
+
+    #[test]
+    fn writes_calls_and_creations_before_their_callees() {
+        let sink = Shared::default();
+        let mut streaming = Streaming {
+            tracer: Eip3155Tracer::new(sink.clone()),
+            sink: sink.clone(),
+            depth: 0,
+            callers: Vec::new(),
+        };
+        let result = nested_calls().inspect(&mut streaming).unwrap();
+        assert!(result.is_success());
+        assert_eq!(streaming.callers, [0xf1, 0xf0, 0xf1]);
+
+        // Calls cost the callee's gas too; creations leave it out, like geth.
+        let lines = sink.lines();
+        let costs = |op: u64| -> Vec<&str> {
+            lines
+                .iter()
+                .filter(|line| line["op"] == op)
+                .map(|line| line["gasCost"].as_str().unwrap())
+                .collect()
+        };
+        assert_eq!(costs(0xf1), ["0x1a28", "0x1a28"]);
+        assert_eq!(costs(0xf0), ["0x7d02"]);
+
+        // The struct logger, which waits for each instruction to finish,
+        // agrees on every cost.
+        let mut logger = StructLogger::new(Default::default());
+        let result = nested_calls().inspect(&mut logger).unwrap();
+        let logged = logger.into_json(&result);
+        let logged: Vec<String> = logged["structLogs"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|entry| format!("{:#x}", entry["gasCost"].as_u64().unwrap()))
+            .collect();
+        let streamed: Vec<&str> = lines.iter().map(|line| line["gasCost"].as_str().unwrap()).collect();
+        assert_eq!(streamed, logged);
+    }
+}
@@ -0,0 +1,97 @@
+//! Ready-made [`Inspector`](crate::Inspector)s that record execution in the
+//! formats other clients produce, so traces can be compared directly.
+//!
+//! [`Eip3155Tracer`] streams EIP-3155 JSON lines. The others build the
+//! results of geth's `debug_traceTransaction`: [`StructLogger`] for the
+//! default `structLogs`, [`CallTracer`] for `callTracer` and
+//! [`PrestateTracer`] for `prestateTracer`.
+
+mod call;
+mod eip3155;
+mod prestate;
+mod struct_log;
+
+pub use call::{CallTracer, CallTracerConfig};
+pub use eip3155::Eip3155Tracer;
+pub use prestate::PrestateTracer;
+pub use struct_log::{StructLogger, StructLoggerConfig};
+
+/// Stores 0x2a in memory, clears storage slot 0 and returns the word.
+#[cfg(test)]
+fn clear_and_return() -> crate::Evm {
+    use crate::{Account, Address, Evm, SpecId, State, TxEnv, TxKind};
+    use primitive_types::U256;
+
+    let contract = Address::from_low_u64_be(0x20);
+    let mut state = State::new();
+    state.insert(
+        contract,
+        Account {
+            // PUSH1 0x2a PUSH1 0 MSTORE PUSH1 0 PUSH1 0 SSTORE PUSH1 0x20 PUSH1 0 RETURN
+            code: hex::decode("602a600052600060005560206000f3").unwrap(),
+            storage: [(U256::zero(), U256::one())].into_iter().collect(),
+            ..Default::default()
+        },
+    );
+    Evm::builder()
+        .with_state(state)
+        .with_spec(SpecId::Cancun)
+        .with_tx_env(TxEnv {
+            caller: Address::from_low_u64_be(0x10),
+            kind: TxKind::Call(contract),
+            gas_limit: 100_000,
+            ..Default::default()
+        })
+        .build()
+}
+
+/// Contract 0x20 calls 0x30, which returns a word, creates a contract with
+/// one byte of code, then calls 0x40, which reverts with `Error("nope")`.
+#[cfg(test)]
+fn nested_calls() -> crate::Evm {
+    use crate::{assemble, Account, Address, Evm, SpecId, State, TxEnv, TxKind};
+    use primitive_types::U256;
+
+    let caller = "PUSH 0\nPUSH 0\nPUSH 0\nPUSH 0\nPUSH 0\nPUSH {to}\nPUSH 0x1000\nCALL\nPOP";
+    let code = [
+        caller.replace("{to}", "0x30"),
+        // Init code: PUSH1 0xff PUSH1 0 MSTORE8 PUSH1 1 PUSH1 0 RETURN
+        "PUSH10 0x60ff60005360016000f3\nPUSH 0\nMSTORE\nPUSH 10\nPUSH 22\nPUSH 0\nCREATE\nPOP".to_string(),
+        caller.replace("{to}", "0x40"),
+        "STOP".to_string(),
+    ]
+    .join("\n");
+    let returns = "PUSH 0x2a\nPUSH 0\nMSTORE\nPUSH 0x20\nPUSH 0\nRETURN";
+    let reverts = "PUSH32 0x08c379a000000000000000000000000000000000000000000000000000000000\nPUSH 0\nMSTORE
+        PUSH 0x20\nPUSH 4\nMSTORE\nPUSH 4\nPUSH 36\nMSTORE
+        PUSH32 0x6e6f706500000000000000000000000000000000000000000000000000000000\nPUSH 68\nMSTORE
+        PUSH 100\nPUSH 0\nREVERT";
+
+    let mut state = State::new();
+    for (address, source) in [(0x20, code.as_str()), (0x30, returns), (0x40, reverts)] {
+        state.insert(
+            Address::from_low_u64_be(address),
+            Account {
+                code: assemble(source).unwrap(),
+                ..Default::default()
+            },
+        );
+    }
+    state.insert(
+        Address::from_low_u64_be(0x10),
+        Account {
+            balance: U256::from(0x100000),
+            ..Default::default()
+        },
+    );
+    Evm::builder()
+        .with_state(state)
+        .with_spec(SpecId::Cancun)
+        .with_tx_env(TxEnv {
+            caller: Address::from_low_u64_be(0x10),
+            kind: TxKind::Call(Address::from_low_u64_be(0x20)),
+            gas_limit: 200_000,
+            ..Default::default()
+        })
+        .build()
+}
@@ -0,0 +1,257 @@
+use crate::host::{CallInputs, CallOutcome, CreateInputs, CreateOutcome};
+use crate::{u256_to_address, Account, Address, Env, FrameState, Inspector, State, StepControl, TxKind};
+use primitive_types::U256;
+use serde_json::{json, Map, Value};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Records the accounts and storage slots a transaction touched, and reports
+/// them as geth's `prestateTracer` does.
+///
+/// By default the result maps each touched account to its state before the
+/// transaction, leaving out contracts the transaction created. In diff mode
+/// it has `pre` and `post` maps holding only what changed: `pre` leaves out
+/// created accounts and `post` leaves out deleted ones, as well as unchanged
+/// fields and cleared slots.
+#[derive(Debug, Default)]
+pub struct PrestateTracer {
+    diff_mode: bool,
+    accounts: BTreeMap<Address, BTreeSet<U256>>,
+    /// Contracts the transaction created.
+    created: BTreeSet<Address>,
+}
+
+impl PrestateTracer {
+    pub fn new(diff_mode: bool) -> Self {
+        PrestateTracer {
+            diff_mode,
+            ..Default::default()
+        }
+    }
+
+    /// The accounts touched by the transaction in `env`, read from the state
+    /// before (`pre`) and after (`post`) it ran.
+    pub fn into_json(mut self, env: &Env, pre: &State, post: &State) -> Value {
+        // The sender, the recipient and the coinbase are always involved.
+        self.touch(env.tx.caller);
+        if let TxKind::Call(to) = env.tx.kind {
+            self.touch(to);
+        }
+        self.touch(env.block.coinbase);
+
+        if !self.diff_mode {
+            // Created contracts had no state before, unless they were funded.
+            let accounts: Map<String, Value> = self
+                .accounts
+                .iter()
+                .filter(|(address, _)| !self.created.contains(address) || pre.account(address).is_some())
+                .map(|(address, slots)| {
+                    let account = pre.account(address).cloned().unwrap_or_default();
+                    (format!("{:?}", address), account_json(&account, slots))
+                })
+                .collect();
+            return accounts.into();
+        }
+
+        let mut pre_accounts = Map::new();
+        let mut post_accounts = Map::new();
+        for (address, slots) in &self.accounts {
+            let before = pre.account(address).filter(|account| exists(account));
+            let after = post.account(address);
+            if before == after {
+                continue;
+            }
+            let key = format!("{:?}", address);
+            let empty = Account::default();
+            let old = before.unwrap_or(&empty);
+            let changed_slots: BTreeSet<U256> = slots
+                .iter()
+                .filter(|key| slot(old, key) != after.map(|account| slot(account, key)).unwrap_or_default())
+                .copied()
+                .collect();
+            if let Some(before) = before {
+                pre_accounts.insert(key.clone(), account_json(before, &changed_slots));
+            }
+            if let Some(after) = after {
+                let mut diff = Map::new();
+                if after.balance != old.balance {
+                    diff.insert("balance".into(), format!("{:#x}", after.balance).into());
+                }
+                if after.nonce != old.nonce {
+                    diff.insert("nonce".into(), after.nonce.into());
+                }
+                if after.code != old.code {
+                    diff.insert("code".into(), format!("0x{}", hex::encode(&after.code)).into());
+                }
+                let storage: Map<String, Value> = changed_slots
+                    .iter()
+                    .filter(|key| !slot(after, key).is_zero())
+                    .map(|key| (hash(*key), hash(slot(after, key)).into()))
+                    .collect();
+                if !storage.is_empty() {
+                    diff.insert("storage".into(), storage.into());
+                }
+                post_accounts.insert(key, diff.into());
+            }
+        }
+        json!({ "pre": pre_accounts, "post": post_accounts })
+    }
+
+    fn touch(&mut self, address: Address) {
+        self.accounts.entry(address).or_default();
+    }
+}
+
+impl Inspector for PrestateTracer {
+    fn step(&mut self, frame: &FrameState) -> StepControl {
+        let peek = |depth: usize| frame.stack.iter().rev().nth(depth).copied();
+        match frame.opcode {
+            // SLOAD and SSTORE
+            0x54 | 0x55 => {
+                if let Some(key) = peek(0) {
+                    self.accounts.entry(frame.address).or_default().insert(key);
+                }
+            }
+            // BALANCE, EXTCODESIZE, EXTCODECOPY, EXTCODEHASH and SELFDESTRUCT
+            0x31 | 0x3b | 0x3c | 0x3f | 0xff => {
+                if let Some(address) = peek(0) {
+                    self.touch(u256_to_address(address));
+                }
+            }
+            // CALL, CALLCODE, DELEGATECALL and STATICCALL
+            0xf1 | 0xf2 | 0xf4 | 0xfa => {
+                if let Some(address) = peek(1) {
+                    self.touch(u256_to_address(address));
+                }
+            }
+            _ => {}
+        }
+        StepControl::Continue
+    }
+
+    fn call(&mut self, inputs: &mut CallInputs) -> Option<CallOutcome> {
+        self.touch(inputs.caller);
+        self.touch(inputs.address);
+        self.touch(inputs.code_address);
+        None
+    }
+
+    fn create_end(&mut self, inputs: &CreateInputs, outcome: CreateOutcome) -> CreateOutcome {
+        self.touch(inputs.caller);
+        if let Some(address) = outcome.address {
+            self.touch(address);
+            self.created.insert(address);
+        }
+        outcome
+    }
+}
+
+/// Whether geth would consider the account to exist.
+fn exists(account: &Account) -> bool {
+    !account.is_empty() || !account.storage.is_empty()
+}
+
+fn slot(account: &Account, key: &U256) -> U256 {
+    account.storage.get(key).copied().unwrap_or_default()
+}
+
+fn hash(value: U256) -> String {
+    format!("0x{:064x}", value)
+}
+
+/// An account as geth prints it, with the storage `slots` that were touched.
+fn account_json(account: &Account, slots: &BTreeSet<U256>) -> Value {
+    let mut json = Map::new();
+    json.insert("balance".into(), format!("{:#x}", account.balance).into());
+    if account.nonce != 0 {
+        json.insert("nonce".into(), account.nonce.into());
+    }
+    if !account.code.is_empty() {
+        json.insert("code".into(), format!("0x{}", hex::encode(&account.code)).into());
+    }
+    if !slots.is_empty() {
+        let storage: Map<String, Value> = slots
+            .iter()
+            .map(|key| (hash(*key), hash(slot(account, key)).into()))
+            .collect();
+        json.insert("storage".into(), storage.into());
+    }
+    json.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tracer::{clear_and_return, nested_calls};
+
+    fn trace(mut evm: crate::Evm, diff_mode: bool) -> Value {
+        let pre = evm.state().clone();
+        let mut tracer = PrestateTracer::new(diff_mode);
+        evm.inspect(&mut tracer).unwrap();
+        tracer.into_json(evm.env(), &pre, evm.state())
+    }
+
+    fn address(low: u64) -> String {
+        format!("{:?}", Address::from_low_u64_be(low))
+    }
+
+    fn code(evm: &crate::Evm, low: u64) -> String {
+        let account = evm.state().account(&Address::from_low_u64_be(low)).unwrap();
+        format!("0x{}", hex::encode(&account.code))
+    }
+
+    #[test]
+    fn prestate_of_nested_calls() {
+        // The created contract is left out, as it did not exist before.
+        let expected = json!({
+            address(0): {"balance": "0x0"},
+            address(0x10): {"balance": "0x100000"},
+            address(0x20): {"balance": "0x0", "code": code(&nested_calls(), 0x20)},
+            address(0x30): {"balance": "0x0", "code": code(&nested_calls(), 0x30)},
+            address(0x40): {"balance": "0x0", "code": code(&nested_calls(), 0x40)},
+        });
+        assert_eq!(trace(nested_calls(), false), expected);
+    }
+
+    #[test]
+    fn diff_of_nested_calls() {
+        // The callees did not change and the created contract has no `pre`.
+        let created = "0x46eba08f4182ee6a5660880b6bad94769f427355";
+        let expected = json!({
+            "pre": {
+                address(0x10): {"balance": "0x100000"},
+                address(0x20): {"balance": "0x0", "code": code(&nested_calls(), 0x20)},
+            },
+            "post": {
+                address(0x10): {"nonce": 1},
+                address(0x20): {"nonce": 1},
+                created: {"code": "0xff", "nonce": 1},
+            },
+        });
+        assert_eq!(trace(nested_calls(), true), expected);
+    }
+
+    #[test]
+    fn storage_in_both_modes() {
+        let code = code(&clear_and_return(), 0x20);
+        let one = hash(U256::one());
+        let zero = hash(U256::zero());
+        let expected = json!({
+            address(0): {"balance": "0x0"},
+            address(0x10): {"balance": "0x0"},
+            address(0x20): {"balance": "0x0", "code": code, "storage": {zero.clone(): one.clone()}},
+        });
+        assert_eq!(trace(clear_and_return(), false), expected);
+
+        // The cleared slot is only in `pre`, leaving an empty `post` entry.
+        let expected = json!({
+            "pre": {
+                address(0x20): {"balance": "0x0", "code": code, "storage": {zero: one}},
+            },
+            "post": {
+                address(0x10): {"nonce": 1},
+                address(0x20): {},
+            },
+        });
+        assert_eq!(trace(clear_and_return(), true), expected);
+    }
+}
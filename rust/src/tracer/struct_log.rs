@@ -0,0 +1,256 @@
+use crate::host::{CallInputs, CallOutcome, CreateInputs, CreateOutcome};
+use crate::{opcode, Address, ExecutionResult, ExitReason, FrameState, Halt, Inspector, StepControl};
+use primitive_types::U256;
+use serde_json::{json, Map, Value};
+use std::collections::{BTreeMap, HashMap};
+
+/// Options of geth's struct logger. The defaults match geth's.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StructLoggerConfig {
+    pub disable_stack: bool,
+    pub disable_storage: bool,
+    pub enable_memory: bool,
+    pub enable_return_data: bool,
+}
+
+/// An instruction whose log entry waits for its gas cost.
+struct OpenStep {
+    index: usize,
+    gas: u64,
+    /// Gas handed back by the call or creation the instruction made.
+    returned: u64,
+    /// Gas given to the creation the instruction made. Unlike for calls,
+    /// geth leaves it out of the instruction's cost.
+    forwarded: u64,
+    refund: i64,
+    /// The slot an SLOAD reads.
+    sload_key: Option<U256>,
+}
+
+/// A call or creation in progress.
+struct OpenFrame {
+    is_create: bool,
+    /// Log entry of the frame's latest instruction.
+    last_step: Option<usize>,
+}
+
+/// Records the `structLogs` trace that geth's `debug_traceTransaction`
+/// returns without a named tracer.
+#[derive(Default)]
+pub struct StructLogger {
+    config: StructLoggerConfig,
+    logs: Vec<Map<String, Value>>,
+    /// The current instruction of every frame on the call stack, outermost first.
+    steps: Vec<OpenStep>,
+    frames: Vec<OpenFrame>,
+    /// Slots each contract has read or written so far, as geth reports them.
+    storage: HashMap<Address, BTreeMap<U256, U256>>,
+}
+
+impl StructLogger {
+    pub fn new(config: StructLoggerConfig) -> Self {
+        StructLogger {
+            config,
+            ..Default::default()
+        }
+    }
+
+    /// The trace of the transaction that ended with `result`.
+    pub fn into_json(self, result: &ExecutionResult) -> Value {
+        // Output is kept on success and revert only, like the revert reason.
+        let output = match result.exit_reason {
+            ExitReason::Halt(_) => String::new(),
+            _ => hex::encode(&result.output[..]),
+        };
+        json!({
+            "gas": result.gas_used,
+            "failed": !result.is_success(),
+            "returnValue": output,
+            "structLogs": self.logs,
+        })
+    }
+
+    fn returned(&mut self, gas_left: u64, forwarded: u64) {
+        if let Some(step) = self.steps.last_mut() {
+            step.returned = step.returned.saturating_add(gas_left);
+            step.forwarded = step.forwarded.saturating_add(forwarded);
+        }
+    }
+
+    /// Puts the error of a halted frame on the instruction that caused it.
+    /// Creations whose code is rejected after their last instruction ran,
+    /// and calls refused before they started, have no such instruction.
+    fn frame_end(&mut self, halt: Option<Halt>) {
+        let Some(frame) = self.frames.pop() else {
+            return;
+        };
+        let (Some(halt), Some(index)) = (halt, frame.last_step) else {
+            return;
+        };
+        let entry = &mut self.logs[index];
+        if frame.is_create && entry["op"] == "RETURN" {
+            return;
+        }
+        entry.insert("error".into(), halt.to_string().into());
+    }
+}
+
+impl Inspector for StructLogger {
+    fn step(&mut self, frame: &FrameState) -> StepControl {
+        let refund = frame.refund + self.steps.iter().map(|step| step.refund).sum::<i64>();
+        let op = match opcode::info(frame.opcode) {
+            Some(info) => info.name.to_string(),
+            None => format!("opcode {:#04x} not defined", frame.opcode),
+        };
+
+        let mut entry = Map::new();
+        entry.insert("pc".into(), frame.pc.into());
+        entry.insert("op".into(), op.into());
+        entry.insert("gas".into(), frame.gas_left.into());
+        entry.insert("gasCost".into(), 0.into());
+        entry.insert("depth".into(), (frame.depth + 1).into());
+        if !self.config.disable_stack {
+            let stack: Vec<String> = frame.stack.iter().map(|value| format!("{:#x}", value)).collect();
+            entry.insert("stack".into(), stack.into());
+        }
+        if self.config.enable_memory && !frame.memory.is_empty() {
+            let memory: Vec<String> = frame.memory.chunks(32).map(hex::encode).collect();
+            entry.insert("memory".into(), memory.into());
+        }
+        if self.config.enable_return_data && !frame.return_data.is_empty() {
+            entry.insert(
+                "returnData".into(),
+                format!("0x{}", hex::encode(frame.return_data)).into(),
+            );
+        }
+        if refund != 0 {
+            entry.insert("refund".into(), refund.into());
+        }
+        // SSTORE shows the slot as written. SLOAD's value is only known once
+        // it has run, in `step_end`.
+        if frame.opcode == 0x55 && !self.config.disable_storage && frame.stack.len() >= 2 {
+            let key = frame.stack[frame.stack.len() - 1];
+            let value = frame.stack[frame.stack.len() - 2];
+            let storage = self.storage.entry(frame.address).or_default();
+            storage.insert(key, value);
+            entry.insert("storage".into(), storage_json(storage));
+        }
+
+        let index = self.logs.len();
+        self.logs.push(entry);
+        self.steps.push(OpenStep {
+            index,
+            gas: frame.gas_left,
+            returned: 0,
+            forwarded: 0,
+            refund,
+            sload_key: frame.stack.last().copied().filter(|_| frame.opcode == 0x54),
+        });
+        if let Some(open) = self.frames.last_mut() {
+            open.last_step = Some(index);
+        }
+        StepControl::Continue
+    }
+
+    fn step_end(&mut self, frame: &FrameState) {
+        let Some(step) = self.steps.pop() else {
+            return;
+        };
+        let cost = step
+            .gas
+            .saturating_add(step.returned)
+            .saturating_sub(frame.gas_left)
+            .saturating_sub(step.forwarded);
+        let entry = &mut self.logs[step.index];
+        entry.insert("gasCost".into(), cost.into());
+        if !self.config.disable_storage {
+            if let (Some(key), Some(value)) = (step.sload_key, frame.stack.last()) {
+                let storage = self.storage.entry(frame.address).or_default();
+                storage.insert(key, *value);
+                entry.insert("storage".into(), storage_json(storage));
+            }
+        }
+    }
+
+    fn call(&mut self, _inputs: &mut CallInputs) -> Option<CallOutcome> {
+        self.frames.push(OpenFrame {
+            is_create: false,
+            last_step: None,
+        });
+        None
+    }
+
+    fn call_end(&mut self, _inputs: &CallInputs, outcome: CallOutcome) -> CallOutcome {
+        self.frame_end(outcome.halt);
+        self.returned(outcome.gas_left, 0);
+        outcome
+    }
+
+    fn create(&mut self, _inputs: &mut CreateInputs) -> Option<CreateOutcome> {
+        self.frames.push(OpenFrame {
+            is_create: true,
+            last_step: None,
+        });
+        None
+    }
+
+    fn create_end(&mut self, inputs: &CreateInputs, outcome: CreateOutcome) -> CreateOutcome {
+        self.frame_end(outcome.result.halt);
+        self.returned(outcome.result.gas_left, inputs.gas_limit);
+        outcome
+    }
+}
+
+fn storage_json(storage: &BTreeMap<U256, U256>) -> Value {
+    storage
+        .iter()
+        .map(|(key, value)| (format!("{:064x}", key), Value::from(format!("{:064x}", value))))
+        .collect::<Map<String, Value>>()
+        .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tracer::clear_and_return;
+
+    #[test]
+    fn matches_geth_struct_logs() {
+        let mut logger = StructLogger::new(StructLoggerConfig {
+            enable_memory: true,
+            ..Default::default()
+        });
+        let result = clear_and_return().inspect(&mut logger).unwrap();
+        let word = format!("{:064x}", 0x2a);
+        let zero = format!("{:064x}", 0);
+        let expected = json!({
+            "gas": 21224,
+            "failed": false,
+            "returnValue": word,
+            "structLogs": [
+                {"pc": 0, "op": "PUSH1", "gas": 79000, "gasCost": 3, "depth": 1, "stack": []},
+                {"pc": 2, "op": "PUSH1", "gas": 78997, "gasCost": 3, "depth": 1, "stack": ["0x2a"]},
+                {"pc": 4, "op": "MSTORE", "gas": 78994, "gasCost": 6, "depth": 1, "stack": ["0x2a", "0x0"]},
+                {"pc": 5, "op": "PUSH1", "gas": 78988, "gasCost": 3, "depth": 1, "stack": [], "memory": [word]},
+                {"pc": 7, "op": "PUSH1", "gas": 78985, "gasCost": 3, "depth": 1, "stack": ["0x0"], "memory": [word]},
+                {
+                    "pc": 9, "op": "SSTORE", "gas": 78982, "gasCost": 5000, "depth": 1,
+                    "stack": ["0x0", "0x0"], "memory": [word], "storage": {zero.clone(): zero}
+                },
+                {
+                    "pc": 10, "op": "PUSH1", "gas": 73982, "gasCost": 3, "depth": 1,
+                    "stack": [], "memory": [word], "refund": 4800
+                },
+                {
+                    "pc": 12, "op": "PUSH1", "gas": 73979, "gasCost": 3, "depth": 1,
+                    "stack": ["0x20"], "memory": [word], "refund": 4800
+                },
+                {
+                    "pc": 14, "op": "RETURN", "gas": 73976, "gasCost": 0, "depth": 1,
+                    "stack": ["0x20", "0x0"], "memory": [word], "refund": 4800
+                },
+            ],
+        });
+        assert_eq!(logger.into_json(&result), expected);
+    }
+}
@@ -0,0 +1,408 @@
+//! Signed transactions as blocks carry them: RLP decoding and encoding, and
+//! recovery of the sender from the signature.
+
+use crate::{gas, keccak256, AccessListItem, Address, Bytes, SpecId, TxEnv, TxKind};
+use k256::ecdsa::{RecoveryId, Signature as EcdsaSignature, SigningKey, VerifyingKey};
+use primitive_types::{H256, U256};
+use rlp::{DecoderError, Rlp, RlpStream};
+
+/// The version byte of blob hashes that commit to KZG commitments.
+pub(crate) const VERSIONED_HASH_VERSION_KZG: u8 = 0x01;
+
+/// The EIP-2718 transaction type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TxType {
+    Legacy = 0,
+    /// EIP-2930.
+    AccessList = 1,
+    /// EIP-1559.
+    DynamicFee = 2,
+    /// EIP-4844.
+    Blob = 3,
+}
+
+impl TxType {
+    /// The fork that introduced the type.
+    pub fn spec(self) -> SpecId {
+        match self {
+            TxType::Legacy => SpecId::Frontier,
+            TxType::AccessList => SpecId::Berlin,
+            TxType::DynamicFee => SpecId::London,
+            TxType::Blob => SpecId::Cancun,
+        }
+    }
+}
+
+/// An ECDSA signature over a transaction's signing hash.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Signature {
+    /// `v` of a legacy transaction: 27 or 28, or `35 + 2 * chain_id` plus
+    /// the y parity (EIP-155). The y parity alone for typed transactions.
+    pub v: u64,
+    pub r: U256,
+    pub s: U256,
+}
+
+/// A signed transaction of any type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Transaction {
+    pub tx_type: TxType,
+    /// `None` for legacy transactions signed without replay protection.
+    pub chain_id: Option<u64>,
+    pub nonce: u64,
+    /// Gas price, or the max fee per gas from EIP-1559 on.
+    pub gas_price: U256,
+    pub max_priority_fee_per_gas: Option<U256>,
+    pub gas_limit: u64,
+    pub kind: TxKind,
+    pub value: U256,
+    pub data: Bytes,
+    pub access_list: Vec<AccessListItem>,
+    pub max_fee_per_blob_gas: Option<U256>,
+    pub blob_hashes: Vec<H256>,
+    pub signature: Signature,
+}
+
+impl Transaction {
+    /// Decodes the EIP-2718 encoding: an RLP list for legacy transactions,
+    /// or the type byte followed by an RLP list.
+    pub fn decode(bytes: &[u8]) -> Result<Self, String> {
+        let (tx_type, payload) = match bytes.first() {
+            Some(0x01) => (TxType::AccessList, &bytes[1..]),
+            Some(0x02) => (TxType::DynamicFee, &bytes[1..]),
+            Some(0x03) => (TxType::Blob, &bytes[1..]),
+            Some(0xc0..) => (TxType::Legacy, bytes),
+            Some(byte) => return Err(format!("unsupported transaction type {:#04x}", byte)),
+            None => return Err("empty transaction".to_string()),
+        };
+        let rlp = Rlp::new(payload);
+        let info = rlp
+            .payload_info()
+            .map_err(|err| format!("invalid transaction: {}", err))?;
+        if info.header_len + info.value_len != payload.len() {
+            return Err("trailing bytes after transaction".to_string());
+        }
+        decode_fields(tx_type, &rlp).map_err(|err| format!("invalid transaction: {}", err))
+    }
+
+    /// Decodes a transaction from the body of a block, where typed ones are
+    /// wrapped in an RLP string.
+    pub fn decode_rlp(rlp: &Rlp) -> Result<Self, String> {
+        match rlp.is_list() {
+            true => Transaction::decode(rlp.as_raw()),
+            false => Transaction::decode(rlp.data().map_err(|err| err.to_string())?),
+        }
+    }
+
+    /// The EIP-2718 encoding, which the transaction hash is taken of.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut stream = RlpStream::new();
+        self.append_fields(&mut stream, true);
+        self.envelope(stream.out().to_vec())
+    }
+
+    /// Appends the transaction to a block body.
+    pub fn rlp_append(&self, stream: &mut RlpStream) {
+        match self.tx_type {
+            TxType::Legacy => stream.append_raw(&self.encode(), 1),
+            _ => stream.append(&self.encode()),
+        };
+    }
+
+    pub fn hash(&self) -> H256 {
+        H256(keccak256(&self.encode()))
+    }
+
+    /// The hash the sender signed.
+    pub fn signing_hash(&self) -> H256 {
+        let mut stream = RlpStream::new();
+        self.append_fields(&mut stream, false);
+        H256(keccak256(&self.envelope(stream.out().to_vec())))
+    }
+
+    /// Recovers the sender from the signature. Signatures with a high `s`
+    /// are rejected (EIP-2).
+    pub fn recover_sender(&self) -> Result<Address, String> {
+        let parity = match (self.tx_type, self.signature.v) {
+            (TxType::Legacy, v @ (27 | 28)) => v - 27,
+            (TxType::Legacy, v) if v >= 35 => (v - 35) % 2,
+            (TxType::Legacy, v) => return Err(format!("invalid signature v {}", v)),
+            (_, v @ (0 | 1)) => v,
+            (_, v) => return Err(format!("invalid signature y parity {}", v)),
+        };
+        let invalid = |err: k256::ecdsa::Error| format!("invalid signature: {}", err);
+        let mut bytes = [0u8; 64];
+        self.signature.r.to_big_endian(&mut bytes[..32]);
+        self.signature.s.to_big_endian(&mut bytes[32..]);
+        let signature = EcdsaSignature::from_slice(&bytes).map_err(invalid)?;
+        if signature.normalize_s().is_some() {
+            return Err("invalid signature: s is above half the curve order".to_string());
+        }
+        let id = RecoveryId::from_byte(parity as u8).ok_or("invalid signature recovery id")?;
+        let key =
+            VerifyingKey::recover_from_prehash(self.signing_hash().as_bytes(), &signature, id).map_err(invalid)?;
+        let point = key.to_encoded_point(false);
+        Ok(Address::from_slice(&keccak256(&point.as_bytes()[1..])[12..]))
+    }
+
+    /// Signs the transaction with a secp256k1 secret key. Legacy transactions
+    /// with a chain id are signed with replay protection (EIP-155).
+    pub fn sign(&mut self, secret_key: &[u8]) -> Result<(), String> {
+        let key = SigningKey::from_slice(secret_key).map_err(|err| format!("invalid secret key: {}", err))?;
+        let (signature, id) = key
+            .sign_prehash_recoverable(self.signing_hash().as_bytes())
+            .map_err(|err| format!("cannot sign: {}", err))?;
+        let parity = u64::from(id.is_y_odd());
+        let bytes = signature.to_bytes();
+        self.signature = Signature {
+            v: match (self.tx_type, self.chain_id) {
+                (TxType::Legacy, Some(chain_id)) => 35 + 2 * chain_id + parity,
+                (TxType::Legacy, None) => 27 + parity,
+                _ => parity,
+            },
+            r: U256::from_big_endian(&bytes[..32]),
+            s: U256::from_big_endian(&bytes[32..]),
+        };
+        Ok(())
+    }
+
+    /// Checks what rules the transaction out of any block under `spec` on
+    /// chain `chain_id`, whatever the state: its type, its replay protection
+    /// and the shape of its blobs.
+    pub fn validate(&self, spec: SpecId, chain_id: u64) -> Result<(), String> {
+        if !spec.is_enabled(self.tx_type.spec()) {
+            return Err(format!(
+                "transaction type {:?} not supported before {}",
+                self.tx_type,
+                self.tx_type.spec()
+            ));
+        }
+        match self.chain_id {
+            Some(id) if id != chain_id => return Err(format!("invalid chain id: have {}, want {}", id, chain_id)),
+            Some(_) if !spec.is_enabled(SpecId::SpuriousDragon) => {
+                return Err("replay protection not supported before SpuriousDragon".to_string())
+            }
+            _ => {}
+        }
+        if self.tx_type == TxType::Blob {
+            if self.kind == TxKind::Create {
+                return Err("blob transaction cannot create a contract".to_string());
+            }
+            if self.blob_hashes.is_empty() {
+                return Err("blob transaction without blobs".to_string());
+            }
+            if self
+                .blob_hashes
+                .iter()
+                .any(|hash| hash[0] != VERSIONED_HASH_VERSION_KZG)
+            {
+                return Err("blob hash with an unsupported version".to_string());
+            }
+        }
+        Ok(())
+    }
+
+    /// The blob gas the transaction's blobs consume (EIP-4844).
+    pub fn blob_gas(&self) -> u64 {
+        gas::BLOB_GAS_PER_BLOB * self.blob_hashes.len() as u64
+    }
+
+    /// The execution environment of the transaction sent by `caller`.
+    pub fn tx_env(&self, caller: Address) -> TxEnv {
+        TxEnv {
+            caller,
+            kind: self.kind,
+            value: self.value,
+            data: self.data.clone(),
+            gas_limit: self.gas_limit,
+            gas_price: self.gas_price,
+            gas_priority_fee: self.max_priority_fee_per_gas,
+            nonce: Some(self.nonce),
+            access_list: self.access_list.clone(),
+            blob_hashes: self.blob_hashes.clone(),
+            max_fee_per_blob_gas: self.max_fee_per_blob_gas,
+            origin: None,
+        }
+    }
+
+    fn envelope(&self, payload: Vec<u8>) -> Vec<u8> {
+        match self.tx_type {
+            TxType::Legacy => payload,
+            tx_type => [&[tx_type as u8][..], &payload].concat(),
+        }
+    }
+
+    /// Appends the fields as an RLP list, with the signature or, for the
+    /// signing hash, without it.
+    fn append_fields(&self, stream: &mut RlpStream, signed: bool) {
+        let legacy_replay_protected = self.tx_type == TxType::Legacy && self.chain_id.is_some();
+        let count = match self.tx_type {
+            TxType::Legacy => 6,
+            TxType::AccessList => 8,
+            TxType::DynamicFee => 9,
+            TxType::Blob => 11,
+        } + if signed || legacy_replay_protected { 3 } else { 0 };
+        stream.begin_list(count);
+        if self.tx_type != TxType::Legacy {
+            stream.append(&self.chain_id.unwrap_or_default());
+        }
+        stream.append(&self.nonce);
+        if matches!(self.tx_type, TxType::DynamicFee | TxType::Blob) {
+            stream.append(&self.max_priority_fee_per_gas.unwrap_or_default());
+        }
+        stream.append(&self.gas_price);
+        stream.append(&self.gas_limit);
+        match self.kind {
+            TxKind::Call(to) => stream.append(&to),
+            TxKind::Create => stream.append_empty_data(),
+        };
+        stream.append(&self.value);
+        stream.append(&self.data.0);
+        if self.tx_type != TxType::Legacy {
+            stream.begin_list(self.access_list.len());
+            for item in &self.access_list {
+                stream.begin_list(2);
+                stream.append(&item.address);
+                stream.begin_list(item.storage_keys.len());
+                for key in &item.storage_keys {
+                    let mut slot = [0u8; 32];
+                    key.to_big_endian(&mut slot);
+                    stream.append(&H256(slot));
+                }
+            }
+        }
+        if self.tx_type == TxType::Blob {
+            stream.append(&self.max_fee_per_blob_gas.unwrap_or_default());
+            stream.append_list(&self.blob_hashes);
+        }
+        if signed {
+            stream.append(&self.signature.v);
+            stream.append(&self.signature.r);
+            stream.append(&self.signature.s);
+        } else if legacy_replay_protected {
+            stream.append(&self.chain_id.unwrap_or_default());
+            stream.append(&0u8);
+            stream.append(&0u8);
+        }
+    }
+}
+
+fn decode_fields(tx_type: TxType, rlp: &Rlp) -> Result<Transaction, DecoderError> {
+    let expected = match tx_type {
+        TxType::Legacy => 9,
+        TxType::AccessList => 11,
+        TxType::DynamicFee => 12,
+        TxType::Blob => 14,
+    };
+    if rlp.item_count()? != expected {
+        return Err(DecoderError::RlpIncorrectListLen);
+    }
+
+    let mut index = 0;
+    let mut next = || {
+        index += 1;
+        rlp.at(index - 1)
+    };
+    let chain_id = match tx_type {
+        TxType::Legacy => None,
+        _ => Some(next()?.as_val()?),
+    };
+    let nonce = next()?.as_val()?;
+    let max_priority_fee_per_gas = match tx_type {
+        TxType::DynamicFee | TxType::Blob => Some(next()?.as_val()?),
+        _ => None,
+    };
+    let gas_price = next()?.as_val()?;
+    let gas_limit = next()?.as_val()?;
+    let to = next()?;
+    let kind = match to.is_empty() {
+        true => TxKind::Create,
+        false => TxKind::Call(to.as_val()?),
+    };
+    let value = next()?.as_val()?;
+    let data = Bytes(next()?.as_val()?);
+    let mut access_list = Vec::new();
+    if tx_type != TxType::Legacy {
+        for item in next()?.iter() {
+            let storage_keys = item
+                .at(1)?
+                .iter()
+                .map(|key| key.as_val::<H256>().map(|key| U256::from_big_endian(key.as_bytes())))
+                .collect::<Result<_, _>>()?;
+            access_list.push(AccessListItem {
+                address: item.val_at(0)?,
+                storage_keys,
+            });
+        }
+    }
+    let (max_fee_per_blob_gas, blob_hashes) = match tx_type {
+        TxType::Blob => (Some(next()?.as_val()?), next()?.as_list()?),
+        _ => (None, Vec::new()),
+    };
+    let signature = Signature {
+        v: next()?.as_val()?,
+        r: next()?.as_val()?,
+        s: next()?.as_val()?,
+    };
+    let chain_id = match (tx_type, signature.v) {
+        (TxType::Legacy, v) if v >= 35 => Some((v - 35) / 2),
+        _ => chain_id,
+    };
+    Ok(Transaction {
+        tx_type,
+        chain_id,
+        nonce,
+        gas_price,
+        max_priority_fee_per_gas,
+        gas_limit,
+        kind,
+        value,
+        data,
+        access_list,
+        max_fee_per_blob_gas,
+        blob_hashes,
+        signature,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_address;
+
+    /// The example transaction of EIP-155, signed with key 0x4646...46.
+    const EIP155_EXAMPLE: &str = "f86c098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a76400008025a028ef61340bd939bc2195fe537567866003e1a15d3c71ff63e1590620aa636276a067cbe9d8997f761aecb703304b3800ccf555c9f3dc64214b297fb1966a3b6d83";
+
+    #[test]
+    fn eip155_example() {
+        let raw = hex::decode(EIP155_EXAMPLE).unwrap();
+        let tx = Transaction::decode(&raw).unwrap();
+        assert_eq!(tx.chain_id, Some(1));
+        assert_eq!(
+            tx.signing_hash(),
+            crate::parse_h256("0xdaf5a779ae972f972197303d7b574746c7ef83eadac0f2791ad23db92e4c8e53").unwrap()
+        );
+        assert_eq!(
+            tx.recover_sender().unwrap(),
+            parse_address("0x9d8a62f656a8d1615c1294fd71e9cfb3e4855a4f").unwrap()
+        );
+        assert_eq!(tx.encode(), raw);
+    }
+
+    #[test]
+    fn signs_deterministically() {
+        // Signing the EIP-155 example again gives back its signature (RFC 6979).
+        let raw = hex::decode(EIP155_EXAMPLE).unwrap();
+        let mut tx = Transaction::decode(&raw).unwrap();
+        tx.signature = Signature::default();
+        tx.sign(&[0x46; 32]).unwrap();
+        assert_eq!(tx.encode(), raw);
+    }
+
+    #[test]
+    fn rejects_malformed_input() {
+        assert!(Transaction::decode(&[]).is_err());
+        assert!(Transaction::decode(&[0x05, 0xc0]).is_err());
+        assert!(Transaction::decode(&[0x02, 0xc0]).is_err());
+    }
+}
@@ -0,0 +1,116 @@
+//! Merkle Patricia trie roots, and the other commitments block headers and
+//! test fixtures make to the state and to logs.
+
+use crate::{keccak256, Account, Log, State};
+use hash256_std_hasher::Hash256StdHasher;
+use hash_db::Hasher;
+use primitive_types::{H256, U256};
+use rlp::{Encodable, RlpStream};
+use std::collections::HashMap;
+
+/// The trie hasher: Keccak-256, as everywhere in Ethereum.
+struct KeccakHasher;
+
+impl Hasher for KeccakHasher {
+    type Out = H256;
+    type StdHasher = Hash256StdHasher;
+    const LENGTH: usize = 32;
+
+    fn hash(data: &[u8]) -> H256 {
+        H256(keccak256(data))
+    }
+}
+
+/// Root of the world state trie, keyed by hashed address.
+pub fn state_root(state: &State) -> H256 {
+    triehash::sec_trie_root::<KeccakHasher, _, _, _>(
+        state.iter().map(|(address, account)| (address, account_rlp(account))),
+    )
+}
+
+/// Root of an account's storage trie, keyed by hashed slot. Zero slots are
+/// left out, as they do not exist.
+pub fn storage_root(storage: &HashMap<U256, U256>) -> H256 {
+    triehash::sec_trie_root::<KeccakHasher, _, _, _>(storage.iter().filter(|(_, value)| !value.is_zero()).map(
+        |(key, value)| {
+            let mut slot = [0u8; 32];
+            key.to_big_endian(&mut slot);
+            (slot, rlp::encode(value))
+        },
+    ))
+}
+
+/// Root of a trie keyed by the RLP of each item's index, as used for the
+/// transactions, receipts and withdrawals of a block.
+pub fn ordered_trie_root<I>(items: I) -> H256
+where
+    I: IntoIterator,
+    I::Item: AsRef<[u8]>,
+{
+    triehash::ordered_trie_root::<KeccakHasher, _>(items)
+}
+
+/// Hash of the RLP list of `logs`, which state tests check instead of the
+/// logs themselves.
+pub fn logs_hash(logs: &[Log]) -> H256 {
+    H256(keccak256(&rlp::encode_list(logs)))
+}
+
+/// Encodes as `[address, [topics...], data]`.
+impl Encodable for Log {
+    fn rlp_append(&self, stream: &mut RlpStream) {
+        stream.begin_list(3);
+        stream.append(&self.address);
+        stream.append_list(&self.topics);
+        stream.append(&self.data.0);
+    }
+}
+
+fn account_rlp(account: &Account) -> Vec<u8> {
+    let mut stream = RlpStream::new_list(4);
+    stream.append(&account.nonce);
+    stream.append(&account.balance);
+    stream.append(&storage_root(&account.storage));
+    stream.append(&H256(keccak256(&account.code)));
+    stream.out().to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_address;
+
+    fn hash(hex: &str) -> H256 {
+        crate::parse_h256(hex).unwrap()
+    }
+
+    #[test]
+    fn empty_roots() {
+        let empty = hash("0x56e81f171bcc55a6ff8345e692c0f86e5b48e01b996cadc001622fb5e363b421");
+        assert_eq!(state_root(&State::new()), empty);
+        assert_eq!(storage_root(&HashMap::new()), empty);
+        assert_eq!(ordered_trie_root(Vec::<Vec<u8>>::new()), empty);
+        // Zero slots do not count.
+        assert_eq!(storage_root(&HashMap::from([(U256::one(), U256::zero())])), empty);
+        assert_eq!(
+            logs_hash(&[]),
+            hash("0x1dcc4de8dec75d7aab85b567b6ccd41ad312451b948a7413f0a142fd40d49347")
+        );
+    }
+
+    #[test]
+    fn single_account_root() {
+        // The state of a chain whose genesis only funds 0x...01 with 1 wei.
+        let mut state = State::new();
+        state.account_mut(&parse_address("0x01").unwrap()).balance = U256::one();
+        let leaf = {
+            let mut stream = RlpStream::new_list(2);
+            let path = keccak256(parse_address("0x01").unwrap().as_bytes());
+            // A lone leaf holds the whole key path, flagged with 0x20.
+            stream.append(&[&[0x20][..], &path[..]].concat());
+            stream.append(&account_rlp(state.account(&parse_address("0x01").unwrap()).unwrap()));
+            stream.out().to_vec()
+        };
+        assert_eq!(state_root(&state), H256(keccak256(&leaf)));
+    }
+}